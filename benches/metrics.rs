@@ -0,0 +1,79 @@
+//! Compares the accuracy and speed of the distance metrics on the test corpus.
+//!
+//! Each sample in `tests/` is split in two halves: the first one trains the category, the
+//! second one is cut into snippets of a few words which are classified with each metric.
+//!
+//! Run with `cargo bench --bench metrics`.
+use std::time::Instant;
+use textcat::category::Categories;
+use textcat::ngram::Metric;
+
+const SAMPLES: &[&str] = &["english", "spanish"];
+const SNIPPET_WORDS: &[usize] = &[5, 10, 20];
+const ROUNDS: usize = 20;
+
+fn halves(text: &str) -> (String, String) {
+    let lines = text.lines().collect::<Vec<_>>();
+    let (train, test) = lines.split_at(lines.len() / 2);
+    (train.join("\n"), test.join("\n"))
+}
+
+fn main() {
+    let corpus = SAMPLES
+        .iter()
+        .map(|name| {
+            let path =
+                format!("{}/tests/{}.sample", env!("CARGO_MANIFEST_DIR"), name);
+            let text = std::fs::read_to_string(&path).expect("read sample");
+            (name.to_string(), halves(&text))
+        })
+        .collect::<Vec<_>>();
+
+    let metrics = [
+        Metric::OutOfPlace,
+        Metric::JensenShannon,
+        Metric::ChiSquare,
+        Metric::Cosine,
+    ];
+
+    for metric in &metrics {
+        let mut categories: Categories<String> = Categories::new();
+        categories.set_metric(*metric);
+        for (name, (train, _)) in corpus.iter() {
+            categories.add_category(name.clone(), train);
+        }
+
+        for size in SNIPPET_WORDS {
+            let snippets = corpus
+                .iter()
+                .flat_map(|(name, (_, test))| {
+                    let words = test.split_whitespace().collect::<Vec<_>>();
+                    words
+                        .chunks(*size)
+                        .map(|chunk| (name.clone(), chunk.join(" ")))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            let mut correct = 0;
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                correct = snippets
+                    .iter()
+                    .filter(|(name, snippet)| {
+                        categories.get_category(snippet).as_ref() == Some(name)
+                    })
+                    .count();
+            }
+            let elapsed = start.elapsed() / (ROUNDS * snippets.len()) as u32;
+
+            println!(
+                "{:<14} {:>2} words: {:>5.1}% accurate, {:>8.1?} per text",
+                format!("{:?}", metric),
+                size,
+                100.0 * correct as f64 / snippets.len() as f64,
+                elapsed,
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Speed of ngram extraction, compared to the previous implementation which allocated a
+//! String for every ngram of every length at every position.
+//!
+//! Run with `cargo bench --bench ngrams`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::collections::HashMap;
+use std::hint::black_box;
+use textcat::ngram::Ngrams;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Ngrams length, as in `Ngrams::new`
+const LENGTH: usize = 5;
+
+/// Previous implementation of `Ngrams::parse_text`, as a baseline
+fn allocating(text: &str, length: usize) -> HashMap<String, u64> {
+    let text = text
+        .to_lowercase()
+        .unicode_words()
+        .fold(String::new(), |a, b| a + "_" + b)
+        .chars()
+        .collect::<Vec<char>>();
+
+    let mut ngrams = HashMap::new();
+    for len in 1..length {
+        for window in text.windows(len) {
+            if len == 1
+                && (window[0].is_numeric() || window[0].is_ascii_punctuation())
+            {
+                continue;
+            }
+            *ngrams.entry(window.iter().collect::<String>()).or_insert(0) += 1;
+        }
+    }
+    ngrams
+}
+
+fn parse_text(c: &mut Criterion) {
+    let english = include_str!("../tests/english.sample");
+    let spanish = include_str!("../tests/spanish.sample");
+    let mut group = c.benchmark_group("parse_text");
+
+    for (name, text) in [("ascii", english), ("unicode", spanish)] {
+        // About 1 MB of text
+        let text = text.repeat(1 + 1_000_000 / text.len());
+        let parsed = Ngrams::parse_text(&text, LENGTH)
+            .into_iter()
+            .map(|(ngram, count)| (ngram.to_string(), count))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(allocating(&text, LENGTH), parsed);
+
+        group.bench_with_input(
+            BenchmarkId::new("slices", name),
+            &text,
+            |b, t| b.iter(|| Ngrams::parse_text(black_box(t), LENGTH)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("allocating", name),
+            &text,
+            |b, t| b.iter(|| allocating(black_box(t), LENGTH)),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = parse_text
+}
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+//! Speed of each step of the pipeline: extracting the ngrams of a text, classifying it and
+//! learning a model from a corpus directory. The model is learned from the test corpus, so
+//! the numbers can be compared with those of older versions.
+//!
+//! Run with `cargo bench --bench pipeline`.
+use criterion::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+};
+use std::hint::black_box;
+use textcat::category::learn_from_directory;
+use textcat::ngram::Ngrams;
+
+/// Ngrams length, as in `Ngrams::new`
+const LENGTH: u8 = 5;
+
+/// Directory of the test corpus
+const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests");
+
+/// Texts of increasing length: a sentence, a paragraph and a long document
+fn inputs() -> Vec<(&'static str, String)> {
+    let english = include_str!("../tests/english.sample");
+    let sentence = english.split('.').next().unwrap_or(english);
+    let paragraph = english.chars().take(1_000).collect::<String>();
+    let document = english.repeat(1 + 100_000 / english.len());
+
+    vec![
+        ("short", sentence.to_string()),
+        ("medium", paragraph),
+        ("long", document),
+    ]
+}
+
+fn extraction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ngrams_new");
+    for (name, text) in inputs() {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &text,
+            |b, t| b.iter(|| Ngrams::new(black_box(t), LENGTH)),
+        );
+    }
+
+    group.finish();
+}
+
+fn classification(c: &mut Criterion) {
+    let model = learn_from_directory(CORPUS).expect("learn the test corpus");
+    let mut group = c.benchmark_group("get_categories");
+    for (name, text) in inputs() {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &text,
+            |b, t| b.iter(|| model.get_categories(black_box(t))),
+        );
+    }
+
+    group.finish();
+}
+
+fn training(c: &mut Criterion) {
+    c.bench_function("learn_from_directory", |b| {
+        b.iter(|| learn_from_directory(black_box(CORPUS)).expect("learn"))
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = extraction, classification, training
+}
+criterion_main!(benches);
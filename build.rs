@@ -0,0 +1,119 @@
+extern crate alloc;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Ngram extraction of the library, see src/extract.rs
+#[path = "src/extract.rs"]
+mod extract;
+
+/// Scripts of the profiles, see src/script.rs
+#[path = "src/script.rs"]
+#[allow(dead_code)]
+mod script;
+
+/// Samples of the languages embedded in the library (see src/default.rs)
+const LANGUAGES: &str = "corpus/languages";
+
+/// Profile size and ngram lengths of the embedded model, the defaults of `Categories`
+const PROFILE_SIZE: usize = 400;
+const NGRAM_LENGTHS: std::ops::Range<usize> = 1..5;
+
+fn main() {
+    embed_languages();
+
+    #[cfg(feature = "napi")]
+    napi_build::setup();
+
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path()
+            .expect("no vendored protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/textcat.proto"], &["proto"])
+            .expect("failed to compile proto/textcat.proto");
+    }
+}
+
+/// Learns the profile of every sample of `LANGUAGES`, as `learn_from_directory` does with
+/// the default settings, and writes them as static tables to `$OUT_DIR/languages.rs`: the
+/// ngrams in rank order, sorted with their rank to look up positions, and the scripts of the
+/// profile. A test of src/default.rs checks that both agree.
+///
+/// Each language is compiled in only with its `lang-<name>` feature, which every sample
+/// needs in Cargo.toml (all of them are part of the `all-languages` default feature).
+fn embed_languages() {
+    println!("cargo:rerun-if-changed={}", LANGUAGES);
+
+    let mut samples = std::fs::read_dir(LANGUAGES)
+        .expect("read corpus/languages")
+        .map(|entry| entry.expect("read corpus/languages").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sample"))
+        .collect::<Vec<PathBuf>>();
+    samples.sort();
+
+    let mut table = String::from(
+        "/// Profiles of the languages of `corpus/languages`, generated by build.rs\n\
+         static LANGUAGES: &[LanguageProfile] = &[\n",
+    );
+    for path in samples.iter() {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let name = path.file_stem().and_then(|n| n.to_str()).expect("name");
+        let ngrams = profile(path);
+        let scripts = script::of_profile(ngrams.iter().map(String::as_str))
+            .iter()
+            .map(|script| format!("Script::{:?}", script))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut ranks = ngrams.iter().enumerate().collect::<Vec<_>>();
+        ranks.sort_by_key(|(_, ngram)| *ngram);
+
+        let ngrams = ngrams
+            .iter()
+            .map(|ngram| format!("{:?}", ngram))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ranks = ranks
+            .iter()
+            .map(|(rank, ngram)| format!("({:?}, {})", ngram, rank))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            table,
+            "    #[cfg(feature = \"lang-{}\")]\n    \
+             LanguageProfile {{ name: {:?}, ngrams: &[{}], ranks: &[{}], \
+             scripts: &[{}] }},",
+            name, name, ngrams, ranks, scripts
+        )
+        .expect("write");
+    }
+    table.push_str("];\n");
+
+    let out = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR"));
+    std::fs::write(out.join("languages.rs"), table)
+        .expect("write languages.rs");
+}
+
+/// Ngrams of a sample in rank order, see `Ngrams::new` and `Ngrams::from_counts`
+fn profile(path: &Path) -> Vec<String> {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+    let normalized = extract::join_words(text.to_lowercase().unicode_words());
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    extract::for_each_window(&normalized, NGRAM_LENGTHS, 0, |_, window| {
+        *counts.entry(window).or_insert(0) += 1;
+    });
+
+    let mut ngrams = counts.into_iter().collect::<Vec<(&str, u64)>>();
+    ngrams.sort_unstable_by(|a, b| extract::by_rank(*a, *b));
+    ngrams.truncate(PROFILE_SIZE);
+    ngrams
+        .into_iter()
+        .map(|(ngram, _)| ngram.to_string())
+        .collect()
+}
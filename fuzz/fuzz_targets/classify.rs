@@ -0,0 +1,42 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use std::sync::OnceLock;
+use textcat::category::{learn_from_directory, Categories};
+use textcat::sampling::Sampling;
+
+/// Model of the test corpus, learned once
+fn model() -> &'static Categories<String> {
+    static MODEL: OnceLock<Categories<String>> = OnceLock::new();
+    MODEL.get_or_init(|| {
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests");
+        learn_from_directory(corpus).expect("learn the test corpus")
+    })
+}
+
+fuzz_target!(|data: (u8, u8, &[u8])| {
+    let (short_text_len, sampling, bytes) = data;
+    // invalid UTF-8 reaches the classifier as replacement characters
+    let text = String::from_utf8_lossy(bytes);
+
+    let mut model = model().clone();
+    model.set_short_text_len(short_text_len as usize);
+    let sampling = match sampling % 4 {
+        0 => Sampling::Full,
+        1 => Sampling::Head { bytes: sampling as usize },
+        2 => Sampling::Chunks {
+            count: 3,
+            size: sampling as usize + 1,
+        },
+        _ => Sampling::Random {
+            count: 2,
+            size: sampling as usize + 1,
+            seed: sampling as u64,
+        },
+    };
+    model.set_sampling(sampling).ok();
+    model.set_shortlist(sampling.is_full() as usize);
+
+    let _ = model.get_categories(&text);
+    let _ = model.get_categories_normalized(&text);
+    let _ = model.detect(&text);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use textcat::category::{from_bytes, migrate};
+
+fuzz_target!(|data: &[u8]| {
+    // every format is detected from the content: JSON, MessagePack, CBOR, bincode and
+    // zstd compressed models
+    if let Ok(model) = from_bytes::<String>(data) {
+        let _ = model.get_categories("the quick brown fox");
+        let _ = model.get_categories("el rápido zorro marrón");
+    }
+    let _ = migrate::<String>(data);
+});
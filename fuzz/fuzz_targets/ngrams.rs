@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use textcat::ngram::Ngrams;
+
+fuzz_target!(|data: (u8, &[u8])| {
+    let (length, bytes) = data;
+    let text = String::from_utf8_lossy(bytes);
+    let ngrams = Ngrams::new(&text, length);
+
+    for pos in 0..ngrams.len() {
+        let ngram = ngrams.get_by_position(pos).expect("ngram by position");
+        assert!(ngrams.position(ngram.ngram()).is_some());
+    }
+});
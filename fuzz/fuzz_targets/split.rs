@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use textcat::ngram::Ngrams;
+
+fuzz_target!(|data: (u8, u8, &str)| {
+    let (start, end, text) = data;
+    let _ = Ngrams::split_and_group_by_ngrams(text, start as usize, end as usize);
+});
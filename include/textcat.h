@@ -0,0 +1,31 @@
+/* C interface of textcat, built with `cargo build --release --features ffi` */
+#ifndef TEXTCAT_H
+#define TEXTCAT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TextCat TextCat;
+
+/* Creates a classifier from the content of a model file, NULL on error */
+TextCat *textcat_new(const uint8_t *model, size_t len);
+
+/* Creates a classifier from a model file, NULL on error */
+TextCat *textcat_load(const char *path);
+
+/* Category of a text, NULL if there is no single candidate. The string
+ * belongs to the handle: do not free it, it is valid until textcat_free. */
+const char *textcat_detect(const TextCat *handle, const char *text);
+
+/* Frees a classifier, NULL is ignored */
+void textcat_free(TextCat *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,142 @@
+//! # Archive
+//!
+//! Layout of archived model files (`Categories::persist_archived`). They are written with
+//! [rkyv](https://rkyv.org), so the profiles can be read in place, e.g. from a file mapped
+//! in shared memory by several processes, without deserializing the model (see `access`).
+//! `category::load_archived` turns an archived model back into `Categories`.
+use crate::error::{Error, Result};
+use crate::script::Script;
+use rkyv::rancor;
+use rkyv::util::AlignedVec;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Alignment of archived models in memory
+pub(crate) const ALIGNMENT: usize = 16;
+
+/// Model as written to archived files
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[rkyv(archived = ArchivedModel)]
+pub struct Model {
+    /// Settings of the model, as a JSON model without categories
+    pub(crate) settings: String,
+
+    /// Categories of the model, in order
+    pub(crate) profiles: Vec<Profile>,
+}
+
+/// Category as written to archived files
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[rkyv(archived = ArchivedProfile)]
+pub struct Profile {
+    /// Name of the category, as JSON
+    pub(crate) name: String,
+
+    /// Ngrams of the category, best ranked first
+    pub(crate) ngrams: Vec<String>,
+
+    /// Scripts of the category, as JSON
+    pub(crate) scripts: String,
+
+    /// Metadata of the category, as JSON
+    pub(crate) metadata: String,
+}
+
+impl Model {
+    /// Writes the model in the archived format
+    pub(crate) fn to_bytes(&self) -> Result<AlignedVec<ALIGNMENT>> {
+        rkyv::to_bytes::<rancor::Error>(self).map_err(Error::serde)
+    }
+}
+
+impl ArchivedModel {
+    /// Settings of the model, as a JSON model without categories
+    pub fn settings(&self) -> &str {
+        &self.settings
+    }
+
+    /// Categories of the model, in order
+    pub fn profiles(&self) -> &[ArchivedProfile] {
+        &self.profiles
+    }
+}
+
+impl ArchivedProfile {
+    /// Name of the category
+    pub fn name<T>(&self) -> Result<T>
+    where
+        for<'a> T: Deserialize<'a>,
+    {
+        serde_json::from_str(&self.name).map_err(Error::serde)
+    }
+
+    /// Scripts the category is written in
+    pub fn scripts(&self) -> Result<Vec<Script>> {
+        serde_json::from_str(&self.scripts).map_err(Error::serde)
+    }
+
+    /// Metadata of the category (see `Categories::category_metadata`)
+    pub fn metadata(&self) -> Result<HashMap<String, String>> {
+        serde_json::from_str(&self.metadata).map_err(Error::serde)
+    }
+
+    /// Number of ngrams in the profile
+    pub fn len(&self) -> usize {
+        self.ngrams.len()
+    }
+
+    /// Returns true if the profile has no ngrams
+    pub fn is_empty(&self) -> bool {
+        self.ngrams.is_empty()
+    }
+
+    /// Ngrams of the profile, best ranked first
+    pub fn ngrams(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        self.ngrams.iter().map(|ngram| ngram.as_str())
+    }
+
+    /// Position of an ngram in the profile, 0 for the best ranked
+    pub fn position(&self, ngram: &str) -> Option<usize> {
+        self.ngrams().position(|n| n == ngram)
+    }
+}
+
+/// Reads an archived model in place, after checking it is well formed. The bytes must be
+/// aligned to 16 bytes, as a mapped file or the buffer used by `category::load_archived`
+/// are.
+pub fn access(bytes: &[u8]) -> Result<&ArchivedModel> {
+    rkyv::access::<ArchivedModel, rancor::Error>(bytes).map_err(Error::serde)
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+    use super::*;
+    use crate::category::learn_from_directory;
+
+    #[test]
+    fn access_in_place() {
+        let model = learn_from_directory("tests").expect("learn");
+        let path = std::env::temp_dir()
+            .join(format!("textcat-access-{}.rkyv", std::process::id()));
+        let path = path.to_str().expect("path");
+        model.persist_archived(path).expect("persist");
+
+        let mut bytes = AlignedVec::<ALIGNMENT>::new();
+        bytes.extend_from_slice(&std::fs::read(path).expect("read"));
+        let _ = std::fs::remove_file(path);
+        let archived = access(&bytes).expect("access");
+
+        let names = archived
+            .profiles()
+            .iter()
+            .map(|profile| profile.name::<String>().expect("name"))
+            .collect::<Vec<_>>();
+        assert_eq!(model.categories(), names);
+
+        let english = &archived.profiles()[0];
+        let expected = model.to_vec()[0].1.clone();
+        assert_eq!(expected, english.ngrams().collect::<Vec<_>>());
+        assert_eq!(Some(1), english.position(expected[1]));
+        assert!(access(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
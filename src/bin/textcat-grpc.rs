@@ -0,0 +1,48 @@
+use std::env;
+use std::process::exit;
+use textcat::category::load;
+use textcat::grpc::Service;
+use tonic::transport::Server;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!(
+            "usage: {} <model file> <listen address>",
+            args.first().map_or("textcat-grpc", |name| name.as_str())
+        );
+        exit(2);
+    }
+
+    let categories = match load::<String>(&args[1]) {
+        Ok(categories) => categories,
+        Err(e) => {
+            eprintln!("error: {}: {}", &args[1], e);
+            exit(1);
+        }
+    };
+
+    let address = match args[2].parse() {
+        Ok(address) => address,
+        Err(e) => {
+            eprintln!("error: {}: {}", &args[2], e);
+            exit(2);
+        }
+    };
+
+    let mut service = Service::new(categories).with_model_path(&args[1]);
+    if let Ok(token) = env::var("TEXTCAT_ADMIN_TOKEN") {
+        service = service.with_admin_token(&token);
+    }
+
+    let result = Server::builder()
+        .add_service(service.into_server())
+        .serve(address)
+        .await;
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
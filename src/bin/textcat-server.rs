@@ -0,0 +1,47 @@
+use std::env;
+use std::process::exit;
+use textcat::category::load;
+use textcat::http::Service;
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!(
+            "usage: {} <model file> <listen address>",
+            args.first().map_or("textcat-server", |name| name.as_str())
+        );
+        exit(2);
+    }
+
+    let categories = match load::<String>(&args[1]) {
+        Ok(categories) => categories,
+        Err(e) => {
+            eprintln!("error: {}: {}", &args[1], e);
+            exit(1);
+        }
+    };
+
+    let listener = match TcpListener::bind(&args[2]).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("error: {}: {}", &args[2], e);
+            exit(2);
+        }
+    };
+
+    let mut service = Service::new(categories);
+    if let Ok(token) = env::var("TEXTCAT_ADMIN_TOKEN") {
+        service = service.with_admin_token(&token);
+    }
+    // uploaded models are only stored when asked to, never over the model given above
+    if let Ok(path) = env::var("TEXTCAT_MODEL_STORE") {
+        service = service.with_model_path(&path);
+    }
+
+    if let Err(e) = axum::serve(listener, service.into_router()).await {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,494 @@
+use clap::{Parser, Subcommand};
+use std::env;
+use std::fs::File;
+use std::io::{stdin, stdout, BufReader, IsTerminal, Read};
+use std::process::exit;
+use std::time::Instant;
+#[cfg(feature = "training")]
+use textcat::category::learn_from_directory_with_report;
+use textcat::category::{learn_from_jsonl_reader, load, migrate, Categories};
+use textcat::encoding;
+use textcat::eval::evaluate;
+use textcat::{Error, Result};
+
+const BAR_WIDTH: usize = 30;
+
+/// Detects the category (e.g. the language) of texts
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Classifies the text read from stdin, or from a file
+    Detect {
+        /// Model file
+        #[arg(long, default_value = "model.json")]
+        model: String,
+
+        /// Reads the text from a file instead of stdin
+        #[arg(long)]
+        file: Option<String>,
+
+        /// Classifies each line separately, printing `category<TAB>line` rows
+        #[arg(long)]
+        per_line: bool,
+
+        /// Disables colors (also disabled by the NO_COLOR environment variable)
+        #[arg(long)]
+        no_color: bool,
+
+        /// Prints only the category, or "unknown"
+        #[arg(long)]
+        plain: bool,
+    },
+
+    /// Learns a model from a directory of samples, from a single sample (--label) or from
+    /// JSON-lines data (--jsonl)
+    Learn {
+        /// Category of the sample read from INPUT
+        #[arg(long, conflicts_with = "jsonl")]
+        label: Option<String>,
+
+        /// Reads `{"label": "...", "text": "..."}` lines from INPUT
+        #[arg(long)]
+        jsonl: bool,
+
+        /// Samples directory, or file ("-" for stdin) with --label and --jsonl
+        input: String,
+
+        /// Model file to create
+        output: String,
+    },
+
+    /// Lists the categories of a model
+    ListCategories {
+        /// Model file
+        #[arg(long, default_value = "model.json")]
+        model: String,
+    },
+
+    /// Measures the accuracy of a model on labeled samples: each line of the `*.sample`
+    /// files of DIR is classified and compared to the name of its file
+    Eval {
+        /// Directory of labeled samples
+        dir: String,
+
+        /// Model file
+        #[arg(long, default_value = "model.json")]
+        model: String,
+    },
+
+    /// Shows which ngrams of a text drove its classification: the position of the best
+    /// ranked ngrams of the text in the closest category profiles, and the running distance
+    Explain {
+        /// Model file
+        #[arg(long, default_value = "model.json")]
+        model: String,
+
+        /// Text to explain, read from stdin when missing
+        text: Option<String>,
+
+        /// Number of ngrams of the text to list
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        /// Prints the whole explanation as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Disables colors (also disabled by the NO_COLOR environment variable)
+        #[arg(long)]
+        no_color: bool,
+    },
+
+    /// Upgrades a model file written by an older version to the current format
+    Migrate {
+        /// Model file to upgrade
+        input: String,
+
+        /// Model file to create, it can be the same as INPUT
+        output: String,
+    },
+}
+
+/// Output styles
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn best(&self, text: &str) -> String {
+        self.paint("1;32", text)
+    }
+
+    fn warn(&self, text: &str) -> String {
+        self.paint("1;33", text)
+    }
+
+    fn dim(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+}
+
+fn fail(e: Error) -> ! {
+    eprintln!("error: {}", e);
+    exit(1);
+}
+
+fn load_model(path: &str) -> Categories<String> {
+    load::<String>(path).unwrap_or_else(|e| {
+        eprintln!("error: {}: {}", path, e);
+        exit(1);
+    })
+}
+
+/// Opens a file, or stdin for "-"
+fn open(path: &str) -> Result<Box<dyn Read>> {
+    if path == "-" {
+        Ok(Box::new(stdin()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// Returns the category of a text, or "unknown" when there is no single candidate
+fn verdict(categories: &Categories<String>, text: &str) -> String {
+    categories
+        .get_category(text)
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn detect(
+    categories: &Categories<String>,
+    text: &str,
+    color: bool,
+    plain: bool,
+) {
+    let start = Instant::now();
+    let distances = categories.get_distances(text);
+    let candidates = categories.get_categories(text).unwrap_or_default();
+    let elapsed = start.elapsed();
+
+    if plain {
+        println!("{}", verdict(categories, text));
+        return;
+    }
+
+    let style = Style { color };
+    let best = distances.first().map_or(0, |(_, d)| *d);
+    let worst = distances.last().map_or(0, |(_, d)| *d);
+    let width = distances.iter().map(|(n, _)| n.chars().count()).max();
+
+    match candidates.as_slice() {
+        [(category, _)] => println!("{}", style.best(category)),
+        [] => println!("{}", style.warn("unknown")),
+        _ => println!(
+            "{} between {}",
+            style.warn("ambiguous"),
+            candidates
+                .iter()
+                .map(|(c, _)| c.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+    println!();
+
+    for (category, distance) in distances.iter() {
+        let filled = if worst > best {
+            ((worst - distance) as usize * BAR_WIDTH) / (worst - best) as usize
+        } else {
+            BAR_WIDTH
+        };
+        let bar =
+            format!("{}{}", "█".repeat(filled), " ".repeat(BAR_WIDTH - filled));
+        let margin = if best > 0 {
+            format!("+{:.1}%", (distance - best) as f64 * 100.0 / best as f64)
+        } else {
+            "".to_string()
+        };
+        let label = format!("{:w$}", category, w = width.unwrap_or(0));
+        let is_candidate = candidates.iter().any(|(c, _)| c == category);
+
+        println!(
+            "{} {} {:>8} {}",
+            if is_candidate {
+                style.best(&label)
+            } else {
+                label
+            },
+            if is_candidate {
+                style.best(&bar)
+            } else {
+                style.dim(&bar)
+            },
+            distance,
+            style.dim(&margin)
+        );
+    }
+
+    println!();
+    println!("{}", style.dim(&format!("classified in {:.2?}", elapsed)));
+}
+
+fn learn(label: Option<String>, jsonl: bool, input: &str, output: &str) {
+    let categories = if let Some(label) = label {
+        let mut categories = Categories::new();
+        open(input)
+            .and_then(|input| categories.add_category_from_reader(label, input))
+            .unwrap_or_else(|e| fail(e));
+        categories
+    } else if jsonl {
+        open(input)
+            .and_then(|input| learn_from_jsonl_reader(BufReader::new(input)))
+            .unwrap_or_else(|e| fail(e))
+    } else {
+        learn_directory(input)
+    };
+
+    if categories.categories().is_empty() {
+        eprintln!("error: no categories were learned");
+        exit(1);
+    }
+
+    if let Err(e) = categories.persist(output) {
+        fail(e);
+    }
+
+    println!("{} has been created", output);
+}
+
+#[cfg(feature = "training")]
+fn learn_directory(input: &str) -> Categories<String> {
+    let (categories, report) =
+        learn_from_directory_with_report(input).unwrap_or_else(|e| fail(e));
+    eprint!("{}", report);
+    categories
+}
+
+#[cfg(not(feature = "training"))]
+fn learn_directory(_input: &str) -> Categories<String> {
+    eprintln!("error: learning from a directory needs the training feature");
+    exit(2);
+}
+
+fn eval(dir: &str, model: &str) {
+    let categories = load_model(model);
+    let entries = std::fs::read_dir(dir).unwrap_or_else(|e| fail(e.into()));
+    let mut files = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sample"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut labeled = Vec::new();
+    for path in files {
+        let label = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let text =
+            std::fs::read_to_string(&path).unwrap_or_else(|e| fail(e.into()));
+        labeled.extend(
+            text.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| (label.to_string(), line.to_string())),
+        );
+    }
+
+    print!("{}", evaluate(&categories, &labeled));
+}
+
+fn explain(
+    categories: &Categories<String>,
+    text: &str,
+    top: usize,
+    color: bool,
+) {
+    let style = Style { color };
+    let explanation = categories.explain(text);
+
+    // The candidates, and at least the runner-up to compare with
+    let shown = explanation
+        .categories
+        .iter()
+        .enumerate()
+        .take_while(|(i, category)| category.candidate || *i < 2)
+        .map(|(_, category)| category)
+        .collect::<Vec<_>>();
+    let ngrams = &explanation.sample[..explanation.sample.len().min(top)];
+    let width = ngrams
+        .iter()
+        .map(|n| n.chars().count() + 2)
+        .chain(Some(5))
+        .max()
+        .unwrap_or(0);
+    let column = shown
+        .iter()
+        .map(|c| c.category.chars().count())
+        .chain(Some(15))
+        .max()
+        .unwrap_or(0);
+
+    print!("{:>4}  {:w$}", "rank", "ngram", w = width);
+    for category in shown.iter() {
+        let name = format!("{:>w$}", category.category, w = column);
+        if category.candidate {
+            print!("  {}", style.best(&name));
+        } else {
+            print!("  {}", name);
+        }
+    }
+    println!();
+    print!("{:4}  {:w$}", "", "", w = width);
+    for _ in shown.iter() {
+        let header = format!("{:>w$}", "profile   total", w = column);
+        print!("  {}", style.dim(&header));
+    }
+    println!();
+
+    let mut totals = vec![0; shown.len()];
+    for (rank, ngram) in ngrams.iter().enumerate() {
+        print!("{:>4}  {:w$}", rank, format!("\"{}\"", ngram), w = width);
+        for (category, total) in shown.iter().zip(totals.iter_mut()) {
+            let found = category.ngrams.iter().find(|n| n.ngram == *ngram);
+            if let Some(found) = found {
+                *total += found.penalty;
+            }
+            let position = found.map_or("-".to_string(), |found| {
+                found.category_rank.to_string()
+            });
+            let cell = format!(
+                "{:>w$}",
+                format!("{:>7} {:>7}", position, total),
+                w = column
+            );
+            if found.is_some() {
+                print!("  {}", cell);
+            } else {
+                print!("  {}", style.dim(&cell));
+            }
+        }
+        println!();
+    }
+
+    println!();
+    for category in shown.iter() {
+        println!(
+            "{}: {} {}",
+            category.category,
+            category.distance,
+            style.dim(&format!(
+                "({} profile ngrams missing from the text, {} each)",
+                category.missing, category.missing_penalty
+            ))
+        );
+    }
+}
+
+fn migrate_model(input: &str, output: &str) {
+    let categories = std::fs::read(input)
+        .map_err(Error::from)
+        .and_then(|bytes| migrate::<String>(&bytes))
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}: {}", input, e);
+            exit(1);
+        });
+
+    if let Err(e) = categories.persist_atomic(output) {
+        fail(e);
+    }
+
+    println!("{} has been migrated to {}", input, output);
+}
+
+fn main() {
+    match Cli::parse().command {
+        Command::Detect {
+            model,
+            file,
+            per_line,
+            no_color,
+            plain,
+        } => {
+            let categories = load_model(&model);
+            // legacy files in UTF-16 or Latin-1 are decoded, see `encoding::decode`
+            let mut bytes = Vec::new();
+            open(file.as_deref().unwrap_or("-"))
+                .and_then(|mut input| Ok(input.read_to_end(&mut bytes)?))
+                .unwrap_or_else(|e| fail(e));
+            let text = encoding::decode(&bytes).0;
+
+            if per_line {
+                let lines = text.lines().collect::<Vec<_>>();
+                for (number, detection) in categories.detect_lines(&text) {
+                    let line = lines[number - 1];
+                    if !line.trim().is_empty() {
+                        let category = detection.category();
+                        println!(
+                            "{}\t{}",
+                            category.as_deref().unwrap_or("unknown"),
+                            line
+                        );
+                    }
+                }
+            } else {
+                let color = !no_color
+                    && env::var_os("NO_COLOR").is_none()
+                    && stdout().is_terminal();
+                detect(&categories, &text, color, plain);
+            }
+        }
+        Command::Learn {
+            label,
+            jsonl,
+            input,
+            output,
+        } => learn(label, jsonl, &input, &output),
+        Command::ListCategories { model } => {
+            for category in load_model(&model).categories() {
+                println!("{}", category);
+            }
+        }
+        Command::Eval { dir, model } => eval(&dir, &model),
+        Command::Explain {
+            model,
+            text,
+            top,
+            json,
+            no_color,
+        } => {
+            let categories = load_model(&model);
+            let text = text.unwrap_or_else(|| {
+                let mut text = String::new();
+                stdin()
+                    .read_to_string(&mut text)
+                    .unwrap_or_else(|e| fail(e.into()));
+                text
+            });
+
+            if json {
+                let explanation = categories.explain(&text);
+                match serde_json::to_string_pretty(&explanation) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => fail(e.into()),
+                }
+            } else {
+                let color = !no_color
+                    && env::var_os("NO_COLOR").is_none()
+                    && stdout().is_terminal();
+                explain(&categories, &text, top, color);
+            }
+        }
+        Command::Migrate { input, output } => migrate_model(&input, &output),
+    }
+}
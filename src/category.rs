@@ -2,36 +2,425 @@
 //!
 //! This module groups sets of ngrams and their category into categories. This is useful to try
 //! unknown texts and see to which pre-trained category it belongs.
-use crate::ngram::Ngrams;
-use glob::{glob, Paths};
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "rkyv")]
+use crate::archive;
+use crate::collections::{HashMap, HashSet};
+use crate::encoding;
+use crate::error::{Error, MergeError, Result};
+#[cfg(feature = "training")]
+use crate::eval::CrossValidation;
+use crate::explain::Explanation;
+use crate::index::InvertedIndex;
+use crate::intern::Interner;
+use crate::lang::Lang;
+#[cfg(feature = "markup")]
+use crate::markup;
+use crate::ngram::{
+    fold_diacritics, Boundaries, Distance, Metric, Ngrams, NgramsBuilder,
+    SmolStr, TokenMode,
+};
+use crate::normalize::{Normalizer, Pipeline};
+use crate::sampling::Sampling;
+use crate::script::{self, Script};
+use crate::threshold::Threshold;
+use alloc::borrow::Cow;
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "bincode")]
+use bincode::Options;
+use core::{fmt::Display, ops::Range};
+#[cfg(feature = "training")]
+use glob::{glob, GlobResult};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde::{ser::Error as _, Deserialize, Serialize, Serializer};
+#[cfg(feature = "std")]
 use std::{
-    fmt::Display,
     fs::File,
-    io::{BufReader, Error, ErrorKind, Read, Write},
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
 };
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Best ranked ngrams of each profile compared with a text to sort the categories before
+/// measuring their distances (see `Categories::nearest`)
+const PREFILTER_NGRAMS: usize = 32;
+
+/// Size of the chunks read from streams
+const STREAM_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Minimum amount of text read from a stream before considering an early result
+const MIN_STREAM_BYTES: usize = 1024;
+
+/// Number of words of the windows used by `Categories::composition`
+const COMPOSITION_WINDOW: usize = 12;
+
+/// Header of encrypted model files
+#[cfg(feature = "encryption")]
+const ENCRYPTED_MAGIC: &[u8] = b"TEXTCAT-AES256GCM\0";
+
+/// Size of the nonce which follows the header of encrypted model files
+#[cfg(feature = "encryption")]
+const ENCRYPTED_NONCE_SIZE: usize = 12;
+
+/// Header of binary model files
+const BINARY_MAGIC: &[u8] = b"TEXTCAT-BINCODE\0";
+
+/// Header of CBOR model files, the self-described CBOR tag (RFC 8949, section 3.4.6)
+const CBOR_MAGIC: &[u8] = b"\xd9\xd9\xf7";
+
+/// Header of zstd frames, with which compressed model files start
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+
+/// zstd level of compressed models, which are written once and loaded many times
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 19;
+
+/// Version of the binary layout, the byte which follows the header
+#[cfg(feature = "bincode")]
+const BINARY_VERSION: u8 = 11;
+
+/// Longest ngram (in chars) accepted in a model
+const MAX_NGRAM_LENGTH: usize = 64;
+
+/// Default number of ngrams kept in the profile of a category
+pub(crate) const DEFAULT_PROFILE_SIZE: usize = 400;
+
+/// Default shortest ngram (in chars)
+const DEFAULT_MIN_NGRAM_LEN: usize = 1;
+
+/// Default longest ngram (in chars)
+pub(crate) const DEFAULT_MAX_NGRAM_LEN: usize = 4;
+
+fn default_profile_size() -> usize {
+    DEFAULT_PROFILE_SIZE
+}
+
+fn is_default_profile_size(size: &usize) -> bool {
+    *size == DEFAULT_PROFILE_SIZE
+}
+
+fn default_min_ngram_len() -> usize {
+    DEFAULT_MIN_NGRAM_LEN
+}
+
+fn default_max_ngram_len() -> usize {
+    DEFAULT_MAX_NGRAM_LEN
+}
+
+fn is_default_min_ngram_len(len: &usize) -> bool {
+    *len == DEFAULT_MIN_NGRAM_LEN
+}
+
+fn is_default_max_ngram_len(len: &usize) -> bool {
+    *len == DEFAULT_MAX_NGRAM_LEN
+}
+
+/// Serializes the categories sorted by name (see `Categories` for the canonical order)
+fn serialize_canonical<T, S>(
+    categories: &[Category<T>],
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+    S: Serializer,
+{
+    serializer.collect_seq(canonical(categories).map_err(S::Error::custom)?)
+}
+
+/// Categories sorted by the serialized (JSON) form of their name
+fn canonical<T>(
+    categories: &[Category<T>],
+) -> serde_json::Result<Vec<&Category<T>>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let mut sorted = categories
+        .iter()
+        .map(|category| {
+            serde_json::to_string(&category.name).map(|key| (key, category))
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(sorted.into_iter().map(|(_, category)| category).collect())
+}
+
+/// Serializes a set of words sorted, so a model is always serialized the same way
+fn serialize_sorted<S>(
+    words: &HashSet<String>,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut sorted = words.iter().collect::<Vec<_>>();
+    sorted.sort();
+    serializer.collect_seq(sorted)
+}
+
+/// Serializes a map sorted by key, so a model is always serialized the same way
+fn serialize_sorted_map<S>(
+    map: &HashMap<String, String>,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut sorted = map.iter().collect::<Vec<_>>();
+    sorted.sort();
+    serializer.collect_map(sorted)
+}
+
+/// Splits a text into windows of (up to) `size` words. Each window is the byte range of the
+/// text from its first word to its last.
+fn word_windows(text: &str, size: usize) -> Vec<Range<usize>> {
+    let words = text.unicode_word_indices().collect::<Vec<_>>();
+
+    words
+        .chunks(size.max(1))
+        .filter_map(|chunk| {
+            let (start, _) = chunk.first()?;
+            let (last, word) = chunk.last()?;
+            Some(*start..last + word.len())
+        })
+        .collect()
+}
+
+/// Byte ranges of the sentences of a text (Unicode sentence boundaries), without their
+/// surrounding whitespace. Sentences without words are skipped.
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    text.split_sentence_bound_indices()
+        .filter(|(_, sentence)| sentence.unicode_words().next().is_some())
+        .map(|(start, sentence)| {
+            let trimmed = sentence.trim_start();
+            let start = start + sentence.len() - trimmed.len();
+            start..start + trimmed.trim_end().len()
+        })
+        .collect()
+}
+
+/// Human readable form of a category name, used in error messages
+fn display_name<T: Serialize>(name: &T) -> String {
+    match serde_json::to_value(name) {
+        Ok(serde_json::Value::String(name)) => name,
+        Ok(value) => value.to_string(),
+        Err(_) => "?".to_string(),
+    }
+}
+
+/// Best category for a text and how clear the decision was
+#[derive(Debug, Clone, PartialEq)]
+pub struct BestMatch<T> {
+    /// Best category
+    pub category: T,
+
+    /// Distance between the category and the text
+    pub distance: u64,
+
+    /// Distance gap to the runner-up. `u64::MAX` if there is no runner-up.
+    pub margin: u64,
+
+    /// Distance gap to the runner-up relative to the best distance, comparable to the
+    /// threshold: the match is ambiguous when it is below the threshold. Infinite if there
+    /// is no runner-up.
+    pub relative_margin: f32,
+}
+
+/// Distance between a category and a text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Score {
+    /// Distance as returned by `Categories::get_categories` (the lower the better)
+    pub distance: u64,
+
+    /// Distance from 0 to 1, as returned by `Categories::get_categories_normalized`
+    pub normalized: f32,
+}
 
-const DEFAULT_THRESHOLD: f32 = 0.03;
+/// Format of a model file, see `Categories::persist_as` and `load_as`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFormat {
+    /// JSON, the default format
+    Json,
 
-fn default_threshold() -> f32 {
-    DEFAULT_THRESHOLD
+    /// MessagePack, with the fields named as in JSON (`msgpack` feature)
+    MsgPack,
+
+    /// CBOR, with the fields named as in JSON (`cbor` feature)
+    Cbor,
+
+    /// Compact binary layout of this library (`bincode` feature), see
+    /// `Categories::persist_binary`
+    Bincode,
+}
+
+impl ModelFormat {
+    /// Guesses the format of the content of a model file from its first bytes. Anything
+    /// which is not binary, CBOR or MessagePack is taken as JSON.
+    pub fn detect(bytes: &[u8]) -> ModelFormat {
+        if bytes.starts_with(BINARY_MAGIC) {
+            return ModelFormat::Bincode;
+        }
+        if bytes.starts_with(CBOR_MAGIC) {
+            return ModelFormat::Cbor;
+        }
+
+        // Models are maps (or arrays, for MessagePack), which no JSON text starts with
+        match bytes.first() {
+            Some(0x80..=0x9f | 0xdc..=0xdf) => ModelFormat::MsgPack,
+            Some(0xa0..=0xbb) => ModelFormat::Cbor,
+            _ => ModelFormat::Json,
+        }
+    }
+
+    /// Error returned when the feature of the format is not enabled
+    #[allow(dead_code)]
+    fn unsupported(self) -> Error {
+        let (name, feature) = match self {
+            ModelFormat::Json => ("JSON", "std"),
+            ModelFormat::MsgPack => ("MessagePack", "msgpack"),
+            ModelFormat::Cbor => ("CBOR", "cbor"),
+            ModelFormat::Bincode => ("binary", "bincode"),
+        };
+        Error::Unsupported(format!(
+            "{} models need the {} feature",
+            name, feature
+        ))
+    }
+}
+
+/// Outcome of `Categories::detect`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Detection<T> {
+    /// A single category is close enough to the text
+    Match(T, Score),
+
+    /// Several categories are within the threshold of each other, best first
+    Ambiguous(Vec<(T, Score)>),
+
+    /// There are no categories to compare the text with
+    Unknown,
+
+    /// The text is shorter than the minimum input length (see
+    /// `Categories::set_min_input_len`)
+    TooShort,
+}
+
+impl<T> Detection<T> {
+    /// Returns the category of a match, None otherwise
+    pub fn category(self) -> Option<T> {
+        match self {
+            Detection::Match(category, _) => Some(category),
+            _ => None,
+        }
+    }
+}
+
+/// What is returned for a text when several categories are within the threshold of each
+/// other, see `Categories::set_ambiguity_policy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbiguityPolicy {
+    /// No category: the text is not classified
+    ReturnNone,
+    /// The closest category
+    ReturnBest,
+    /// Every candidate, best first (see `Categories::get_category_candidates`). Methods
+    /// returning a single category return the closest one.
+    ReturnAll,
+}
+
+impl Default for AmbiguityPolicy {
+    fn default() -> Self {
+        AmbiguityPolicy::ReturnNone
+    }
+}
+
+/// Layout of binary model files. Unlike the text formats every setting is always stored,
+/// as bincode is not self-describing and cannot skip fields.
+#[cfg(feature = "bincode")]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "T: Serialize, for<'b> T: Deserialize<'b>")]
+struct BinaryModel<'a, T>
+where
+    for<'b> T: PartialEq<T> + Serialize + Deserialize<'b> + Clone,
+{
+    version: Cow<'a, str>,
+    /// Every distinct ngram of the model, profiles refer to them by position
+    symbols: Vec<Cow<'a, str>>,
+    categories: Vec<BinaryCategory<'a, T>>,
+    boundaries: Boundaries,
+    fold_diacritics: bool,
+    profile_size: usize,
+    min_ngram_len: usize,
+    max_ngram_len: usize,
+    mode: TokenMode,
+    metric: Metric,
+    missing_penalty: Option<u64>,
+    #[serde(serialize_with = "serialize_sorted")]
+    stopwords: Cow<'a, HashSet<String>>,
+    sampling: Sampling,
+    normalization: Cow<'a, Pipeline>,
+    threshold: Threshold,
+}
+
+/// Category as stored in binary model files
+#[cfg(feature = "bincode")]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "T: Serialize, for<'b> T: Deserialize<'b>")]
+struct BinaryCategory<'a, T>
+where
+    for<'b> T: PartialEq<T> + Serialize + Deserialize<'b> + Clone,
+{
+    name: Cow<'a, T>,
+    /// Positions of the ngrams in the symbols of the model, in rank order
+    ngrams: Vec<u32>,
+    scripts: Cow<'a, [Script]>,
+    #[serde(serialize_with = "serialize_sorted_map")]
+    metadata: Cow<'a, HashMap<String, String>>,
 }
 
 /// IoResult type
-pub type IoResult<T> = std::result::Result<T, Error>;
+#[deprecated(note = "use textcat::Result")]
+pub type IoResult<T> = Result<T>;
 
 /// Category structure
 ///
 /// A category is a 'name' given to a set o ngrams. N-grams are useful to extract features from
 /// categories to "train". The extracted knowledge can be serialize/deserialize with this struct.
 #[derive(Clone, Serialize, Deserialize)]
-#[serde(bound = "T: Serialize, for<'a> T: Deserialize<'a>")]
+#[serde(
+    bound = "T: Serialize, for<'a> T: Deserialize<'a>",
+    deny_unknown_fields
+)]
 struct Category<T>
 where
     for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
 {
     name: T,
     ngrams: Ngrams,
+    /// Scripts the category is written in, texts in other scripts are not compared with
+    /// it. Untagged categories are compared with every text.
+    #[serde(default)]
+    scripts: Vec<Script>,
+    /// Free-form information about the category, e.g. its ISO code or a description
+    #[serde(default, serialize_with = "serialize_sorted_map")]
+    metadata: HashMap<String, String>,
+    /// Ids of the ngrams in the pool of the model, in rank order (see `Category::intern`)
+    #[serde(skip)]
+    ids: Vec<u32>,
+}
+
+/// Profile of a category compared with a text, see `Categories::nearest`
+struct Profile<'a, T> {
+    name: &'a T,
+    ngrams: Cow<'a, Ngrams>,
+    /// Ids of the ngrams, see `Category::ids`
+    ids: Cow<'a, [u32]>,
 }
 
 impl<T> From<(T, Vec<&str>)> for Category<T>
@@ -39,10 +428,7 @@ where
     for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
 {
     fn from(value: (T, Vec<&str>)) -> Category<T> {
-        Self {
-            name: value.0,
-            ngrams: value.1.into(),
-        }
+        Self::new(value.0, value.1.into())
     }
 }
 
@@ -50,8 +436,55 @@ impl<T> Category<T>
 where
     for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
 {
-    pub fn distance(&self, ngrams: &Ngrams) -> u64 {
-        self.ngrams.distance(ngrams)
+    /// Creates a category tagged with the scripts of its profile
+    fn new(name: T, ngrams: Ngrams) -> Category<T> {
+        let scripts = script::of_profile(ngrams.to_vec());
+        Category {
+            name,
+            ngrams,
+            scripts,
+            metadata: HashMap::new(),
+            ids: Vec::new(),
+        }
+    }
+
+    /// Numbers the ngrams of the profile in the pool of the model. The ids replace the
+    /// lookup table of the profile, which is dropped.
+    fn intern(&mut self, pool: &mut Interner) {
+        self.ids = self
+            .to_vec()
+            .into_iter()
+            .map(|ngram| pool.intern_id(ngram))
+            .collect();
+        self.ngrams.drop_index();
+    }
+
+    /// Profile of the category to compare with a text
+    fn profile(&self) -> Profile<'_, T> {
+        Profile {
+            name: &self.name,
+            ngrams: Cow::Borrowed(&self.ngrams),
+            ids: Cow::Borrowed(&self.ids),
+        }
+    }
+
+    /// Returns true if texts of the given dominant script are compared with the category
+    fn accepts(&self, script: Option<Script>) -> bool {
+        match script {
+            Some(script) if !self.scripts.is_empty() => {
+                self.scripts.contains(&script)
+            }
+            _ => true,
+        }
+    }
+
+    pub fn distance(
+        &self,
+        ngrams: &Ngrams,
+        metric: Metric,
+        missing_penalty: u64,
+    ) -> u64 {
+        metric.distance_with_penalty(&self.ngrams, ngrams, missing_penalty)
     }
 
     /// Exports the current structure as a vector
@@ -61,32 +494,129 @@ where
 }
 
 /// This structure is the serialized/unserialized sorted first N n-grams from a text.
+///
+/// The serialized form is canonical, serializing the same model always produces the same
+/// output regardless of the order the categories were added in:
+///
+///  * Categories are sorted by the serialized (JSON) form of their name.
+///  * Ngrams are sorted by rank: highest count first, ties are broken by comparing the ngrams
+///    bytewise in descending order.
 #[derive(Clone, Serialize, Deserialize)]
-#[serde(bound = "T: Serialize, for<'a> T: Deserialize<'a>")]
+#[serde(
+    bound = "T: Serialize, for<'a> T: Deserialize<'a>",
+    deny_unknown_fields
+)]
 pub struct Categories<T>
 where
     for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
 {
-    /// Version of the file format. Not used at the moment but it will allow the program
-    /// to refuse to work older file formats.
+    /// Version of the library which wrote the model. `load` refuses models of incompatible
+    /// versions (see `is_compatible_version`), `migrate` upgrades them.
     version: String,
 
     /// List of categories with their features/n-grams
+    #[serde(serialize_with = "serialize_canonical")]
     categories: Vec<Category<T>>,
 
-    /// Runtime configuration.
-    ///
-    /// Minimun threshold to (0.00-0.99) to consider a match close enough from each
-    /// other. This setting can be updated with update set_threshold(0.01), the default
-    /// value is 3% (0.03)
+    /// Margin within which categories close to the best one are candidates too, see
+    /// `set_threshold`.
+    #[serde(default, skip_serializing_if = "Threshold::is_default")]
+    threshold: Threshold,
+
+    /// How ngrams touching word boundaries are weighted, both to learn and to classify.
+    #[serde(default, skip_serializing_if = "Boundaries::is_all")]
+    boundaries: Boundaries,
+
+    /// Whether accents and other diacritics are removed, both to learn and to classify.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    fold_diacritics: bool,
+
+    /// Normalizers applied to texts, both to learn and to classify.
+    #[serde(default, skip_serializing_if = "Pipeline::is_empty")]
+    normalization: Pipeline,
+
+    /// Number of ngrams kept in the profile of each category.
+    #[serde(
+        default = "default_profile_size",
+        skip_serializing_if = "is_default_profile_size"
+    )]
+    profile_size: usize,
+
+    /// Shortest character ngram (in chars), both to learn and to classify.
+    #[serde(
+        default = "default_min_ngram_len",
+        skip_serializing_if = "is_default_min_ngram_len"
+    )]
+    min_ngram_len: usize,
+
+    /// Longest character ngram (in chars), both to learn and to classify.
+    #[serde(
+        default = "default_max_ngram_len",
+        skip_serializing_if = "is_default_max_ngram_len"
+    )]
+    max_ngram_len: usize,
+
+    /// Which ngrams are extracted from texts, both to learn and to classify.
+    #[serde(default, skip_serializing_if = "TokenMode::is_char")]
+    mode: TokenMode,
+
+    /// How distances between categories and texts are measured.
+    #[serde(default, skip_serializing_if = "Metric::is_out_of_place")]
+    metric: Metric,
+
+    /// Out-of-place distance of an ngram missing from the text. The profile size when not
+    /// set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    missing_penalty: Option<u64>,
+
+    /// Words left out of the texts, see `set_stopwords`.
     #[serde(
-        skip_deserializing,
-        skip_serializing,
-        default = "default_threshold"
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
     )]
-    threshold: f32,
+    stopwords: HashSet<String>,
+
+    /// Whether the profiles are stored with the count of each ngram, see `set_keep_counts`.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    counts: bool,
+
+    /// Parts of long texts which are classified, see `set_sampling`.
+    #[serde(default, skip_serializing_if = "Sampling::is_full")]
+    sampling: Sampling,
+
+    /// Runtime configuration, see `set_min_input_len`
+    #[serde(skip)]
+    min_input_len: usize,
+
+    /// Runtime configuration, see `set_short_text_len`
+    #[serde(skip)]
+    short_text_len: usize,
+
+    /// Runtime configuration, see `set_shortlist`
+    #[serde(skip)]
+    shortlist: usize,
+
+    /// Runtime configuration, see `set_ambiguity_policy`
+    #[serde(skip)]
+    ambiguity: AmbiguityPolicy,
+
+    /// Ids of the ngrams of all categories, the lookup table of every profile (see
+    /// `Interner`).
+    #[serde(skip)]
+    pool: Interner,
+
+    /// Categories of each ngram, see `set_shortlist`.
+    #[serde(skip)]
+    index: InvertedIndex,
 }
 
+/// Former name of the model with string categories, from the time it had its own
+/// implementation. Every part of the crate (binaries, bindings and the embedded model)
+/// uses `Categories`.
+#[deprecated(since = "0.3.2", note = "use `Categories<String>`")]
+pub type FileContent = Categories<String>;
+
 impl<T> From<Vec<(T, Vec<&str>)>> for Categories<T>
 where
     for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
@@ -97,6 +627,7 @@ where
             .iter()
             .map(|m| m.to_owned().into())
             .collect::<Vec<Category<T>>>();
+        new.intern();
         new
     }
 }
@@ -111,145 +642,4287 @@ where
         Categories {
             categories: Vec::new(),
             version: env!("CARGO_PKG_VERSION").to_string(),
-            threshold: DEFAULT_THRESHOLD,
+            threshold: Threshold::default(),
+            boundaries: Boundaries::All,
+            fold_diacritics: false,
+            normalization: Pipeline::new(),
+            profile_size: DEFAULT_PROFILE_SIZE,
+            min_ngram_len: DEFAULT_MIN_NGRAM_LEN,
+            max_ngram_len: DEFAULT_MAX_NGRAM_LEN,
+            mode: TokenMode::Char,
+            metric: Metric::OutOfPlace,
+            missing_penalty: None,
+            stopwords: HashSet::new(),
+            counts: false,
+            sampling: Sampling::Full,
+            min_input_len: 0,
+            short_text_len: 0,
+            shortlist: 0,
+            ambiguity: AmbiguityPolicy::ReturnNone,
+            pool: Interner::new(),
+            index: InvertedIndex::new(),
         }
     }
 
-    /// Converts the current structure into a vector (language, [ngrams])
-    pub fn to_vec(&self) -> Vec<(T, Vec<&str>)> {
+    /// Creates an empty model with the same settings as this one
+    pub(crate) fn with_same_options(&self) -> Categories<T> {
+        Categories {
+            categories: Vec::new(),
+            version: self.version.clone(),
+            threshold: self.threshold,
+            boundaries: self.boundaries,
+            fold_diacritics: self.fold_diacritics,
+            normalization: self.normalization.clone(),
+            profile_size: self.profile_size,
+            min_ngram_len: self.min_ngram_len,
+            max_ngram_len: self.max_ngram_len,
+            mode: self.mode,
+            metric: self.metric,
+            missing_penalty: self.missing_penalty,
+            stopwords: self.stopwords.clone(),
+            counts: self.counts,
+            sampling: self.sampling,
+            min_input_len: self.min_input_len,
+            short_text_len: self.short_text_len,
+            shortlist: self.shortlist,
+            ambiguity: self.ambiguity,
+            pool: Interner::new(),
+            index: InvertedIndex::new(),
+        }
+    }
+
+    /// Keeps only the categories for which the predicate returns true
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.categories.retain(|category| f(&category.name));
+        self.pool = Interner::new();
+        self.intern();
+    }
+
+    /// Removes a category, returns false if there is no such category
+    pub fn remove_category(&mut self, name: &T) -> bool {
+        let len = self.categories.len();
+        self.retain(|category| category != name);
+        self.categories.len() != len
+    }
+
+    /// Renames a category. Fails if there is no such category or if the new name is
+    /// already taken by another one.
+    pub fn rename_category(&mut self, old: &T, new: T) -> Result<()> {
+        if old != &new && self.categories.iter().any(|c| c.name == new) {
+            return Err(Error::InvalidSetting(format!(
+                "category {} already exists",
+                display_name(&new)
+            )));
+        }
+
+        match self.categories.iter_mut().find(|c| &c.name == old) {
+            Some(category) => {
+                category.name = new;
+                Ok(())
+            }
+            None => Err(Error::UnknownCategory(display_name(old))),
+        }
+    }
+
+    /// Returns the scripts a category is written in (see `set_scripts`), None if there is
+    /// no such category
+    pub fn scripts(&self, name: &T) -> Option<&[Script]> {
         self.categories
             .iter()
-            .map(|category| (category.name.clone(), category.to_vec()))
-            .collect()
+            .find(|c| &c.name == name)
+            .map(|c| c.scripts.as_slice())
     }
 
-    /// Updates the result threshold
-    pub fn set_threshold(&mut self, threshold: f32) -> Result<(), &str> {
-        if threshold <= 0.0 && 1.0 <= threshold {
-            return Err("The value has to between 0 and 1");
+    /// Tags a category with the scripts it is written in, texts written mostly in other
+    /// scripts are not compared with it (see `script::dominant`). Categories are tagged
+    /// with the scripts of their profile when learned; without scripts a category is
+    /// compared with every text. Fails if there is no such category.
+    pub fn set_scripts(
+        &mut self,
+        name: &T,
+        scripts: Vec<Script>,
+    ) -> Result<()> {
+        match self.categories.iter_mut().find(|c| &c.name == name) {
+            Some(category) => {
+                category.scripts = scripts;
+                category.scripts.sort_unstable();
+                category.scripts.dedup();
+                Ok(())
+            }
+            None => Err(Error::UnknownCategory(display_name(name))),
         }
+    }
 
-        self.threshold = threshold;
-
-        Ok(())
+    /// Returns the metadata of a category, None if there is no such category
+    pub fn category_metadata(
+        &self,
+        name: &T,
+    ) -> Option<&HashMap<String, String>> {
+        self.categories
+            .iter()
+            .find(|c| &c.name == name)
+            .map(|c| &c.metadata)
     }
 
-    /// Returns a single category for a given text. If two categories or more categories
-    /// that are close together None will be returned.
-    pub fn get_category(&self, sample: &str) -> Option<T> {
-        if let Some(categories) = self.get_categories(sample) {
-            if categories.len() == 1 {
-                return Some(categories[0].0.to_owned());
+    /// Sets an entry of the metadata of a category (e.g. "iso" or "description"), which is
+    /// stored with the model. Fails if there is no such category.
+    pub fn set_category_metadata<K, V>(
+        &mut self,
+        name: &T,
+        key: K,
+        value: V,
+    ) -> Result<()>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        match self.categories.iter_mut().find(|c| &c.name == name) {
+            Some(category) => {
+                category.metadata.insert(key.into(), value.into());
+                Ok(())
             }
+            None => Err(Error::UnknownCategory(display_name(name))),
         }
+    }
 
-        None
+    /// Returns a new model with only the given categories, and the same settings.
+    pub fn filter_categories(&self, names: &[T]) -> Categories<T> {
+        let mut filtered = self.with_same_options();
+        filtered.categories = self
+            .categories
+            .iter()
+            .filter(|category| names.contains(&category.name))
+            .cloned()
+            .collect();
+        filtered.intern();
+        filtered
     }
 
-    /// Returns a sorted list of categories which are candidates and their score (the lower the better)
-    pub fn get_categories(&self, sample: &str) -> Option<Vec<(T, u64)>> {
-        let ngrams = Ngrams::new(sample, 5);
+    /// Removes the ngrams found in the profiles of `min_categories` categories or more, and
+    /// ranks the remaining ngrams of each profile again. Ngrams shared by most categories
+    /// say little about any of them, removing them helps to tell closely related categories
+    /// (e.g. Spanish and Portuguese) apart. `min_categories` must be at least 2.
+    pub fn prune_common(&mut self, min_categories: usize) -> Result<()> {
+        if min_categories < 2 {
+            return Err(Error::InvalidSetting(
+                "ngrams are pruned when found in 2 categories or more"
+                    .to_string(),
+            ));
+        }
+
+        let mut found: HashMap<String, usize> = HashMap::new();
+        for ngram in self.categories.iter().flat_map(|c| c.to_vec()) {
+            *found.entry(ngram.to_string()).or_insert(0) += 1;
+        }
+
+        for category in self.categories.iter_mut() {
+            category
+                .ngrams
+                .retain(|ngram| found[ngram] < min_categories);
+        }
+
+        self.pool = Interner::new();
+        self.intern();
+        Ok(())
+    }
+
+    /// Adds the categories of another model, e.g. to ship separately trained models in a
+    /// single file. Both models must have been trained with the same settings (ngram
+    /// lengths, profile size, metric, ...) and must not share category names, otherwise
+    /// nothing is merged. The threshold and the runtime settings of this model are kept.
+    pub fn merge(
+        &mut self,
+        other: Categories<T>,
+    ) -> core::result::Result<(), MergeError> {
+        fn check<V: PartialEq + core::fmt::Debug>(
+            setting: &'static str,
+            ours: V,
+            theirs: V,
+        ) -> core::result::Result<(), MergeError> {
+            if ours == theirs {
+                Ok(())
+            } else {
+                Err(MergeError::IncompatibleSettings {
+                    setting,
+                    ours: format!("{:?}", ours),
+                    theirs: format!("{:?}", theirs),
+                })
+            }
+        }
 
-        let mut categories = self
+        check("ngram lengths", self.ngram_lengths(), other.ngram_lengths())?;
+        check("profile size", self.profile_size, other.profile_size)?;
+        check("metric", self.metric, other.metric)?;
+        check(
+            "missing penalty",
+            self.missing_penalty,
+            other.missing_penalty,
+        )?;
+        check("boundaries", self.boundaries, other.boundaries)?;
+        check(
+            "diacritic folding",
+            self.fold_diacritics,
+            other.fold_diacritics,
+        )?;
+        check("normalization", &self.normalization, &other.normalization)?;
+        check("token mode", self.mode, other.mode)?;
+        check("stopwords", &self.stopwords, &other.stopwords)?;
+
+        if let Some(duplicate) = other
             .categories
             .iter()
-            .map(|category| (category.distance(&ngrams), category))
-            .collect::<Vec<(u64, &Category<T>)>>();
+            .find(|c| self.categories.iter().any(|own| own.name == c.name))
+        {
+            return Err(MergeError::DuplicateCategory(display_name(
+                &duplicate.name,
+            )));
+        }
+
+        // categories are moved whole, with their scripts and metadata
+        for mut category in other.categories {
+            category.ngrams.serialize_counts(self.counts);
+            category.intern(&mut self.pool);
+            self.index.add(self.categories.len(), &category.ids);
+            self.categories.push(category);
+        }
 
-        categories.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(())
+    }
 
-        let best_candidate = categories.first()?;
-        let threshold: u64 =
-            ((1.0 + self.threshold) * best_candidate.0 as f32) as u64;
+    /// Numbers the ngrams of all categories, and indexes them again.
+    fn intern(&mut self) {
+        self.index = InvertedIndex::new();
+        for (id, category) in self.categories.iter_mut().enumerate() {
+            category.intern(&mut self.pool);
+            self.index.add(id, &category.ids);
+        }
+    }
 
-        Some(
-            categories
-                .iter()
-                .filter(|p| threshold > p.0)
-                .map(|p| (p.1.name.clone(), p.0))
-                .collect(),
-        )
+    /// Converts the current structure into a vector (language, [ngrams])
+    pub fn to_vec(&self) -> Vec<(T, Vec<&str>)> {
+        self.categories
+            .iter()
+            .map(|category| (category.name.clone(), category.to_vec()))
+            .collect()
     }
 
-    /// Stores the categories in a JSON file.
-    pub fn persist(&self, output: &str) -> IoResult<()> {
-        let j = serde_json::to_string(&self)?;
-        File::create(output)?.write_all(j.as_bytes())?;
+    /// Sets how close to the best category other categories must be to make a text
+    /// ambiguous (see `Threshold`): a fraction of the best distance (3% by default, a plain
+    /// `f32` is a relative threshold), a fixed gap, or none at all. It is stored with the
+    /// model.
+    pub fn set_threshold<V: Into<Threshold>>(
+        &mut self,
+        threshold: V,
+    ) -> Result<()> {
+        let threshold = threshold.into();
+        if let Some(problem) = threshold.check() {
+            return Err(Error::InvalidSetting(problem.to_string()));
+        }
+
+        self.threshold = threshold;
         Ok(())
     }
 
-    /// Add sample text to learn a new category.
-    pub fn add_category(&mut self, name: T, sample: &str) {
-        self.categories.push(Category {
-            name,
-            ngrams: Ngrams::new(&<&str>::clone(&sample), 5),
-        });
+    /// Margin within which categories close to the best one are candidates too, see
+    /// `set_threshold`
+    pub fn threshold(&self) -> Threshold {
+        self.threshold
     }
 
-    /// Returns all categories in this file content
-    pub fn categories(&self) -> Vec<T> {
-        self.categories.iter().map(|r| r.name.clone()).collect()
+    /// Sets how ngrams touching word boundaries are weighted (see `Boundaries`). The setting
+    /// is stored with the model and used both to learn and to classify, so it should be set
+    /// before adding categories.
+    pub fn set_boundaries(&mut self, boundaries: Boundaries) {
+        self.boundaries = boundaries;
     }
-}
 
-/// Loads categories stored from a file.
-pub fn load<T>(path: &str) -> IoResult<Categories<T>>
-where
-    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
-{
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let u = serde_json::from_reader(reader)?;
+    /// Enables removing accents and other diacritics from texts ("cancion" then matches
+    /// "canción"). Like `set_boundaries` it is stored with the model and should be set
+    /// before adding categories.
+    pub fn set_fold_diacritics(&mut self, fold: bool) {
+        self.fold_diacritics = fold;
+    }
 
-    Ok(u)
-}
+    /// Sets the normalizers applied to texts before their ngrams are extracted (none by
+    /// default, see `Pipeline`), e.g. to strip the markup and URLs of scraped pages. They
+    /// run before diacritic folding. Like `set_boundaries` the pipeline is stored with the
+    /// model and should be set before adding categories.
+    pub fn set_normalization(&mut self, normalization: Pipeline) {
+        self.normalization = normalization;
+    }
 
-/// Learn categories from a given directory. In the directory all the files
-/// should have a 'sample' extensions.
-pub fn learn_from_directory(path: &str) -> IoResult<Categories<String>> {
-    let files = get_files_from_directory(path)?;
-    let mut content = Categories::new();
+    /// Normalizers applied to texts, see `set_normalization`
+    pub fn normalization(&self) -> &Pipeline {
+        &self.normalization
+    }
 
-    for p in files {
-        let mut buf: Vec<u8> = Vec::new();
+    /// Sets how many of the best ranked ngrams are kept in the profile of each category
+    /// (400 by default). Profiles are cut when categories are learned, so a model behaves
+    /// the same before and after being persisted. Existing categories are cut too.
+    pub fn set_profile_size(&mut self, size: usize) -> Result<()> {
+        if size == 0 {
+            return Err(Error::InvalidSetting(
+                "the profile size must be greater than 0".to_string(),
+            ));
+        }
 
-        let p = p.map_err(|_e| {
-            Error::new(ErrorKind::InvalidData, "failed reading glob path")
-        })?;
+        self.profile_size = size;
+        self.categories
+            .iter_mut()
+            .for_each(|category| category.ngrams.truncate(size));
+        Ok(())
+    }
 
-        let _bytes = File::open(p.as_path())?.read_to_end(&mut buf)?;
-        if let Some(Some(name)) = p.as_path().file_stem().map(|n| n.to_str()) {
-            let str = String::from_utf8_lossy(&buf).to_string();
-            content.add_category(name.to_string(), &str);
+    /// Sets the shortest and the longest character ngrams (in chars, 1 and 4 by default).
+    /// Like `set_boundaries` they are stored with the model and should be set before
+    /// adding categories.
+    pub fn set_ngram_lengths(&mut self, min: usize, max: usize) -> Result<()> {
+        if min == 0 || min > max || max > MAX_NGRAM_LENGTH {
+            return Err(Error::InvalidSetting(format!(
+                "ngram lengths must be within 1..={}, got {}..={}",
+                MAX_NGRAM_LENGTH, min, max
+            )));
         }
+
+        self.min_ngram_len = min;
+        self.max_ngram_len = max;
+        Ok(())
     }
 
-    Ok(content)
-}
+    /// Lengths of the character ngrams
+    fn ngram_lengths(&self) -> Range<usize> {
+        self.min_ngram_len..self.max_ngram_len + 1
+    }
 
-/// Returns all sample files in a given directory
-fn get_files_from_directory(path: &str) -> IoResult<Paths> {
-    glob(format!("{}/*.sample", path).as_str())
-        .map_err(|_p| Error::new(ErrorKind::InvalidData, "invalid data"))
-}
+    /// Sets which ngrams are extracted from texts (see `TokenMode`): character ngrams fit
+    /// languages, word ngrams fit topics. Like `set_boundaries` it is stored with the model
+    /// and should be set before adding categories.
+    pub fn set_token_mode(&mut self, mode: TokenMode) {
+        self.mode = mode;
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Sets how distances between categories and texts are measured (see `Metric`). The
+    /// divergence metrics compare ngram frequencies, which models loaded from a file only
+    /// approximate from the ranks. The metric is stored with the model.
+    pub fn set_metric(&mut self, metric: Metric) {
+        self.metric = metric;
+    }
 
-    #[test]
-    fn test_files_listing_in_path() {
-        let r: Vec<String> = get_files_from_directory(&"tests")
-            .expect("Some went wrong")
-            .map(|p| p.expect("read name"))
-            .map(|p| p.to_str().clone().expect("to string").to_string())
+    /// Sets the words left out of the texts, both when learning and classifying, e.g. the
+    /// most common words of a language which blur topic categories. Words are compared
+    /// lowercased, after folding diacritics. Like `set_boundaries` the stopwords are stored
+    /// with the model and should be set before adding categories.
+    pub fn set_stopwords<I, S>(&mut self, stopwords: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.stopwords = stopwords
+            .into_iter()
+            .map(|word| word.as_ref().to_lowercase())
             .collect();
+    }
 
-        assert_eq!(vec!["tests/english.sample", "tests/spanish.sample",], r);
+    /// Stores the profiles with the count of each ngram (the full format) instead of only the
+    /// ngrams in rank order (the compact format, by default). Training can then continue
+    /// from a persisted model as if it had never been persisted (see `add_sample`), and
+    /// frequency-based metrics see the real counts, at the cost of bigger files. Binary
+    /// models are always compact.
+    pub fn set_keep_counts(&mut self, keep: bool) {
+        self.counts = keep;
+        for category in self.categories.iter_mut() {
+            category.ngrams.serialize_counts(keep);
+        }
     }
 
-    #[test]
+    /// Sets the minimum length, in letters and digits, of the texts to classify (no minimum by
+    /// default). Shorter texts have too few ngrams to tell categories apart, `get_category`
+    /// and `get_categories` return None for them. It is a runtime setting, not stored with
+    /// the model.
+    pub fn set_min_input_len(&mut self, len: usize) {
+        self.min_input_len = len;
+    }
+
+    /// Sets what `get_category` and the other methods returning a single category return
+    /// when several categories are within the threshold of each other: nothing by default,
+    /// or the closest one as a best guess. It is a runtime setting, like the minimum input
+    /// length.
+    pub fn set_ambiguity_policy(&mut self, policy: AmbiguityPolicy) {
+        self.ambiguity = policy;
+    }
+
+    /// Enables the short-text mode for texts shorter than `len` letters and digits (off by
+    /// default): they are compared to the categories with unigrams and bigrams only, whose
+    /// ranks are more reliable than those of longer ngrams when there are few of them. Word
+    /// models are not affected. It is a runtime setting, like the minimum input length.
+    pub fn set_short_text_len(&mut self, len: usize) {
+        self.short_text_len = len;
+    }
+
+    /// Only measures the distance to the `size` categories sharing the most ngrams with the
+    /// text (all of them by default, 0). The model keeps an inverted index from each ngram
+    /// to its categories, so with hundreds of categories the shortlist is much cheaper than
+    /// the distances it saves. Categories left out can't be returned, a shortlist too short
+    /// may miss the best one. It is a runtime setting, like the minimum input length.
+    pub fn set_shortlist(&mut self, size: usize) {
+        self.shortlist = size;
+    }
+
+    /// Sets which parts of long texts are classified (the whole text by default, see
+    /// `Sampling`), so multi-megabyte documents are classified faster and their
+    /// boilerplate weighs less. It is applied by `get_categories` and the methods built on
+    /// it, and stored with the model.
+    pub fn set_sampling(&mut self, sampling: Sampling) -> Result<()> {
+        if let Some(problem) = sampling.check() {
+            return Err(Error::InvalidSetting(problem.to_string()));
+        }
+
+        self.sampling = sampling;
+        Ok(())
+    }
+
+    /// Parts of long texts which are classified, see `set_sampling`
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    /// Length of a text as counted by `set_min_input_len` and `set_short_text_len`
+    fn input_len(sample: &str) -> usize {
+        sample.chars().filter(|c| c.is_alphanumeric()).count()
+    }
+
+    /// Sets the out-of-place distance of an ngram missing from the text. It defaults to the
+    /// profile size (see `set_profile_size`), the largest distance of a misplaced ngram, so
+    /// it stays calibrated with larger profiles. It is stored with the model.
+    pub fn set_missing_penalty(&mut self, penalty: u64) -> Result<()> {
+        if penalty == 0 {
+            return Err(Error::InvalidSetting(
+                "the missing ngram penalty must be greater than 0".to_string(),
+            ));
+        }
+
+        self.missing_penalty = Some(penalty);
+        Ok(())
+    }
+
+    /// Out-of-place distance of an ngram missing from the text, see
+    /// `set_missing_penalty`
+    pub fn missing_penalty(&self) -> u64 {
+        self.missing_penalty.unwrap_or(self.profile_size as u64)
+    }
+
+    /// Normalizes a text and folds its diacritics, as configured for this model
+    fn normalize_text<'a>(&self, sample: &'a str) -> Cow<'a, str> {
+        let sample = self.normalization.normalize(sample);
+        if !self.fold_diacritics {
+            return sample;
+        }
+
+        match sample {
+            Cow::Borrowed(sample) => fold_diacritics(sample),
+            Cow::Owned(sample) => fold_diacritics(&sample).into_owned().into(),
+        }
+    }
+
+    /// Extracts the ngrams of a text, as configured for this model
+    pub(crate) fn profile(&self, sample: &str) -> Ngrams {
+        let sample = self.normalize_text(sample);
+
+        Ngrams::new_with_options(
+            &sample,
+            self.ngram_lengths(),
+            self.boundaries,
+            self.mode,
+            &self.stopwords,
+        )
+    }
+
+    /// Incremental version of `profile`
+    fn profile_builder(&self) -> NgramsBuilder {
+        NgramsBuilder::new(5)
+            .with_boundaries(self.boundaries)
+            .with_diacritic_folding(self.fold_diacritics)
+            .with_normalization(self.normalization.clone())
+            .with_lengths(self.ngram_lengths())
+            .with_mode(self.mode)
+            .with_stopwords(self.stopwords.clone())
+    }
+
+    /// Returns the profile of a category
+    pub(crate) fn profile_of(&self, name: &T) -> Option<&Ngrams> {
+        self.categories
+            .iter()
+            .find(|category| &category.name == name)
+            .map(|category| &category.ngrams)
+    }
+
+    /// Breaks the distance between a text and every category down to the ngrams of the
+    /// category profiles, to find out why the text was classified as it was.
+    pub fn explain(&self, sample: &str) -> Explanation<T> {
+        crate::explain::explain(self, sample)
+    }
+
+    /// Returns a single category for a given text. If two categories or more categories
+    /// that are close together None will be returned.
+    pub fn get_category(&self, sample: &str) -> Option<T> {
+        match self.detect(sample) {
+            Detection::Ambiguous(candidates)
+                if self.ambiguity != AmbiguityPolicy::ReturnNone =>
+            {
+                candidates.into_iter().next().map(|(name, _)| name)
+            }
+            detection => detection.category(),
+        }
+    }
+
+    /// Returns the categories of a text following the ambiguity policy (see
+    /// `set_ambiguity_policy`): the matching category, or for an ambiguous text none of
+    /// them, the closest one or every candidate, best first.
+    pub fn get_category_candidates(&self, sample: &str) -> Vec<T> {
+        match self.detect(sample) {
+            Detection::Match(name, _) => vec![name],
+            Detection::Ambiguous(candidates) => {
+                let take = match self.ambiguity {
+                    AmbiguityPolicy::ReturnNone => 0,
+                    AmbiguityPolicy::ReturnBest => 1,
+                    AmbiguityPolicy::ReturnAll => candidates.len(),
+                };
+                candidates
+                    .into_iter()
+                    .take(take)
+                    .map(|(name, _)| name)
+                    .collect()
+            }
+            Detection::Unknown | Detection::TooShort => Vec::new(),
+        }
+    }
+
+    /// Same as `get_category` for an HTML document, e.g. a scraped web page: only its
+    /// visible text is classified, without tags, scripts and styles (see
+    /// `markup::html_text`).
+    #[cfg(feature = "markup")]
+    pub fn get_category_from_html(&self, html: &str) -> Option<T> {
+        self.get_category(&markup::html_text(html))
+    }
+
+    /// Same as `get_category` for a Markdown document: its code blocks, code spans and
+    /// link URLs are left out (see `markup::markdown_text`).
+    #[cfg(feature = "markup")]
+    pub fn get_category_from_markdown(&self, markdown: &str) -> Option<T> {
+        self.get_category(&markup::markdown_text(markdown))
+    }
+
+    /// Same as `get_category` for a text which may not be UTF-8, e.g. a legacy file. UTF-16
+    /// and UTF-8 with a byte-order mark are decoded, and with the `charset` feature
+    /// UTF-16 without one and Latin-1 too, instead of replacing their characters (see
+    /// `encoding::decode`).
+    pub fn get_category_from_bytes(&self, bytes: &[u8]) -> Option<T> {
+        self.get_category(&encoding::decode(bytes).0)
+    }
+
+    /// Classifies a text, telling apart a match, an ambiguous text, a model without
+    /// categories and a text too short to be classified.
+    pub fn detect(&self, sample: &str) -> Detection<T> {
+        self.detect_with(sample, None)
+    }
+
+    /// Classifies every line of a text, e.g. subtitles or log files, along with its line
+    /// number (starting at 1). Blank lines are classified too (usually as too short) so
+    /// numbers match the lines of the text. The profiles of the categories used for short
+    /// texts (see `set_short_text_len`) are extracted once for all the lines.
+    pub fn detect_lines<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl Iterator<Item = (usize, Detection<T>)> + 'a {
+        let mut short = None;
+        text.lines().enumerate().map(move |(i, line)| {
+            let short = if Self::input_len(line) < self.short_text_len
+                && self.mode != TokenMode::Word
+            {
+                Some(
+                    short
+                        .get_or_insert_with(|| self.short_profiles())
+                        .as_slice(),
+                )
+            } else {
+                None
+            };
+            (i + 1, self.detect_with(line, short))
+        })
+    }
+
+    /// Same as `detect`, with the short profiles of every category already extracted
+    /// (see `short_profiles`)
+    fn detect_with(
+        &self,
+        sample: &str,
+        short: Option<&[Profile<'_, T>]>,
+    ) -> Detection<T> {
+        if Self::input_len(sample) < self.min_input_len {
+            return Detection::TooShort;
+        }
+
+        let candidates = match self.categories_among(sample, short, |_| true) {
+            Some(candidates) if !candidates.is_empty() => candidates,
+            _ => return Detection::Unknown,
+        };
+        let mut candidates = candidates
+            .into_iter()
+            .filter_map(|(name, distance)| {
+                let category =
+                    self.categories.iter().find(|c| c.name == name)?;
+                let normalized = self.normalize(category, distance);
+                Some((
+                    name,
+                    Score {
+                        distance,
+                        normalized,
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        if candidates.len() == 1 {
+            let (name, score) = candidates.remove(0);
+            Detection::Match(name, score)
+        } else {
+            Detection::Ambiguous(candidates)
+        }
+    }
+
+    /// Same as `get_category`, along with a confidence from 0 to 1: one minus the distance
+    /// to the category relative to the largest possible distance (see
+    /// `Metric::max_distance`). A text with no ngram in common with the category gets 0.
+    pub fn get_category_with_confidence(
+        &self,
+        sample: &str,
+    ) -> Option<(T, f32)> {
+        let (name, score) = match self.detect(sample) {
+            Detection::Match(name, score) => (name, score),
+            Detection::Ambiguous(candidates)
+                if self.ambiguity != AmbiguityPolicy::ReturnNone =>
+            {
+                candidates.into_iter().next()?
+            }
+            _ => return None,
+        };
+
+        Some((name, 1.0 - score.normalized))
+    }
+
+    /// Distance relative to the largest distance a text can have to the category, from 0
+    /// to 1 (see `Metric::max_distance`).
+    fn normalize(&self, category: &Category<T>, distance: u64) -> f32 {
+        let worst = self.metric.max_distance_with_penalty(
+            &category.ngrams,
+            self.missing_penalty(),
+        );
+        if worst > 0 {
+            distance.min(worst) as f32 / worst as f32
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the category if there is a single candidate, or the closest one when the
+    /// ambiguity policy allows it
+    fn single(&self, candidates: Option<Vec<(T, u64)>>) -> Option<T> {
+        match candidates?.as_slice() {
+            [(category, _)] => Some(category.to_owned()),
+            [(category, _), ..]
+                if self.ambiguity != AmbiguityPolicy::ReturnNone =>
+            {
+                Some(category.to_owned())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the best category for a given text, even if it is ambiguous, along with the
+    /// margin to the runner-up. None is returned only if there are no categories.
+    pub fn get_best_match(&self, sample: &str) -> Option<BestMatch<T>> {
+        let distances = self.get_distances(sample);
+        let mut distances = distances.into_iter();
+        let (category, distance) = distances.next()?;
+
+        let (margin, relative_margin) = match distances.next() {
+            Some((_, second)) => {
+                let margin = second - distance;
+                let relative_margin = if distance > 0 {
+                    margin as f32 / distance as f32
+                } else if margin > 0 {
+                    f32::INFINITY
+                } else {
+                    0.0
+                };
+                (margin, relative_margin)
+            }
+            None => (u64::MAX, f32::INFINITY),
+        };
+
+        Some(BestMatch {
+            category,
+            distance,
+            margin,
+            relative_margin,
+        })
+    }
+
+    /// Returns a sorted list of categories which are candidates and their score (the lower the better)
+    pub fn get_categories(&self, sample: &str) -> Option<Vec<(T, u64)>> {
+        self.categories_among(sample, None, |_| true)
+    }
+
+    /// Same as `get_categories`, considering only the `allowed` categories. The other
+    /// categories are skipped before computing distances, so a short list is faster too.
+    pub fn get_categories_filtered(
+        &self,
+        sample: &str,
+        allowed: &[T],
+    ) -> Option<Vec<(T, u64)>> {
+        self.categories_among(sample, None, |name| allowed.contains(name))
+    }
+
+    /// Same as `get_categories`, skipping the `denied` categories
+    pub fn get_categories_excluding(
+        &self,
+        sample: &str,
+        denied: &[T],
+    ) -> Option<Vec<(T, u64)>> {
+        self.categories_among(sample, None, |name| !denied.contains(name))
+    }
+
+    /// Candidates among the categories for which `keep` returns true. Categories written in
+    /// other scripts than the text are skipped. The `short` profiles of every category, if
+    /// given, are used for short texts instead of extracting them again.
+    fn categories_among<F>(
+        &self,
+        sample: &str,
+        short: Option<&[Profile<'_, T>]>,
+        keep: F,
+    ) -> Option<Vec<(T, u64)>>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let len = Self::input_len(sample);
+        if len < self.min_input_len {
+            return None;
+        }
+
+        let sample = self.sampling.apply(sample);
+        let sample = sample.as_ref();
+        let script = script::dominant(sample);
+        let keep = |category: &Category<T>| {
+            category.accepts(script) && keep(&category.name)
+        };
+
+        if len < self.short_text_len && self.mode != TokenMode::Word {
+            let (ngrams, profiles) = match short {
+                Some(short) => (
+                    self.short_text_ngrams(sample),
+                    self.categories
+                        .iter()
+                        .zip(short)
+                        .filter(|(category, _)| keep(category))
+                        .map(|(_, profile)| Profile {
+                            name: profile.name,
+                            ngrams: Cow::Borrowed(profile.ngrams.as_ref()),
+                            ids: Cow::Borrowed(profile.ids.as_ref()),
+                        })
+                        .collect(),
+                ),
+                None => self.short_text_profiles(sample, keep),
+            };
+            return self.nearest(profiles, &ngrams);
+        }
+
+        let ngrams = self.profile(sample);
+        let profiles = self
+            .shortlisted(&ngrams, keep)
+            .into_iter()
+            .map(Category::profile)
+            .collect();
+        self.nearest(profiles, &ngrams)
+    }
+
+    /// Categories for which `keep` returns true, only the ones sharing the most ngrams
+    /// with the text when there are more than the shortlist size (see `set_shortlist`)
+    fn shortlisted<F>(&self, ngrams: &Ngrams, keep: F) -> Vec<&Category<T>>
+    where
+        F: Fn(&Category<T>) -> bool,
+    {
+        if self.shortlist == 0 || self.categories.len() <= self.shortlist {
+            return self.categories.iter().filter(|c| keep(c)).collect();
+        }
+
+        self.index
+            .shortlist(
+                ngrams
+                    .to_vec()
+                    .into_iter()
+                    .filter_map(|ngram| self.pool.id(ngram)),
+                self.categories.len(),
+                self.shortlist,
+                |id| keep(&self.categories[id]),
+            )
+            .into_iter()
+            .map(|id| &self.categories[id])
+            .collect()
+    }
+
+    /// Distances of every category to a short text, comparing only the shortest ngrams
+    /// (see `set_short_text_len`)
+    fn short_text_distances<F>(&self, sample: &str, keep: F) -> Vec<(T, u64)>
+    where
+        F: Fn(&Category<T>) -> bool,
+    {
+        let (ngrams, profiles) = self.short_text_profiles(sample, keep);
+
+        self.distances_to(
+            profiles
+                .iter()
+                .map(|profile| (profile.name, profile.ngrams.as_ref()))
+                .collect(),
+            &ngrams,
+        )
+    }
+
+    /// Shortest ngrams of a short text and of every category profile, see
+    /// `short_text_distances`
+    fn short_text_profiles<F>(
+        &self,
+        sample: &str,
+        keep: F,
+    ) -> (Ngrams, Vec<Profile<'_, T>>)
+    where
+        F: Fn(&Category<T>) -> bool,
+    {
+        let profiles = self
+            .categories
+            .iter()
+            .filter(|category| keep(category))
+            .map(|category| self.short_profile(category))
+            .collect::<Vec<_>>();
+
+        (self.short_text_ngrams(sample), profiles)
+    }
+
+    /// Longest ngram compared on short texts, see `short_text_distances`
+    fn short_ngram_len(&self) -> usize {
+        self.min_ngram_len.max(2).min(self.max_ngram_len)
+    }
+
+    /// Shortest ngrams of a short text, see `short_text_distances`
+    fn short_text_ngrams(&self, sample: &str) -> Ngrams {
+        let sample = self.normalize_text(sample);
+        Ngrams::new_with_options(
+            &sample,
+            self.min_ngram_len..self.short_ngram_len() + 1,
+            self.boundaries,
+            self.mode,
+            &self.stopwords,
+        )
+    }
+
+    /// Shortest ngrams of a category profile, see `short_text_distances`
+    fn short_profile<'a>(&self, category: &'a Category<T>) -> Profile<'a, T> {
+        let max = self.short_ngram_len();
+        let (short, ids): (Vec<&str>, Vec<u32>) = category
+            .to_vec()
+            .into_iter()
+            .zip(category.ids.iter().copied())
+            .filter(|(ngram, _)| ngram.chars().count() <= max)
+            .unzip();
+        Profile {
+            name: &category.name,
+            ngrams: Cow::Owned(Ngrams::from(short)),
+            ids: Cow::Owned(ids),
+        }
+    }
+
+    /// Short profiles of every category, in the same order
+    fn short_profiles(&self) -> Vec<Profile<'_, T>> {
+        self.categories
+            .iter()
+            .map(|category| self.short_profile(category))
+            .collect()
+    }
+
+    /// Same as `get_categories`, with the distances normalized from 0 (identical) to 1 (no
+    /// ngram in common). With the out-of-place metric the distance is divided by the profile
+    /// length times the missing ngram penalty, so scores of texts of different lengths, or
+    /// of different runs, can be compared.
+    pub fn get_categories_normalized(
+        &self,
+        sample: &str,
+    ) -> Option<Vec<(T, f32)>> {
+        let candidates = self.get_categories(sample)?;
+
+        Some(
+            candidates
+                .into_iter()
+                .filter_map(|(name, distance)| {
+                    let category =
+                        self.categories.iter().find(|c| c.name == name)?;
+                    Some((name, self.normalize(category, distance)))
+                })
+                .collect(),
+        )
+    }
+
+    /// Same as `get_categories` but for a text whose ngrams are already extracted.
+    pub fn get_categories_from_ngrams(
+        &self,
+        ngrams: &Ngrams,
+    ) -> Option<Vec<(T, u64)>> {
+        self.candidates(self.distances(ngrams))
+    }
+
+    /// Keeps the categories within the threshold of the best one
+    fn candidates(&self, categories: Vec<(T, u64)>) -> Option<Vec<(T, u64)>> {
+        let threshold = self.cutoff(categories.first()?.1);
+
+        Some(categories.into_iter().filter(|p| threshold > p.1).collect())
+    }
+
+    /// Distance a candidate must be below of to be within the threshold of the best one
+    fn cutoff(&self, best: u64) -> u64 {
+        self.threshold.cutoff(best)
+    }
+
+    /// Same as `candidates` of the distances of the given profiles to the ngrams. With the
+    /// out-of-place metric the ngrams are compared by id (see `positions`), the profiles
+    /// are sorted by the distance of their best ranked ngrams, a cheap lower bound of their
+    /// distance, and the distance of each one is only summed while it can still be within
+    /// the threshold of the best candidate so far (see `Ngrams::distance_with_cutoff`).
+    fn nearest(
+        &self,
+        profiles: Vec<Profile<'_, T>>,
+        ngrams: &Ngrams,
+    ) -> Option<Vec<(T, u64)>> {
+        // models deserialized by hand are not interned, see `prepare`
+        let interned = profiles.iter().all(|p| p.ids.len() == p.ngrams.len());
+        if !self.metric.is_out_of_place() || !interned {
+            let profiles = profiles
+                .iter()
+                .map(|profile| (profile.name, profile.ngrams.as_ref()))
+                .collect();
+            return self.candidates(self.distances_to(profiles, ngrams));
+        }
+
+        let penalty = self.missing_penalty();
+        let positions = self.positions(ngrams);
+        let position = |id: &u32| match positions[*id as usize] {
+            u32::MAX => penalty,
+            position => position as u64,
+        };
+
+        let mut profiles = profiles
+            .into_iter()
+            .enumerate()
+            .map(|(i, profile)| {
+                let bound = profile
+                    .ids
+                    .iter()
+                    .take(PREFILTER_NGRAMS)
+                    .map(position)
+                    .sum::<u64>();
+                (bound, i, profile)
+            })
+            .collect::<Vec<_>>();
+        profiles.sort_by_key(|(bound, i, _)| (*bound, *i));
+
+        let mut cutoff = u64::MAX;
+        let mut distances = Vec::new();
+        for (bound, i, profile) in profiles {
+            if bound > cutoff {
+                break;
+            }
+            let distance = profile.ids.iter().try_fold(0u64, |sum, id| {
+                Some(sum + position(id)).filter(|sum| *sum <= cutoff)
+            });
+            if let Some(distance) = distance {
+                cutoff = cutoff.min(self.cutoff(distance).max(distance));
+                distances.push((distance, i, profile.name));
+            }
+        }
+
+        // same order as `distances_to`, ties in the order of the categories
+        distances.sort_unstable_by_key(|(distance, i, _)| (*distance, *i));
+        self.candidates(
+            distances
+                .into_iter()
+                .map(|(distance, _, name)| (name.clone(), distance))
+                .collect(),
+        )
+    }
+
+    /// Positions of the ngrams of a text by id, `u32::MAX` for the ngrams missing from the
+    /// text. Ngrams of the text which no category has are left out.
+    fn positions(&self, ngrams: &Ngrams) -> Vec<u32> {
+        let mut positions = vec![u32::MAX; self.pool.len()];
+        for (position, ngram) in ngrams.to_vec().into_iter().enumerate() {
+            if let Some(id) = self.pool.id(ngram) {
+                let first = &mut positions[id as usize];
+                *first = (*first).min(position as u32);
+            }
+        }
+
+        positions
+    }
+
+    /// Returns true when the best candidate is unique and far enough (twice the threshold)
+    /// from the runner-up that more text is not going to change the result.
+    fn is_confident(&self, distances: &[(T, u64)]) -> bool {
+        match distances {
+            [(_, best), (_, second), ..] => {
+                *second >= self.threshold.widened(2).cutoff(*best)
+            }
+            [_] => true,
+            [] => false,
+        }
+    }
+
+    /// Classifies a text read from an async reader. The text is profiled as it arrives and
+    /// the result is returned as soon as it is clear (see `is_confident`), without waiting
+    /// for the end of the stream.
+    #[cfg(feature = "tokio")]
+    pub async fn classify_stream<R>(&self, mut reader: R) -> Result<Option<T>>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        use tokio::io::AsyncReadExt;
+
+        let mut builder = self.profile_builder();
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        let mut read = 0;
+
+        loop {
+            let bytes = reader.read(&mut buf).await?;
+            if bytes == 0 {
+                break;
+            }
+
+            builder.feed_bytes(&buf[..bytes]);
+            read += bytes;
+
+            if read >= MIN_STREAM_BYTES {
+                let distances = self.distances(&builder.ngrams());
+                if self.is_confident(&distances) {
+                    return Ok(distances.into_iter().next().map(|(c, _)| c));
+                }
+            }
+        }
+
+        Ok(self.single(self.get_categories_from_ngrams(&builder.finish())))
+    }
+
+    /// Same as `get_category` for a text read from a reader, e.g. a large file, which is
+    /// profiled chunk by chunk instead of being loaded in memory. With `early_stop` the
+    /// result is returned as soon as it is clear (see `is_confident`), without reading the
+    /// rest of the text.
+    #[cfg(feature = "std")]
+    pub fn get_category_from_reader<R: Read>(
+        &self,
+        mut reader: R,
+        early_stop: bool,
+    ) -> Result<Option<T>> {
+        let mut builder = self.profile_builder();
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        let mut read = 0;
+
+        loop {
+            let bytes = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            builder.feed_bytes(&buf[..bytes]);
+            read += bytes;
+
+            if early_stop && read >= MIN_STREAM_BYTES {
+                let distances = self.distances(&builder.ngrams());
+                if self.is_confident(&distances) {
+                    return Ok(distances.into_iter().next().map(|(c, _)| c));
+                }
+            }
+        }
+
+        Ok(self.single(self.get_categories_from_ngrams(&builder.finish())))
+    }
+
+    /// Estimates which proportion of a text belongs to each category, e.g. 70% english and
+    /// 30% french. The text is classified in windows of a few words, the weight of each window
+    /// (its length) is split between its candidates. Returns the categories sorted by their
+    /// share, best first; shares add up to 1.
+    pub fn composition(&self, sample: &str) -> Vec<(T, f32)> {
+        let mut shares: Vec<(T, f32)> = Vec::new();
+        let mut total = 0.0;
+
+        for window in word_windows(sample, COMPOSITION_WINDOW) {
+            let window = &sample[window];
+            let candidates = match self.get_categories(window) {
+                Some(candidates) if !candidates.is_empty() => candidates,
+                _ => continue,
+            };
+
+            let weight = window.chars().count() as f32;
+            total += weight;
+
+            let share = weight / candidates.len() as f32;
+            for (category, _) in candidates {
+                match shares.iter_mut().find(|(c, _)| *c == category) {
+                    Some((_, value)) => *value += share,
+                    None => shares.push((category, share)),
+                }
+            }
+        }
+
+        shares.iter_mut().for_each(|(_, share)| *share /= total);
+        shares.sort_by(|a, b| b.1.total_cmp(&a.1));
+        shares
+    }
+
+    /// Splits a text mixing categories (e.g. an English email quoting Spanish) into spans of
+    /// a single category. The text is classified in windows of `window_size` words, and
+    /// adjacent windows of the same category are merged. Returns the byte range of each
+    /// span, from its first word to its last, and its category (the best match, even if
+    /// ambiguous).
+    pub fn segment(
+        &self,
+        text: &str,
+        window_size: usize,
+    ) -> Vec<(Range<usize>, T)> {
+        self.merge_spans(text, word_windows(text, window_size))
+    }
+
+    /// Same as `segment`, classifying each sentence (split on Unicode sentence boundaries)
+    /// instead of windows of a fixed number of words, so spans end where the sentences do.
+    /// Consecutive sentences of the same category are merged.
+    pub fn detect_sentences(&self, text: &str) -> Vec<(Range<usize>, T)> {
+        self.merge_spans(text, sentences(text))
+    }
+
+    /// Classifies the given parts of a text, merging adjacent parts of the same category
+    fn merge_spans(
+        &self,
+        text: &str,
+        parts: Vec<Range<usize>>,
+    ) -> Vec<(Range<usize>, T)> {
+        let mut spans: Vec<(Range<usize>, T)> = Vec::new();
+
+        for part in parts {
+            let category = match self.get_best_match(&text[part.clone()]) {
+                Some(best) => best.category,
+                None => break,
+            };
+
+            match spans.last_mut() {
+                Some((span, last)) if *last == category => span.end = part.end,
+                _ => spans.push((part, category)),
+            }
+        }
+
+        spans
+    }
+
+    /// Returns every category and its distance to a given text, sorted by distance (the
+    /// lower the better). Unlike `get_categories` no threshold is applied.
+    pub fn get_distances(&self, sample: &str) -> Vec<(T, u64)> {
+        self.distances(&self.profile(sample))
+    }
+
+    /// Distances of every category to the given ngrams, best first. With the `rayon`
+    /// feature the distances are computed in parallel.
+    pub(crate) fn distances(&self, ngrams: &Ngrams) -> Vec<(T, u64)> {
+        self.distances_among(ngrams, |_| true)
+    }
+
+    /// Same as `distances`, for the categories for which `keep` returns true
+    fn distances_among<F>(&self, ngrams: &Ngrams, keep: F) -> Vec<(T, u64)>
+    where
+        F: Fn(&Category<T>) -> bool,
+    {
+        let profiles = self
+            .categories
+            .iter()
+            .filter(|category| keep(category))
+            .map(|category| (&category.name, &category.ngrams))
+            .collect::<Vec<_>>();
+
+        self.distances_to(profiles, ngrams)
+    }
+
+    /// Distances of the given (category, profile) pairs to the ngrams, best first
+    fn distances_to(
+        &self,
+        profiles: Vec<(&T, &Ngrams)>,
+        ngrams: &Ngrams,
+    ) -> Vec<(T, u64)> {
+        let (names, profiles): (Vec<&T>, Vec<&Ngrams>) =
+            profiles.into_iter().unzip();
+
+        #[cfg(feature = "rayon")]
+        let profiles = profiles.into_par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let profiles = profiles.into_iter();
+
+        let (metric, penalty) = (self.metric, self.missing_penalty());
+        let distances = profiles
+            .map(|profile| {
+                metric.distance_with_penalty(profile, ngrams, penalty)
+            })
+            .collect::<Vec<u64>>();
+
+        let mut categories = names
+            .into_iter()
+            .cloned()
+            .zip(distances)
+            .collect::<Vec<(T, u64)>>();
+
+        categories.sort_by(|a, b| a.1.cmp(&b.1));
+        categories
+    }
+
+    /// Stores the categories in a JSON file.
+    #[cfg(feature = "std")]
+    pub fn persist(&self, output: &str) -> Result<()> {
+        let j = serde_json::to_string(&self)?;
+        File::create(output)?.write_all(j.as_bytes())?;
+        Ok(())
+    }
+
+    /// Same as `persist`, writing the file with `tokio::fs` so the runtime is not blocked
+    #[cfg(feature = "tokio")]
+    pub async fn persist_async(&self, output: &str) -> Result<()> {
+        let j = serde_json::to_vec(&self)?;
+        tokio::fs::write(output, j).await?;
+        Ok(())
+    }
+
+    /// Stores the categories in a JSON file, atomically: the model is written to a temporary
+    /// file which then replaces the output, readers never see a partially written model.
+    #[cfg(feature = "std")]
+    pub fn persist_atomic(&self, output: &str) -> Result<()> {
+        let temporary = format!("{}.tmp-{}", output, std::process::id());
+        self.persist(&temporary)
+            .and_then(|_| Ok(std::fs::rename(&temporary, output)?))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&temporary);
+                e
+            })
+    }
+
+    /// Stores the categories in a YAML file (one ngram per line).
+    #[cfg(feature = "yaml")]
+    pub fn persist_yaml(&self, output: &str) -> Result<()> {
+        let y = serde_yaml::to_string(&self).map_err(Error::serde)?;
+        File::create(output)?.write_all(y.as_bytes())?;
+        Ok(())
+    }
+
+    /// Stores the categories in a TOML file.
+    #[cfg(feature = "toml")]
+    pub fn persist_toml(&self, output: &str) -> Result<()> {
+        let t = toml::to_string(&self).map_err(Error::serde)?;
+        File::create(output)?.write_all(t.as_bytes())?;
+        Ok(())
+    }
+
+    /// Stores the categories in a file of the given format. `load` detects the format by
+    /// itself.
+    #[cfg(feature = "std")]
+    pub fn persist_as(&self, output: &str, format: ModelFormat) -> Result<()> {
+        match format {
+            ModelFormat::Json => self.persist(output),
+            #[cfg(feature = "msgpack")]
+            ModelFormat::MsgPack => {
+                let m = rmp_serde::to_vec_named(&self).map_err(Error::serde)?;
+                File::create(output)?.write_all(&m)?;
+                Ok(())
+            }
+            #[cfg(feature = "cbor")]
+            ModelFormat::Cbor => {
+                let mut c = CBOR_MAGIC.to_vec();
+                ciborium::into_writer(&self, &mut c).map_err(Error::serde)?;
+                File::create(output)?.write_all(&c)?;
+                Ok(())
+            }
+            #[cfg(feature = "bincode")]
+            ModelFormat::Bincode => self.persist_binary(output),
+            #[allow(unreachable_patterns)]
+            format => Err(format.unsupported()),
+        }
+    }
+
+    /// Stores the categories in a JSON file compressed with zstd, about 10 times smaller.
+    /// `load` decompresses it by itself.
+    #[cfg(feature = "zstd")]
+    pub fn persist_compressed(&self, output: &str) -> Result<()> {
+        let j = serde_json::to_vec(&self)?;
+        let z = zstd::encode_all(j.as_slice(), ZSTD_LEVEL)?;
+        File::create(output)?.write_all(&z)?;
+        Ok(())
+    }
+
+    /// Stores the categories in a compact binary file (bincode), faster to load than JSON.
+    /// Each distinct ngram is stored once, profiles are lists of ngram ids. `load` detects
+    /// the format by itself.
+    #[cfg(feature = "bincode")]
+    pub fn persist_binary(&self, output: &str) -> Result<()> {
+        let mut symbols = Vec::new();
+        let mut ids = HashMap::new();
+        let mut categories = Vec::new();
+        for category in canonical(&self.categories)? {
+            let mut ngrams = Vec::new();
+            for ngram in category.to_vec() {
+                let id = *ids.entry(ngram).or_insert_with(|| {
+                    symbols.push(Cow::Borrowed(ngram));
+                    symbols.len() as u32 - 1
+                });
+                ngrams.push(id);
+            }
+
+            categories.push(BinaryCategory {
+                name: Cow::Borrowed(&category.name),
+                ngrams,
+                scripts: Cow::Borrowed(&category.scripts),
+                metadata: Cow::Borrowed(&category.metadata),
+            });
+        }
+
+        let model = BinaryModel {
+            version: Cow::Borrowed(&self.version),
+            symbols,
+            categories,
+            boundaries: self.boundaries,
+            fold_diacritics: self.fold_diacritics,
+            profile_size: self.profile_size,
+            min_ngram_len: self.min_ngram_len,
+            max_ngram_len: self.max_ngram_len,
+            mode: self.mode,
+            metric: self.metric,
+            missing_penalty: self.missing_penalty,
+            stopwords: Cow::Borrowed(&self.stopwords),
+            sampling: self.sampling,
+            normalization: Cow::Borrowed(&self.normalization),
+            threshold: self.threshold,
+        };
+        let b = binary_options().serialize(&model).map_err(Error::serde)?;
+
+        let mut file = File::create(output)?;
+        file.write_all(BINARY_MAGIC)?;
+        file.write_all(&[BINARY_VERSION])?;
+        file.write_all(&b)?;
+        Ok(())
+    }
+
+    /// Stores the categories in an archived (rkyv) file, whose profiles can be read in
+    /// place without deserializing the model (see `archive::access`). The ngram counts of
+    /// the full format are not kept. Load it with `load_archived`.
+    #[cfg(feature = "rkyv")]
+    pub fn persist_archived(&self, output: &str) -> Result<()> {
+        let profiles = self
+            .categories
+            .iter()
+            .map(|category| {
+                Ok(archive::Profile {
+                    name: serde_json::to_string(&category.name)?,
+                    ngrams: category
+                        .to_vec()
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                    scripts: serde_json::to_string(&category.scripts)?,
+                    metadata: serde_json::to_string(&category.metadata)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let model = archive::Model {
+            settings: serde_json::to_string(&self.with_same_options())?,
+            profiles,
+        };
+
+        File::create(output)?.write_all(&model.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Stores the categories encrypted (AES-256-GCM) with the given key. The file can only
+    /// be loaded with `load_encrypted` and the same key.
+    #[cfg(feature = "encryption")]
+    pub fn persist_encrypted(
+        &self,
+        output: &str,
+        key: &[u8; 32],
+    ) -> Result<()> {
+        use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+        use aes_gcm::Aes256Gcm;
+
+        let j = serde_json::to_vec(&self)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let encrypted = Aes256Gcm::new(key.into())
+            .encrypt(&nonce, j.as_slice())
+            .map_err(|_| Error::Unsupported("encryption failed".to_string()))?;
+
+        let mut file = File::create(output)?;
+        file.write_all(ENCRYPTED_MAGIC)?;
+        file.write_all(&nonce)?;
+        file.write_all(&encrypted)?;
+        Ok(())
+    }
+
+    /// Add sample text to learn a new category.
+    pub fn add_category(&mut self, name: T, sample: &str) {
+        let ngrams = self.profile(sample);
+        self.push_category(name, ngrams);
+    }
+
+    /// Learns categories from (category, text) pairs from any source, e.g. database rows
+    /// or HTTP responses. All the texts of a category are learned as a single sample, and
+    /// the categories are added in the order they first appear. With the `rayon` feature
+    /// the categories are profiled in parallel.
+    pub fn learn_from_iter<I, S>(&mut self, samples: I)
+    where
+        I: IntoIterator<Item = (T, S)>,
+        S: AsRef<str>,
+    {
+        let mut names: Vec<T> = Vec::new();
+        let mut texts: Vec<String> = Vec::new();
+        for (name, text) in samples {
+            match names.iter().position(|n| *n == name) {
+                Some(id) => {
+                    texts[id].push('\n');
+                    texts[id].push_str(text.as_ref());
+                }
+                None => {
+                    names.push(name);
+                    texts.push(text.as_ref().to_string());
+                }
+            }
+        }
+
+        // Profiling the samples is the expensive part, with the `rayon` feature it is done
+        // in parallel
+        let builder = self.profile_builder();
+        #[cfg(feature = "rayon")]
+        let profiles = texts.par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let profiles = texts.iter();
+
+        let profiles = profiles
+            .map(|text| {
+                let mut builder = builder.clone();
+                builder.feed(text);
+                builder.finish()
+            })
+            .collect::<Vec<Ngrams>>();
+
+        for (name, ngrams) in names.into_iter().zip(profiles) {
+            self.push_category(name, ngrams);
+        }
+    }
+
+    /// Same as `add_category`, with the metadata of the new category (see
+    /// `category_metadata`)
+    pub fn add_category_with_metadata(
+        &mut self,
+        name: T,
+        sample: &str,
+        metadata: HashMap<String, String>,
+    ) {
+        self.add_category(name, sample);
+        if let Some(category) = self.categories.last_mut() {
+            category.metadata = metadata;
+        }
+    }
+
+    /// Adds a sample text to a category, creating it if needed. The ngram counts of the
+    /// sample are multiplied by `weight` and merged into the category's counts, so that
+    /// e.g. curated samples count more than scraped ones. A weight of 0 is ignored.
+    ///
+    /// Models loaded from a file don't keep counts, only ranks; for their categories the
+    /// counts are derived from the ranks (see `Ngrams::with_rank_counts`).
+    pub fn add_sample(&mut self, name: T, sample: &str, weight: u64) {
+        if weight == 0 {
+            return;
+        }
+
+        let ngrams = self.profile(sample);
+        match self.categories.iter().position(|c| c.name == name) {
+            Some(id) => {
+                let existing = &self.categories[id].ngrams;
+                let existing = if existing.has_counts() {
+                    existing.clone()
+                } else {
+                    existing.with_rank_counts()
+                };
+                let mut merged = existing.merge(&ngrams, weight);
+                merged.truncate(self.profile_size);
+                merged.serialize_counts(self.counts);
+                self.index.remove(id, &self.categories[id].ids);
+
+                let category = &mut self.categories[id];
+                category.scripts = script::of_profile(merged.to_vec());
+                category.ngrams = merged;
+                category.intern(&mut self.pool);
+                self.index.add(id, &category.ids);
+            }
+            None => {
+                let empty = Ngrams::from_counts(HashMap::<SmolStr, u64>::new());
+                self.push_category(name, empty.merge(&ngrams, weight));
+            }
+        }
+    }
+
+    /// Improves an existing category with a new sample: its ngram counts are added to the
+    /// category's counts and the ngrams are ranked again, as if the category had been
+    /// learned from all its samples at once. Fails if there is no such category (unlike
+    /// `add_sample`, which creates it).
+    pub fn update_category(&mut self, name: &T, sample: &str) -> Result<()> {
+        if !self.categories.iter().any(|c| &c.name == name) {
+            return Err(Error::UnknownCategory(display_name(name)));
+        }
+
+        self.add_sample(name.clone(), sample, 1);
+        Ok(())
+    }
+
+    /// Add a sample text read from a reader (e.g. stdin) to learn a new category. The
+    /// text is processed as it is read.
+    #[cfg(feature = "std")]
+    pub fn add_category_from_reader<R: Read>(
+        &mut self,
+        name: T,
+        mut reader: R,
+    ) -> Result<()> {
+        let mut builder = self.profile_builder();
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(bytes) => builder.feed_bytes(&buf[..bytes]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.push_category(name, builder.finish());
+        Ok(())
+    }
+
+    /// Adds a category with an already extracted profile
+    fn push_category(&mut self, name: T, mut ngrams: Ngrams) {
+        ngrams.truncate(self.profile_size);
+        ngrams.serialize_counts(self.counts);
+
+        let mut category = Category::new(name, ngrams);
+        category.intern(&mut self.pool);
+        self.index.add(self.categories.len(), &category.ids);
+        self.categories.push(category);
+    }
+
+    /// Returns all categories in this file content
+    pub fn categories(&self) -> Vec<T> {
+        self.categories.iter().map(|r| r.name.clone()).collect()
+    }
+
+    /// Returns, for each category, its `limit` most distinctive ngrams: those ranked much
+    /// higher in its profile than in the other categories' profiles. Useful to review the
+    /// training data (e.g. boilerplate in another language inside a sample shows up here).
+    ///
+    /// The score of an ngram is the average of its relative position (0 first, 1 last or
+    /// not found) in the other profiles minus its relative position in the category's
+    /// profile, so it goes from -1 to 1.
+    pub fn audit(&self, limit: usize) -> Vec<(T, Vec<(&str, f32)>)> {
+        let others = self.categories.len().saturating_sub(1).max(1) as f32;
+
+        // Sum of the relative positions of every ngram of every category in the other
+        // categories, one other category at a time
+        let mut elsewhere = self
+            .categories
+            .iter()
+            .map(|category| vec![0f32; category.ids.len()])
+            .collect::<Vec<_>>();
+        let mut positions = vec![u32::MAX; self.pool.len()];
+        for (other_id, other) in self.categories.iter().enumerate() {
+            for (pos, ngram) in other.ids.iter().enumerate().rev() {
+                positions[*ngram as usize] = pos as u32;
+            }
+
+            let len = other.ids.len() as f32;
+            let categories = self.categories.iter().zip(&mut elsewhere);
+            for (id, (category, sums)) in categories.enumerate() {
+                if id == other_id {
+                    continue;
+                }
+                for (sum, ngram) in sums.iter_mut().zip(&category.ids) {
+                    *sum += match positions[*ngram as usize] {
+                        u32::MAX => 1.0,
+                        pos => pos as f32 / len,
+                    };
+                }
+            }
+
+            for ngram in other.ids.iter() {
+                positions[*ngram as usize] = u32::MAX;
+            }
+        }
+
+        self.categories
+            .iter()
+            .zip(elsewhere)
+            .map(|(category, elsewhere)| {
+                let len = category.ids.len() as f32;
+                let mut scores = category
+                    .to_vec()
+                    .into_iter()
+                    .zip(elsewhere)
+                    .enumerate()
+                    .map(|(own, (ngram, sum))| {
+                        (ngram, sum / others - own as f32 / len)
+                    })
+                    .collect::<Vec<_>>();
+
+                scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+                scores.truncate(limit);
+                (category.name.clone(), scores)
+            })
+            .collect()
+    }
+
+    /// Checks the model is usable: it was written by a compatible version of the library
+    /// (see `is_compatible_version`), there is at least one category, names are unique and
+    /// every category has a non-empty profile of sane ngrams. `load` calls it on every model.
+    pub fn validate(&self) -> Result<()> {
+        if !is_compatible_version(&self.version) {
+            return Err(Error::IncompatibleVersion(self.version.clone()));
+        }
+
+        if self.categories.is_empty() {
+            return Err(Error::invalid("model has no categories".to_string()));
+        }
+
+        if self.profile_size == 0 {
+            return Err(Error::invalid("the profile size is 0".to_string()));
+        }
+
+        if self.missing_penalty == Some(0) {
+            return Err(Error::invalid("the missing ngram penalty is 0"));
+        }
+
+        if let Some(problem) = self.sampling.check() {
+            return Err(Error::invalid(problem));
+        }
+
+        if let Some(problem) = self.threshold.check() {
+            return Err(Error::invalid(problem));
+        }
+
+        if self.min_ngram_len == 0
+            || self.min_ngram_len > self.max_ngram_len
+            || self.max_ngram_len > MAX_NGRAM_LENGTH
+        {
+            return Err(Error::invalid(format!(
+                "invalid ngram lengths {}..={}",
+                self.min_ngram_len, self.max_ngram_len
+            )));
+        }
+
+        for (id, category) in self.categories.iter().enumerate() {
+            let name = display_name(&category.name);
+            let context = format!("category {} '{}'", id, name);
+
+            if self.categories[..id]
+                .iter()
+                .any(|c| c.name == category.name)
+            {
+                return Err(Error::invalid(format!(
+                    "{}: duplicated name",
+                    context
+                )));
+            }
+
+            let ngrams = category.to_vec();
+            if ngrams.is_empty() {
+                return Err(Error::invalid(format!(
+                    "{}: empty profile",
+                    context
+                )));
+            }
+
+            let mut seen = HashSet::new();
+            for (pos, ngram) in ngrams.iter().enumerate() {
+                let length = ngram.chars().count();
+                if length == 0 || length > MAX_NGRAM_LENGTH {
+                    return Err(Error::invalid(format!(
+                        "{}: ngram {} has an invalid length ({})",
+                        context, pos, length
+                    )));
+                }
+
+                if !seen.insert(ngram) {
+                    return Err(Error::invalid(format!(
+                        "{}: ngram {} ({:?}) is duplicated",
+                        context, pos, ngram
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Categories<String> {
+    /// Same as `get_categories_filtered`, with the allowed categories given as languages:
+    /// a category is allowed if its name is one of them, or if both its name and one of
+    /// them stand for the same language (e.g. "english", "en" and "eng", see
+    /// `lang::Lang`).
+    pub fn get_categories_for_languages(
+        &self,
+        sample: &str,
+        languages: &[&str],
+    ) -> Option<Vec<(String, u64)>> {
+        let languages = languages
+            .iter()
+            .map(|name| (*name, Lang::find(name)))
+            .collect::<Vec<_>>();
+
+        self.categories_among(sample, None, |category| {
+            let lang = Lang::find(category);
+            languages.iter().any(|(name, language)| {
+                *name == category.as_str()
+                    || (lang.is_some() && *language == lang)
+            })
+        })
+    }
+
+    /// Returns the language of a text, if the category it belongs to (see `get_category`)
+    /// is named after a known language
+    pub fn get_language(&self, sample: &str) -> Option<Lang> {
+        Lang::find(&self.get_category(sample)?)
+    }
+}
+
+/// Builds an empty model with its settings, which are otherwise set one by one with the
+/// `Categories::set_*` methods:
+///
+/// ```
+/// use textcat::category::{Categories, CategoriesBuilder};
+/// use textcat::ngram::TokenMode;
+///
+/// let categories: Categories<String> = CategoriesBuilder::new()
+///     .with_ngram_lengths(1, 5)
+///     .with_profile_size(800)
+///     .with_threshold(0.05)
+///     .with_token_mode(TokenMode::Mixed)
+///     .build()
+///     .expect("valid settings");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CategoriesBuilder {
+    threshold: Threshold,
+    profile_size: usize,
+    ngram_lengths: (usize, usize),
+    boundaries: Boundaries,
+    fold_diacritics: bool,
+    normalization: Pipeline,
+    mode: TokenMode,
+    metric: Metric,
+    missing_penalty: Option<u64>,
+    stopwords: Vec<String>,
+    counts: bool,
+    sampling: Sampling,
+    min_input_len: usize,
+    short_text_len: usize,
+    shortlist: usize,
+    ambiguity: AmbiguityPolicy,
+}
+
+impl Default for CategoriesBuilder {
+    fn default() -> Self {
+        CategoriesBuilder {
+            threshold: Threshold::default(),
+            profile_size: DEFAULT_PROFILE_SIZE,
+            ngram_lengths: (DEFAULT_MIN_NGRAM_LEN, DEFAULT_MAX_NGRAM_LEN),
+            boundaries: Boundaries::All,
+            fold_diacritics: false,
+            normalization: Pipeline::new(),
+            mode: TokenMode::Char,
+            metric: Metric::OutOfPlace,
+            missing_penalty: None,
+            stopwords: Vec::new(),
+            counts: false,
+            sampling: Sampling::Full,
+            min_input_len: 0,
+            short_text_len: 0,
+            shortlist: 0,
+            ambiguity: AmbiguityPolicy::ReturnNone,
+        }
+    }
+}
+
+impl CategoriesBuilder {
+    /// Creates a builder with the default settings
+    pub fn new() -> CategoriesBuilder {
+        CategoriesBuilder::default()
+    }
+
+    /// See `Categories::set_threshold`
+    pub fn with_threshold<V: Into<Threshold>>(
+        mut self,
+        threshold: V,
+    ) -> CategoriesBuilder {
+        self.threshold = threshold.into();
+        self
+    }
+
+    /// See `Categories::set_profile_size`
+    pub fn with_profile_size(mut self, size: usize) -> CategoriesBuilder {
+        self.profile_size = size;
+        self
+    }
+
+    /// See `Categories::set_ngram_lengths`
+    pub fn with_ngram_lengths(
+        mut self,
+        min: usize,
+        max: usize,
+    ) -> CategoriesBuilder {
+        self.ngram_lengths = (min, max);
+        self
+    }
+
+    /// See `Categories::set_boundaries`
+    pub fn with_boundaries(
+        mut self,
+        boundaries: Boundaries,
+    ) -> CategoriesBuilder {
+        self.boundaries = boundaries;
+        self
+    }
+
+    /// See `Categories::set_fold_diacritics`
+    pub fn with_diacritic_folding(mut self, fold: bool) -> CategoriesBuilder {
+        self.fold_diacritics = fold;
+        self
+    }
+
+    /// See `Categories::set_normalization`
+    pub fn with_normalization(
+        mut self,
+        normalization: Pipeline,
+    ) -> CategoriesBuilder {
+        self.normalization = normalization;
+        self
+    }
+
+    /// See `Categories::set_token_mode`
+    pub fn with_token_mode(mut self, mode: TokenMode) -> CategoriesBuilder {
+        self.mode = mode;
+        self
+    }
+
+    /// See `Categories::set_metric`
+    pub fn with_metric(mut self, metric: Metric) -> CategoriesBuilder {
+        self.metric = metric;
+        self
+    }
+
+    /// See `Categories::set_missing_penalty`
+    pub fn with_missing_penalty(mut self, penalty: u64) -> CategoriesBuilder {
+        self.missing_penalty = Some(penalty);
+        self
+    }
+
+    /// See `Categories::set_stopwords`
+    pub fn with_stopwords<I, S>(mut self, stopwords: I) -> CategoriesBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.stopwords = stopwords
+            .into_iter()
+            .map(|word| word.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// See `Categories::set_keep_counts`
+    pub fn with_keep_counts(mut self, keep: bool) -> CategoriesBuilder {
+        self.counts = keep;
+        self
+    }
+
+    /// See `Categories::set_ambiguity_policy`
+    pub fn with_ambiguity_policy(
+        mut self,
+        policy: AmbiguityPolicy,
+    ) -> CategoriesBuilder {
+        self.ambiguity = policy;
+        self
+    }
+
+    /// See `Categories::set_min_input_len`
+    pub fn with_min_input_len(mut self, len: usize) -> CategoriesBuilder {
+        self.min_input_len = len;
+        self
+    }
+
+    /// See `Categories::set_sampling`
+    pub fn with_sampling(mut self, sampling: Sampling) -> CategoriesBuilder {
+        self.sampling = sampling;
+        self
+    }
+
+    /// See `Categories::set_short_text_len`
+    pub fn with_short_text_len(mut self, len: usize) -> CategoriesBuilder {
+        self.short_text_len = len;
+        self
+    }
+
+    /// See `Categories::set_shortlist`
+    pub fn with_shortlist(mut self, size: usize) -> CategoriesBuilder {
+        self.shortlist = size;
+        self
+    }
+
+    /// Creates the empty model. Fails if a setting is out of its valid range.
+    pub fn build<T>(self) -> Result<Categories<T>>
+    where
+        for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+    {
+        let mut categories = Categories::new();
+        categories.set_threshold(self.threshold)?;
+        categories.set_profile_size(self.profile_size)?;
+        categories
+            .set_ngram_lengths(self.ngram_lengths.0, self.ngram_lengths.1)?;
+        categories.set_boundaries(self.boundaries);
+        categories.set_fold_diacritics(self.fold_diacritics);
+        categories.set_normalization(self.normalization);
+        categories.set_token_mode(self.mode);
+        categories.set_metric(self.metric);
+        if let Some(penalty) = self.missing_penalty {
+            categories.set_missing_penalty(penalty)?;
+        }
+        categories.set_stopwords(self.stopwords);
+        categories.set_keep_counts(self.counts);
+        categories.set_sampling(self.sampling)?;
+        categories.set_min_input_len(self.min_input_len);
+        categories.set_short_text_len(self.short_text_len);
+        categories.set_shortlist(self.shortlist);
+        categories.set_ambiguity_policy(self.ambiguity);
+
+        Ok(categories)
+    }
+
+    /// Creates the model and learns it from a directory (see `learn_from_directory`)
+    #[cfg(feature = "training")]
+    pub fn learn_from_directory(
+        self,
+        path: &str,
+    ) -> Result<Categories<String>> {
+        learn_directory(self.build()?, path)
+    }
+
+    /// Same as `learn_from_directory`, measuring the accuracy of these settings with k-fold
+    /// cross-validation (see `eval::learn_with_cross_validation`)
+    #[cfg(feature = "training")]
+    pub fn learn_with_cross_validation(
+        self,
+        path: &str,
+        k: usize,
+    ) -> Result<(Categories<String>, CrossValidation<String>)> {
+        crate::eval::cross_validate(self.build()?, path, k)
+    }
+}
+
+/// Loads categories stored from a file, in any format (see `ModelFormat`) and maybe
+/// compressed (see `Categories::persist_compressed`): the format is detected from the
+/// content.
+#[cfg(feature = "std")]
+pub fn load<T>(path: &str) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    from_bytes(&std::fs::read(path)?)
+}
+
+/// Same as `load`, reading the file with `tokio::fs` and decoding it in a blocking task, so
+/// loading a large model does not block the runtime.
+#[cfg(feature = "tokio")]
+pub async fn load_async<T>(path: &str) -> Result<Categories<T>>
+where
+    for<'a> T:
+        PartialEq<T> + Serialize + Deserialize<'a> + Clone + Send + 'static,
+{
+    let bytes = tokio::fs::read(path).await?;
+    tokio::task::spawn_blocking(move || from_bytes(&bytes))
+        .await
+        .map_err(std::io::Error::from)?
+}
+
+/// Loads categories from the content of a model file, in any format (see `ModelFormat`).
+pub fn from_bytes<T>(bytes: &[u8]) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    prepare(decode(bytes)?)
+}
+
+/// Loads a model written by any version of the library, from the content of its file
+/// (in any format), and upgrades it to the current version. Settings added since the
+/// model was written take their default value, and categories are tagged with their
+/// scripts (see `Categories::set_scripts`). Persisting the result writes a model that
+/// `load` accepts.
+pub fn migrate<T>(bytes: &[u8]) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let mut u = decode(bytes)?;
+    u.version = env!("CARGO_PKG_VERSION").to_string();
+    for category in u.categories.iter_mut() {
+        if category.scripts.is_empty() {
+            category.scripts = script::of_profile(category.to_vec());
+        }
+    }
+
+    prepare(u)
+}
+
+/// Returns true if models written by the given version of the library can be loaded by
+/// this one. Versions are compatible as in semver: their first non-zero component (the
+/// major version, or the minor version of 0.x versions) must be the same.
+pub fn is_compatible_version(version: &str) -> bool {
+    fn compatibility(version: &str) -> Option<(u64, u64)> {
+        let mut parts = version.split('.').map(|part| part.parse::<u64>());
+        match (parts.next()?.ok()?, parts.next()?.ok()?) {
+            (0, minor) => Some((0, minor)),
+            (major, _) => Some((major, 0)),
+        }
+    }
+
+    let current = compatibility(env!("CARGO_PKG_VERSION"));
+    current.is_some() && compatibility(version) == current
+}
+
+/// Loads categories stored in a file of the given format, without detecting it.
+#[cfg(feature = "std")]
+pub fn load_as<T>(path: &str, format: ModelFormat) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    prepare(decode_as(&std::fs::read(path)?, format)?)
+}
+
+/// Deserializes the content of a model file, in any format and maybe compressed, without
+/// validating it
+fn decode<T>(bytes: &[u8]) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    if bytes.starts_with(ZSTD_MAGIC) {
+        return decode(&decompress(bytes)?);
+    }
+
+    decode_as(bytes, ModelFormat::detect(bytes))
+}
+
+/// Decompresses the content of a compressed model file
+#[cfg(feature = "zstd")]
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    Ok(zstd::decode_all(bytes)?)
+}
+
+/// Compressed models need the `zstd` feature
+#[cfg(not(feature = "zstd"))]
+fn decompress(_bytes: &[u8]) -> Result<Vec<u8>> {
+    Err(Error::Unsupported(
+        "compressed models need the zstd feature".to_string(),
+    ))
+}
+
+/// Deserializes the content of a model file of the given format, without validating it
+fn decode_as<T>(bytes: &[u8], format: ModelFormat) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    match format {
+        ModelFormat::Json => {
+            serde_json::from_slice(bytes).map_err(Error::serde)
+        }
+        #[cfg(feature = "msgpack")]
+        ModelFormat::MsgPack => {
+            rmp_serde::from_slice(bytes).map_err(Error::serde)
+        }
+        #[cfg(feature = "cbor")]
+        ModelFormat::Cbor => {
+            let bytes = bytes.strip_prefix(CBOR_MAGIC).unwrap_or(bytes);
+            ciborium::from_reader(bytes).map_err(Error::serde)
+        }
+        ModelFormat::Bincode => match bytes.strip_prefix(BINARY_MAGIC) {
+            Some(binary) => decode_binary(binary),
+            None => Err(Error::invalid("not a binary model")),
+        },
+        #[allow(unreachable_patterns)]
+        format => Err(format.unsupported()),
+    }
+}
+
+/// Deserializes the content of a binary model file, after its header
+#[cfg(feature = "bincode")]
+fn decode_binary<T>(bytes: &[u8]) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let bytes = match bytes.split_first() {
+        Some((&BINARY_VERSION, bytes)) => bytes,
+        Some((version, _)) => {
+            return Err(Error::Unsupported(format!(
+                "unsupported binary version {}",
+                version
+            )))
+        }
+        None => return Err(Error::invalid("truncated binary model")),
+    };
+    let model: BinaryModel<T> =
+        binary_options().deserialize(bytes).map_err(Error::serde)?;
+
+    let symbols = model.symbols;
+    let mut u = Categories::new();
+    u.version = model.version.into_owned();
+    for category in model.categories {
+        let ngrams = category
+            .ngrams
+            .iter()
+            .map(|id| symbols.get(*id as usize).map(|ngram| ngram.as_ref()))
+            .collect::<Option<Vec<&str>>>()
+            .ok_or_else(|| Error::invalid("unknown ngram in binary model"))?;
+
+        u.categories.push(Category {
+            name: category.name.into_owned(),
+            ngrams: ngrams.into(),
+            scripts: category.scripts.into_owned(),
+            metadata: category.metadata.into_owned(),
+            ids: Vec::new(),
+        });
+    }
+    u.boundaries = model.boundaries;
+    u.fold_diacritics = model.fold_diacritics;
+    u.profile_size = model.profile_size;
+    u.min_ngram_len = model.min_ngram_len;
+    u.max_ngram_len = model.max_ngram_len;
+    u.mode = model.mode;
+    u.metric = model.metric;
+    u.missing_penalty = model.missing_penalty;
+    u.stopwords = model.stopwords.into_owned();
+    u.sampling = model.sampling;
+    u.normalization = model.normalization.into_owned();
+    u.threshold = model.threshold;
+
+    Ok(u)
+}
+
+/// Encoding of binary models: integers, ngram ids and lengths included, are varints
+#[cfg(feature = "bincode")]
+fn binary_options() -> impl bincode::Options {
+    bincode::DefaultOptions::new()
+}
+
+/// Binary models need the `bincode` feature
+#[cfg(not(feature = "bincode"))]
+fn decode_binary<T>(_bytes: &[u8]) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    Err(ModelFormat::Bincode.unsupported())
+}
+
+/// Loads categories stored with `persist_archived`.
+#[cfg(feature = "rkyv")]
+pub fn load_archived<T>(path: &str) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let mut bytes = rkyv::util::AlignedVec::<{ archive::ALIGNMENT }>::new();
+    bytes.extend_from_slice(&std::fs::read(path)?);
+    let archived = archive::access(&bytes)?;
+
+    let mut u: Categories<T> = serde_json::from_str(archived.settings())?;
+    for profile in archived.profiles() {
+        u.categories.push(Category {
+            name: profile.name()?,
+            ngrams: profile.ngrams().collect::<Vec<_>>().into(),
+            scripts: profile.scripts()?,
+            metadata: profile.metadata()?,
+            ids: Vec::new(),
+        });
+    }
+
+    prepare(u)
+}
+
+/// Loads categories stored with `persist_encrypted`.
+#[cfg(feature = "encryption")]
+pub fn load_encrypted<T>(path: &str, key: &[u8; 32]) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    use aes_gcm::aead::{Aead, KeyInit};
+    use aes_gcm::{Aes256Gcm, Nonce};
+
+    let content = std::fs::read(path)?;
+    let content = content
+        .strip_prefix(ENCRYPTED_MAGIC)
+        .filter(|content| content.len() >= ENCRYPTED_NONCE_SIZE)
+        .ok_or_else(|| Error::invalid("not an encrypted model"))?;
+    let (nonce, encrypted) = content.split_at(ENCRYPTED_NONCE_SIZE);
+
+    let j = Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| Error::invalid("wrong key or corrupted file"))?;
+
+    from_bytes(&j)
+}
+
+/// Loads categories stored in a YAML file.
+#[cfg(feature = "yaml")]
+pub fn load_yaml<T>(path: &str) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let reader = BufReader::new(File::open(path)?);
+    let u = serde_yaml::from_reader(reader).map_err(Error::serde)?;
+
+    prepare(u)
+}
+
+/// Loads categories stored in a TOML file.
+#[cfg(feature = "toml")]
+pub fn load_toml<T>(path: &str) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let content = std::fs::read_to_string(path)?;
+    let u = toml::from_str(&content).map_err(Error::serde)?;
+
+    prepare(u)
+}
+
+/// Validates a freshly deserialized model and gets it ready to be used
+fn prepare<T>(mut u: Categories<T>) -> Result<Categories<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    u.validate()?;
+    u.intern();
+    u.set_keep_counts(u.counts);
+
+    Ok(u)
+}
+
+/// Summary of a training run over a corpus directory. Problems in the corpus would otherwise
+/// only show up later as a poor accuracy.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg(feature = "std")]
+pub struct TrainingReport {
+    /// Sample files used to learn a category
+    pub learned: Vec<PathBuf>,
+
+    /// Files which could not be used, and the reason
+    pub skipped: Vec<(PathBuf, String)>,
+
+    /// Sample files without any text to learn from (skipped)
+    pub empty: Vec<PathBuf>,
+
+    /// Sample files whose category was already learned from another file (skipped)
+    pub duplicates: Vec<PathBuf>,
+
+    /// Sample files which are not valid UTF-8. They were decoded as described in
+    /// `encoding::decode`, replacing the invalid sequences of the encoding.
+    pub non_utf8: Vec<PathBuf>,
+}
+
+#[cfg(feature = "std")]
+impl TrainingReport {
+    /// Returns true if every sample file was learned without issues
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty()
+            && self.empty.is_empty()
+            && self.duplicates.is_empty()
+            && self.non_utf8.is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for TrainingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} sample files learned", self.learned.len())?;
+        for (path, reason) in self.skipped.iter() {
+            writeln!(f, "skipped {}: {}", path.display(), reason)?;
+        }
+        for path in self.empty.iter() {
+            writeln!(f, "skipped {}: empty sample", path.display())?;
+        }
+        for path in self.duplicates.iter() {
+            writeln!(f, "skipped {}: duplicated category", path.display())?;
+        }
+        for path in self.non_utf8.iter() {
+            writeln!(f, "warning {}: invalid UTF-8 content", path.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Learn categories from a given directory. In the directory all the files
+/// should have a 'sample' extensions, or be grouped in a subdirectory per category
+/// (`english/*`). Fails with `Error::EmptyCorpus` if there was no
+/// sample to learn from.
+#[cfg(feature = "training")]
+pub fn learn_from_directory(path: &str) -> Result<Categories<String>> {
+    learn_directory(Categories::new(), path)
+}
+
+/// Same as `learn_from_directory`, learning in a blocking task so training a large corpus
+/// does not block the runtime
+#[cfg(all(feature = "training", feature = "tokio"))]
+pub async fn learn_from_directory_async(
+    path: &str,
+) -> Result<Categories<String>> {
+    let path = path.to_string();
+    tokio::task::spawn_blocking(move || learn_from_directory(&path))
+        .await
+        .map_err(std::io::Error::from)?
+}
+
+/// Learns the categories of a directory into an empty model, failing if there was nothing
+/// to learn
+#[cfg(feature = "training")]
+fn learn_directory(
+    content: Categories<String>,
+    path: &str,
+) -> Result<Categories<String>> {
+    let (content, report) = learn_directory_with_report(content, path)?;
+    if report.learned.is_empty() {
+        return Err(Error::EmptyCorpus(path.to_string()));
+    }
+
+    Ok(content)
+}
+
+/// Learn categories from a given directory (see `learn_from_directory`), returning alongside
+/// the categories a report of the problems found in the corpus.
+#[cfg(feature = "training")]
+pub fn learn_from_directory_with_report(
+    path: &str,
+) -> Result<(Categories<String>, TrainingReport)> {
+    learn_directory_with_report(Categories::new(), path)
+}
+
+/// Learns the categories of a directory into an empty model
+#[cfg(feature = "training")]
+fn learn_directory_with_report(
+    mut content: Categories<String>,
+    path: &str,
+) -> Result<(Categories<String>, TrainingReport)> {
+    let (samples, report) = read_samples(path)?;
+    content.learn_from_iter(samples);
+
+    Ok((content, report))
+}
+
+/// Reads the samples of a corpus directory, as (category, text) pairs, along with a report
+/// of the problems found in the corpus.
+///
+/// Each `*.sample` file is a category named after the file. Each subdirectory is a category
+/// too, named after the subdirectory, learned from all the files inside it (`english/*`).
+/// Files ending in `.gz` (`gzip` feature) or `.zst` (`zstd` feature) are decompressed, so
+/// `english.sample.gz` is the `english` category.
+#[cfg(feature = "training")]
+pub(crate) fn read_samples(
+    path: &str,
+) -> Result<(Vec<(String, String)>, TrainingReport)> {
+    let (samples, report) = read_sample_files(path)?;
+    let samples = samples
+        .into_iter()
+        .map(|(name, texts)| (name, texts.join("\n")))
+        .collect();
+
+    Ok((samples, report))
+}
+
+/// Same as `read_samples`, keeping apart the text of each file of a category
+#[cfg(feature = "training")]
+pub(crate) fn read_sample_files(
+    path: &str,
+) -> Result<(Vec<(String, Vec<String>)>, TrainingReport)> {
+    let files = get_files_from_directory(path)?.map(|p| (p, false));
+    let nested = get_nested_files_from_directory(path)?.map(|p| (p, true));
+    let mut report = TrainingReport::default();
+    let mut samples: Vec<(String, Vec<String>)> = Vec::new();
+    // Categories learned from a subdirectory, which may have several samples
+    let mut directories: HashSet<String> = HashSet::new();
+
+    for (p, is_nested) in files.chain(nested) {
+        let mut buf: Vec<u8> = Vec::new();
+
+        let p = match p {
+            Ok(p) if is_nested && !p.is_file() => continue,
+            Ok(p) => p,
+            Err(e) => {
+                report.skipped.push((e.path().to_path_buf(), e.to_string()));
+                continue;
+            }
+        };
+
+        if let Err(e) = read_sample_file(&p, &mut buf) {
+            report.skipped.push((p, e.to_string()));
+            continue;
+        }
+
+        let name = if is_nested {
+            p.parent().and_then(|dir| dir.file_name())
+        } else if compression(&p).is_some() {
+            p.file_stem().and_then(|stem| Path::new(stem).file_stem())
+        } else {
+            p.file_stem()
+        };
+        let name = match name.map(|n| n.to_str()) {
+            Some(Some(name)) => name.to_string(),
+            _ => {
+                report.skipped.push((p, "invalid file name".to_string()));
+                continue;
+            }
+        };
+
+        let existing =
+            samples.iter().position(|(existing, _)| *existing == name);
+        if existing.is_some() && !(is_nested && directories.contains(&name)) {
+            report.duplicates.push(p);
+            continue;
+        }
+
+        if core::str::from_utf8(&buf).is_err() {
+            report.non_utf8.push(p.clone());
+        }
+        let str = encoding::decode(&buf).0.into_owned();
+
+        if Ngrams::new(&str, 2).is_empty() {
+            report.empty.push(p);
+            continue;
+        }
+
+        match existing {
+            Some(id) => samples[id].1.push(str),
+            None => {
+                if is_nested {
+                    directories.insert(name.clone());
+                }
+                samples.push((name, vec![str]));
+            }
+        }
+        report.learned.push(p);
+    }
+
+    Ok((samples, report))
+}
+
+/// Labeled sample, one per line in JSON-lines training data
+#[derive(Deserialize)]
+#[cfg(feature = "std")]
+struct LabeledSample {
+    label: String,
+    text: String,
+}
+
+/// Learn categories from JSON-lines data, one `{"label": "...", "text": "..."}` object per
+/// line (empty lines are ignored). All the texts of a label are learned as a single sample.
+#[cfg(feature = "std")]
+pub fn learn_from_jsonl_reader<R: BufRead>(
+    reader: R,
+) -> Result<Categories<String>> {
+    let samples =
+        reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.as_ref().map_or(true, |line| !line.trim().is_empty())
+            })
+            .map(|(line_number, line)| {
+                let sample: LabeledSample = serde_json::from_str(&line?)
+                    .map_err(|source| Error::InvalidSample {
+                        line: line_number + 1,
+                        source,
+                    })?;
+                Ok((sample.label, sample.text))
+            });
+
+    learn_labeled(samples, "JSON-lines data")
+}
+
+/// Learn categories from a JSON-lines file (see `learn_from_jsonl_reader`)
+#[cfg(feature = "std")]
+pub fn learn_from_jsonl(path: &str) -> Result<Categories<String>> {
+    learn_from_jsonl_reader(BufReader::new(File::open(path)?))
+}
+
+/// Learn categories from a CSV file with a header row, taking the label and the text of
+/// each row from the columns named `label_column` and `text_column`. All the texts of a
+/// label are learned as a single sample.
+#[cfg(feature = "csv")]
+pub fn learn_from_csv(
+    path: &str,
+    label_column: &str,
+    text_column: &str,
+) -> Result<Categories<String>> {
+    let mut reader = csv::Reader::from_path(path).map_err(Error::serde)?;
+    let headers = reader.headers().map_err(Error::serde)?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| {
+                Error::InvalidSetting(format!("{}: no column {}", path, name))
+            })
+    };
+    let (label, text) = (column(label_column)?, column(text_column)?);
+
+    let samples = reader.into_records().map(|record| {
+        let record = record.map_err(Error::serde)?;
+        match (record.get(label), record.get(text)) {
+            (Some(label), Some(text)) => {
+                Ok((label.to_string(), text.to_string()))
+            }
+            _ => Err(Error::invalid(format!(
+                "{}: missing column in line {}",
+                path,
+                record.position().map_or(0, |p| p.line())
+            ))),
+        }
+    });
+
+    learn_labeled(samples, path)
+}
+
+/// Learns labeled texts into a new model, aggregating the ngram counts of all the texts of
+/// a label into its category. Fails with `Error::EmptyCorpus` if there was no text.
+#[cfg(feature = "std")]
+fn learn_labeled<I>(samples: I, source: &str) -> Result<Categories<String>>
+where
+    I: Iterator<Item = Result<(String, String)>>,
+{
+    let mut content = Categories::new();
+    let mut builders: Vec<(String, NgramsBuilder)> = Vec::new();
+
+    for sample in samples {
+        let (label, text) = sample?;
+        let position = builders.iter().position(|(l, _)| *l == label);
+        let builder = match position {
+            Some(position) => &mut builders[position].1,
+            None => {
+                builders.push((label, content.profile_builder()));
+                &mut builders.last_mut().expect("just pushed").1
+            }
+        };
+
+        builder.feed(&text);
+        builder.feed("\n");
+    }
+
+    if builders.is_empty() {
+        return Err(Error::EmptyCorpus(source.to_string()));
+    }
+
+    for (label, builder) in builders {
+        content.push_category(label, builder.finish());
+    }
+
+    Ok(content)
+}
+
+/// Extension of a compressed sample file
+#[cfg(feature = "training")]
+fn compression(path: &Path) -> Option<&str> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| *ext == "gz" || *ext == "zst")
+}
+
+/// Reads a sample file, decompressing it if needed (see `read_samples`)
+#[cfg(feature = "training")]
+fn read_sample_file(path: &Path, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+    let mut file = File::open(path)?;
+
+    match compression(path) {
+        #[cfg(feature = "gzip")]
+        Some("gz") => flate2::read::MultiGzDecoder::new(file).read_to_end(buf),
+        #[cfg(feature = "zstd")]
+        Some("zst") => zstd::stream::read::Decoder::new(file)?.read_to_end(buf),
+        Some(ext) => Err(std::io::Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                ".{} samples are not supported, see the crate features",
+                ext
+            ),
+        )),
+        None => file.read_to_end(buf),
+    }
+}
+
+/// Returns all sample files in a given directory, compressed ones included
+#[cfg(feature = "training")]
+fn get_files_from_directory(
+    path: &str,
+) -> Result<impl Iterator<Item = GlobResult>> {
+    let plain = glob(format!("{}/*.sample", path).as_str())?;
+    let gzip = glob(format!("{}/*.sample.gz", path).as_str())?;
+    let zstd = glob(format!("{}/*.sample.zst", path).as_str())?;
+
+    Ok(plain.chain(gzip).chain(zstd))
+}
+
+/// Returns all the files in the subdirectories of a given directory
+#[cfg(feature = "training")]
+fn get_nested_files_from_directory(
+    path: &str,
+) -> Result<impl Iterator<Item = GlobResult>> {
+    Ok(glob(format!("{}/*/*", path).as_str())?)
+}
+
+#[cfg(all(test, feature = "training"))]
+mod test {
+    use super::*;
+    use crate::normalize::Normalization;
+
+    /// Temporary file unique to the test process, removed when dropped
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let name = format!("textcat-{}-{}", std::process::id(), name);
+            TempFile(std::env::temp_dir().join(name))
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().expect("path")
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_files_listing_in_path() {
+        let r: Vec<String> = get_files_from_directory(&"tests")
+            .expect("Some went wrong")
+            .map(|p| p.expect("read name"))
+            .map(|p| p.to_str().clone().expect("to string").to_string())
+            .collect();
+
+        assert_eq!(vec!["tests/english.sample", "tests/spanish.sample",], r);
+    }
+
+    #[test]
     fn test_learn_from_directory() {
         learn_from_directory("tests").expect("failed to read file");
     }
+
+    #[test]
+    #[cfg(all(feature = "gzip", feature = "zstd"))]
+    fn test_learn_from_compressed_samples() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir()
+            .join(format!("textcat-compressed-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("german")).expect("mkdir");
+        let english = std::fs::read("tests/english.sample").expect("read");
+        let spanish = std::fs::read("tests/spanish.sample").expect("read");
+
+        let file = File::create(dir.join("english.sample.gz")).expect("create");
+        let mut gz =
+            flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        gz.write_all(&english).expect("compress");
+        gz.finish().expect("compress");
+        let zst = zstd::encode_all(&spanish[..], 0).expect("compress");
+        std::fs::write(dir.join("spanish.sample.zst"), zst).expect("write");
+        let zst = zstd::encode_all(&b"das Haus ist gross"[..], 0).expect("zst");
+        std::fs::write(dir.join("german/a.txt.zst"), zst).expect("write");
+
+        let path = dir.to_str().expect("path");
+        let (categories, report) =
+            learn_from_directory_with_report(path).expect("learn");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(3, report.learned.len());
+        assert_eq!(
+            vec!["english", "spanish", "german"],
+            categories.categories()
+        );
+        let expected = learn_from_directory("tests").expect("learn");
+        assert_eq!(
+            expected.get_distances("la casa es grande"),
+            categories
+                .get_distances("la casa es grande")
+                .into_iter()
+                .filter(|(name, _)| name != "german")
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_serialization_is_canonical() {
+        let mut a = Categories::new();
+        a.add_category("spanish".to_string(), "hola que tal");
+        a.add_category("english".to_string(), "hello how are you");
+
+        let mut b = Categories::new();
+        b.add_category("english".to_string(), "hello how are you");
+        b.add_category("spanish".to_string(), "hola que tal");
+
+        let a = serde_json::to_string(&a).expect("serialize");
+        let b = serde_json::to_string(&b).expect("serialize");
+
+        assert_eq!(a, b);
+        assert!(
+            a.find("english").expect("name") < a.find("spanish").expect("name")
+        );
+    }
+
+    #[test]
+    fn test_training_is_reproducible() {
+        let paths = ["run-1.json", "run-2.json"].map(TempFile::new);
+
+        for path in paths.iter() {
+            learn_from_directory("tests")
+                .expect("learn")
+                .persist(path.path())
+                .expect("persist");
+        }
+
+        let first = std::fs::read(paths[0].path()).expect("read");
+        assert_eq!(first, std::fs::read(paths[1].path()).expect("read"));
+    }
+
+    #[test]
+    fn test_version_check_and_migrate() {
+        assert!(is_compatible_version(env!("CARGO_PKG_VERSION")));
+        assert!(is_compatible_version("0.3.0"));
+        assert!(!is_compatible_version("0.2.9"));
+        assert!(!is_compatible_version("1.3.0"));
+        assert!(!is_compatible_version("unknown"));
+
+        let old = br#"{"version":"0.1.0","categories":[
+            {"name":"english","ngrams":["e","_t"]}]}"#;
+        assert!(matches!(
+            from_bytes::<String>(old),
+            Err(Error::IncompatibleVersion(v)) if v == "0.1.0"
+        ));
+
+        let migrated = migrate::<String>(old).expect("migrate");
+        assert_eq!(env!("CARGO_PKG_VERSION"), migrated.version);
+        let json = serde_json::to_vec(&migrated).expect("serialize");
+        assert_eq!(
+            migrated.to_vec(),
+            from_bytes::<String>(&json).expect("load").to_vec()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid: Categories<String> =
+            vec![("english".to_string(), vec!["e", "_t"])].into();
+        assert!(valid.validate().is_ok());
+
+        let empty: Categories<String> = Categories::new();
+        assert!(empty.validate().is_err());
+
+        let categories: Categories<String> = vec![
+            ("english".to_string(), vec!["e", "_t"]),
+            ("spanish".to_string(), vec![]),
+        ]
+        .into();
+        let err = categories.validate().expect_err("empty profile");
+        assert_eq!(
+            "invalid model: category 1 'spanish': empty profile",
+            err.to_string()
+        );
+
+        let categories: Categories<String> = vec![
+            ("english".to_string(), vec!["e"]),
+            ("english".to_string(), vec!["a"]),
+        ]
+        .into();
+        let err = categories.validate().expect_err("duplicated");
+        assert!(matches!(
+            err,
+            Error::InvalidModel(msg) if msg == "category 1 'english': duplicated name"
+        ));
+    }
+
+    #[test]
+    fn test_learn_from_subdirectories() {
+        let dir = std::env::temp_dir()
+            .join(format!("textcat-subdirectories-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("english")).expect("mkdir");
+        std::fs::create_dir_all(dir.join("spanish/nested")).expect("mkdir");
+        std::fs::write(dir.join("english/a.txt"), "the house").expect("write");
+        std::fs::write(dir.join("english/b.txt"), "is big").expect("write");
+        std::fs::write(dir.join("spanish/a.txt"), "la casa").expect("write");
+        std::fs::write(dir.join("spanish.sample"), "es grande").expect("write");
+        std::fs::write(dir.join("german.sample"), "das haus").expect("write");
+
+        let path = dir.to_str().expect("path");
+        let (categories, report) =
+            learn_from_directory_with_report(path).expect("learn");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            vec!["german", "spanish", "english"],
+            categories.categories()
+        );
+        assert_eq!(4, report.learned.len());
+        assert_eq!(vec![dir.join("spanish/a.txt")], report.duplicates);
+
+        let english = &categories.categories[2].ngrams;
+        assert!(english.position("_big").is_some());
+        assert!(english.position("_hou").is_some());
+    }
+
+    #[test]
+    fn test_empty_corpus() {
+        let err = learn_from_directory("src/bin").err().expect("no samples");
+        assert!(matches!(err, Error::EmptyCorpus(path) if path == "src/bin"));
+
+        let err = learn_from_jsonl_reader(&b"\n"[..]).err().expect("no lines");
+        assert!(matches!(err, Error::EmptyCorpus(_)));
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_fields() {
+        let path = TempFile::new("unknown-fields.json");
+        std::fs::write(
+            path.path(),
+            r#"{"version":"0.3.2","categories":[],"x":1}"#,
+        )
+        .expect("write model");
+
+        let err = load::<String>(path.path()).err().expect("invalid model");
+        assert!(matches!(err, Error::Serde(_)));
+        assert!(err.to_string().starts_with("malformed data: unknown field"));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_round_trip() {
+        let path = TempFile::new("model.yaml");
+        let path = path.path();
+        let json = TempFile::new("model-yaml.json");
+        let json = json.path();
+        let model = learn_from_directory("tests").expect("learn");
+        model.persist_yaml(path).expect("persist");
+        model.persist(json).expect("persist");
+
+        let loaded = load_yaml::<String>(path).expect("load");
+        let expected = load::<String>(json).expect("load");
+        assert_eq!(expected.to_vec(), loaded.to_vec());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_round_trip() {
+        let path = TempFile::new("model.toml");
+        let path = path.path();
+        let json = TempFile::new("model-toml.json");
+        let json = json.path();
+        let model = learn_from_directory("tests").expect("learn");
+        model.persist_toml(path).expect("persist");
+        model.persist(json).expect("persist");
+
+        let loaded = load_toml::<String>(path).expect("load");
+        let expected = load::<String>(json).expect("load");
+        assert_eq!(expected.to_vec(), loaded.to_vec());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_binary_round_trip() {
+        let path = TempFile::new("model.bin");
+        let path = path.path();
+        let mut model = learn_from_directory("tests").expect("learn");
+        model.set_metric(Metric::ChiSquare);
+        let sampling = Sampling::Head { bytes: 4096 };
+        model.set_sampling(sampling).expect("sampling");
+        let pipeline = Pipeline::new().then(Normalization::Nfkc);
+        model.set_normalization(pipeline.clone());
+        model.persist_binary(path).expect("persist");
+
+        let loaded = load::<String>(path).expect("load");
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let expected = from_bytes::<String>(&json).expect("load");
+        assert_eq!(expected.to_vec(), loaded.to_vec());
+        assert_eq!(Metric::ChiSquare, loaded.metric);
+        assert_eq!(sampling, loaded.sampling());
+        assert_eq!(&pipeline, loaded.normalization());
+
+        let content = std::fs::read(path).expect("read");
+        assert!(content.len() < json.len());
+        assert!(from_bytes::<String>(&content[..content.len() / 2]).is_err());
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn test_archived_round_trip() {
+        let path = TempFile::new("model.rkyv");
+        let path = path.path();
+        let mut model = learn_from_directory("tests").expect("learn");
+        model.set_metric(Metric::ChiSquare);
+        model.set_stopwords(["the"]);
+        model.persist_archived(path).expect("persist");
+
+        let loaded = load_archived::<String>(path).expect("load");
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let expected = from_bytes::<String>(&json).expect("load");
+        assert_eq!(expected.to_vec(), loaded.to_vec());
+        assert_eq!(Metric::ChiSquare, loaded.metric);
+        assert_eq!(model.stopwords, loaded.stopwords);
+
+        let text = "la casa es grande y tiene un jardín";
+        assert_eq!(expected.get_distances(text), loaded.get_distances(text));
+
+        let content = std::fs::read(path).expect("read");
+        std::fs::write(path, &content[..content.len() / 2]).expect("write");
+        assert!(load_archived::<String>(path).is_err());
+    }
+
+    #[cfg(all(feature = "msgpack", feature = "cbor"))]
+    #[test]
+    fn test_persist_as() {
+        let mut model = learn_from_directory("tests").expect("learn");
+        model.set_metric(Metric::Cosine);
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let expected = from_bytes::<String>(&json).expect("load");
+
+        for (format, name) in [
+            (ModelFormat::Json, "json"),
+            (ModelFormat::MsgPack, "msgpack"),
+            (ModelFormat::Cbor, "cbor"),
+        ] {
+            let path = TempFile::new(&format!("model.{}", name));
+            let path = path.path();
+            model.persist_as(path, format).expect("persist");
+
+            let content = std::fs::read(path).expect("read");
+            assert_eq!(format, ModelFormat::detect(&content));
+            let loaded = load::<String>(path).expect("load");
+            assert_eq!(expected.to_vec(), loaded.to_vec());
+            assert_eq!(Metric::Cosine, loaded.metric);
+            assert_eq!(
+                expected.to_vec(),
+                load_as::<String>(path, format).expect("load").to_vec()
+            );
+        }
+
+        // the full format keeps the counts in every self-describing format
+        model.set_keep_counts(true);
+        let counts = model.categories[0].ngrams.counts();
+        for format in [ModelFormat::MsgPack, ModelFormat::Cbor].iter() {
+            let path = TempFile::new("model-counts");
+            let path = path.path();
+            model.persist_as(path, *format).expect("persist");
+
+            let loaded = load::<String>(path).expect("load");
+            assert_eq!(counts, loaded.categories[0].ngrams.counts());
+            let json = serde_json::to_string(&loaded).expect("serialize");
+            assert!(!json.contains(",0]"));
+        }
+
+        let path = TempFile::new("model-as.json");
+        let path = path.path();
+        model.persist(path).expect("persist");
+        assert!(load_as::<String>(path, ModelFormat::Cbor).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed_round_trip() {
+        let path = TempFile::new("model.json.zst");
+        let path = path.path();
+        let model = learn_from_directory("tests").expect("learn");
+        model.persist_compressed(path).expect("persist");
+
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let content = std::fs::read(path).expect("read");
+        assert!(content.len() < json.len() / 2);
+
+        let loaded = load::<String>(path).expect("load");
+        let expected = from_bytes::<String>(&json).expect("load");
+        assert_eq!(expected.to_vec(), loaded.to_vec());
+        assert!(from_bytes::<String>(&content[..content.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let model = learn_from_directory("tests").expect("learn");
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+
+        assert_eq!(model.categories(), loaded.categories());
+        assert!(from_bytes::<String>(b"\xff\x00{").is_err());
+    }
+
+    #[test]
+    fn test_set_threshold() {
+        let mut model = learn_from_directory("tests").expect("learn");
+
+        assert!(model.set_threshold(0.5).is_ok());
+        assert!(model.set_threshold(0.0).is_err());
+        assert!(model.set_threshold(1.0).is_err());
+        assert!(model.set_threshold(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn test_threshold() {
+        let mut model = learn_from_directory("tests").expect("learn");
+        let json = serde_json::to_string(&model).expect("serialize");
+        assert!(!json.contains("threshold"));
+
+        model
+            .set_threshold(Threshold::AbsoluteGap(40))
+            .expect("gap");
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(Threshold::AbsoluteGap(40), loaded.threshold());
+
+        let distances = model.get_distances("la casa");
+        let best = distances[0].1;
+        let within = distances.iter().filter(|(_, d)| *d <= best + 40).count();
+        let candidates = model.get_categories("la casa").expect("candidates");
+        assert_eq!(within, candidates.len());
+
+        model.set_threshold(Threshold::None).expect("none");
+        assert_eq!(1, model.get_categories("la casa").expect("best").len());
+
+        let invalid = serde_json::to_vec(&serde_json::json!({
+            "categories": [{"name": "a", "ngrams": ["a"]}],
+            "threshold": {"relative": 2.0},
+        }))
+        .expect("serialize");
+        assert!(from_bytes::<String>(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_training_report() {
+        let dir = std::env::temp_dir()
+            .join(format!("textcat-training-report-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        std::fs::write(dir.join("english.sample"), "hello world")
+            .expect("write");
+        std::fs::write(dir.join("empty.sample"), " ... ").expect("write");
+        std::fs::write(dir.join("latin1.sample"), b"canci\xf3n")
+            .expect("write");
+
+        let (content, report) =
+            learn_from_directory_with_report(dir.to_str().expect("path"))
+                .expect("learn");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(vec!["english", "latin1"], content.categories());
+        assert_eq!(vec![dir.join("empty.sample")], report.empty);
+        assert_eq!(vec![dir.join("latin1.sample")], report.non_utf8);
+        assert!(!report.is_clean());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_classify_stream() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let sample = std::fs::read("tests/spanish.sample").expect("read");
+
+        let category = categories
+            .classify_stream(&sample[..])
+            .await
+            .expect("classify");
+        assert_eq!(Some("spanish".to_string()), category);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_learn_persist_and_load() {
+        let path = TempFile::new("model-async.json");
+        let path = path.path();
+        let model = learn_from_directory_async("tests").await.expect("learn");
+        assert_eq!(
+            learn_from_directory("tests").expect("learn").to_vec(),
+            model.to_vec()
+        );
+        assert!(learn_from_directory_async("/nonexistent").await.is_err());
+
+        model.persist_async(path).await.expect("persist");
+        let loaded = load_async::<String>(path).await.expect("load");
+        assert_eq!(
+            load::<String>(path).expect("load").to_vec(),
+            loaded.to_vec()
+        );
+        assert!(load_async::<String>("/nonexistent.json").await.is_err());
+    }
+
+    /// Reader which always fails
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(ErrorKind::Other, "broken"))
+        }
+    }
+
+    #[test]
+    fn test_get_category_from_reader() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let sample = std::fs::read("tests/spanish.sample").expect("read");
+        let text = String::from_utf8(sample.clone()).expect("utf-8");
+
+        let category = categories
+            .get_category_from_reader(&sample[..], false)
+            .expect("classify");
+        assert_eq!(categories.get_category(&text), category);
+
+        // Stops before reaching the invalid data at the end
+        let mut reader = (&sample[..]).chain(FailingReader);
+        let category = categories
+            .get_category_from_reader(&mut reader, true)
+            .expect("classify");
+        assert_eq!(Some("spanish".to_string()), category);
+        assert!(categories
+            .get_category_from_reader(FailingReader, true)
+            .is_err());
+    }
+
+    #[test]
+    fn test_word_windows() {
+        assert_eq!(
+            vec![2..10, 11..23, 24..28],
+            word_windows("  one, two three.  four five!", 2)
+        );
+        assert!(word_windows(" ... ", 2).is_empty());
+    }
+
+    #[test]
+    fn test_segment() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        categories.set_metric(Metric::JensenShannon);
+        let english = "The weather was nice and we walked along the river \
+            for hours, talking about the books we had been reading lately.";
+        let spanish = "El tiempo estaba muy agradable y caminamos junto al \
+            río durante horas, hablando de los libros que habíamos leído.";
+        let text = format!("{} {}", english, spanish);
+
+        // 21 english words, 3 windows of 7 words
+        let spans = categories.segment(&text, 7);
+        assert_eq!(
+            vec![
+                (0..english.len() - 1, "english".to_string()),
+                (english.len() + 1..text.len() - 1, "spanish".to_string())
+            ],
+            spans
+        );
+        assert!(categories.segment("", 12).is_empty());
+    }
+
+    #[test]
+    fn test_detect_sentences() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        categories.set_metric(Metric::JensenShannon);
+        let english = "The weather was nice and we walked along the river. \
+            We talked about the books we had been reading lately.";
+        let spanish = "El tiempo estaba muy agradable junto al río. \
+            Hablamos de los libros que habíamos leído.";
+        let text = format!("{}  {}\n", english, spanish);
+
+        assert_eq!(
+            vec![
+                (0..english.len(), "english".to_string()),
+                (english.len() + 2..text.len() - 1, "spanish".to_string())
+            ],
+            categories.detect_sentences(&text)
+        );
+        assert!(categories.detect_sentences(" ... ").is_empty());
+    }
+
+    #[test]
+    fn test_composition() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let text =
+            std::fs::read_to_string("tests/spanish.sample").expect("read");
+        let text = text.chars().take(3000).collect::<String>();
+
+        let composition = categories.composition(&text);
+        let total: f32 = composition.iter().map(|(_, share)| share).sum();
+
+        assert_eq!("spanish", composition[0].0);
+        assert!((total - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_boundaries_are_persisted() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.set_boundaries(Boundaries::Emphasize(2));
+        categories.add_category("english".to_string(), "hello world");
+
+        let json = serde_json::to_vec(&categories).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(Boundaries::Emphasize(2), loaded.boundaries);
+
+        let default = serde_json::to_string(&Categories::<String>::new())
+            .expect("serialize");
+        assert!(!default.contains("boundaries"));
+    }
+
+    #[test]
+    fn test_get_best_match() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let text = "el rápido zorro marrón salta sobre el perro perezoso";
+        let distances = categories.get_distances(text);
+        let best = categories.get_best_match(text).expect("best match");
+
+        assert_eq!(distances[0].0, best.category);
+        assert_eq!(distances[1].1 - distances[0].1, best.margin);
+        assert_eq!(
+            best.margin as f32 / best.distance as f32,
+            best.relative_margin
+        );
+
+        let single: Categories<String> =
+            vec![("english".to_string(), vec!["e"])].into();
+        let best = single.get_best_match("hello").expect("best match");
+        assert_eq!(u64::MAX, best.margin);
+        assert!(Categories::<String>::new()
+            .get_best_match("hello")
+            .is_none());
+    }
+
+    #[test]
+    fn test_audit() {
+        let categories: Categories<String> = vec![
+            ("english".to_string(), vec!["e", "th", "a", "_the"]),
+            ("spanish".to_string(), vec!["e", "a", "ñ", "th"]),
+        ]
+        .into();
+
+        let audit = categories.audit(2);
+        assert_eq!("english", audit[0].0);
+        assert_eq!(vec![("th", 0.5), ("_the", 0.25)], audit[0].1);
+        assert_eq!(vec![("ñ", 0.5), ("a", 0.25)], audit[1].1);
+    }
+
+    #[test]
+    fn test_add_category_from_reader() {
+        let sample = std::fs::read("tests/spanish.sample").expect("read");
+        let mut from_reader = Categories::new();
+        from_reader
+            .add_category_from_reader("spanish".to_string(), &sample[..])
+            .expect("learn");
+
+        let mut from_str = Categories::new();
+        from_str.add_category(
+            "spanish".to_string(),
+            &String::from_utf8_lossy(&sample),
+        );
+
+        assert_eq!(from_str.to_vec(), from_reader.to_vec());
+    }
+
+    #[test]
+    fn test_learn_from_jsonl_reader() {
+        let data = r#"{"label": "english", "text": "hello world"}
+
+{"label": "spanish", "text": "hola mundo"}
+{"label": "english", "text": "good bye"}
+"#;
+        let content = learn_from_jsonl_reader(data.as_bytes()).expect("learn");
+        assert_eq!(vec!["english", "spanish"], content.categories());
+        assert!(content.categories[0].ngrams.position("_bye").is_some());
+
+        let err = learn_from_jsonl_reader(&b"{}\n"[..])
+            .err()
+            .expect("invalid");
+        assert!(err.to_string().starts_with("line 1: missing field"));
+    }
+
+    #[test]
+    fn test_learn_from_iter() {
+        let rows = vec![
+            (1, "the house is big"),
+            (2, "la casa es grande"),
+            (1, "where is the station"),
+        ];
+        let mut content: Categories<u32> = Categories::new();
+        content.learn_from_iter(rows.iter().copied());
+        assert_eq!(vec![1, 2], content.categories());
+
+        let mut expected: Categories<u32> = Categories::new();
+        expected.add_category(1, "the house is big\nwhere is the station");
+        expected.add_category(2, "la casa es grande");
+        assert_eq!(expected.to_vec(), content.to_vec());
+    }
+
+    #[test]
+    fn test_learn_from_jsonl() {
+        let path = TempFile::new("samples.jsonl");
+        std::fs::write(
+            path.path(),
+            "{\"label\": \"english\", \"text\": \"hello world\"}\n",
+        )
+        .expect("write");
+
+        let content = learn_from_jsonl(path.path()).expect("learn");
+        assert_eq!(vec!["english"], content.categories());
+        assert!(learn_from_jsonl("/nonexistent.jsonl").is_err());
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_learn_from_csv() {
+        let path = TempFile::new("samples.csv");
+        let path = path.path();
+        std::fs::write(
+            path,
+            "id,lang,body\n\
+             1,english,hello world\n\
+             2,spanish,\"hola, mundo\"\n\
+             3,english,good bye\n",
+        )
+        .expect("write");
+
+        let content = learn_from_csv(path, "lang", "body").expect("learn");
+        assert_eq!(vec!["english", "spanish"], content.categories());
+        assert!(content.categories[0].ngrams.position("_bye").is_some());
+        assert!(content.categories[1].ngrams.position("a_mu").is_some());
+
+        let err = learn_from_csv(path, "label", "body").err().expect("column");
+        assert!(matches!(err, Error::InvalidSetting(_)));
+
+        std::fs::write(path, "lang,body\n").expect("write");
+        let err = learn_from_csv(path, "lang", "body").err().expect("empty");
+        assert!(matches!(err, Error::EmptyCorpus(_)));
+    }
+
+    #[test]
+    fn test_retain_and_filter_categories() {
+        let mut categories: Categories<String> = vec![
+            ("english".to_string(), vec!["e", "th"]),
+            ("spanish".to_string(), vec!["e", "ñ"]),
+            ("german".to_string(), vec!["e", "ß"]),
+        ]
+        .into();
+        categories.set_boundaries(Boundaries::Only);
+
+        let filtered = categories
+            .filter_categories(&["german".to_string(), "english".to_string()]);
+        assert_eq!(vec!["english", "german"], filtered.categories());
+        assert_eq!(Boundaries::Only, filtered.boundaries);
+
+        categories.retain(|name| name != "english");
+        assert_eq!(vec!["spanish", "german"], categories.categories());
+        assert_eq!(3, categories.pool.len());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut languages: Categories<String> =
+            vec![("english".to_string(), vec!["e", "th"])].into();
+        let mut spam: Categories<String> =
+            vec![("spam".to_string(), vec!["$", "win"])].into();
+        let name = "spam".to_string();
+        spam.set_category_metadata(&name, "source", "mailbox")
+            .expect("metadata");
+        spam.set_scripts(&name, vec![Script::Latin, Script::Greek])
+            .expect("scripts");
+
+        let mut small = Categories::new();
+        small.set_profile_size(10).expect("valid size");
+        assert_eq!(
+            Err(MergeError::IncompatibleSettings {
+                setting: "profile size",
+                ours: "400".to_string(),
+                theirs: "10".to_string(),
+            }),
+            languages.merge(small)
+        );
+
+        languages.merge(spam.clone()).expect("merge");
+        assert_eq!(vec!["english", "spam"], languages.categories());
+        assert_eq!(4, languages.pool.len());
+        assert_eq!(
+            Some(&"mailbox".to_string()),
+            languages
+                .category_metadata(&name)
+                .expect("spam")
+                .get("source")
+        );
+        assert_eq!(spam.scripts(&name), languages.scripts(&name));
+        assert_eq!(
+            Some("spam".to_string()),
+            languages.get_category("win $ win $")
+        );
+
+        let err = languages.merge(spam).expect_err("duplicate");
+        assert_eq!("category spam is in both models", err.to_string());
+        assert_eq!(2, languages.categories().len());
+    }
+
+    #[test]
+    fn test_remove_and_rename_category() {
+        let mut categories: Categories<String> = vec![
+            ("english".to_string(), vec!["e", "th"]),
+            ("spanish".to_string(), vec!["e", "ñ"]),
+            ("german".to_string(), vec!["e", "ß"]),
+        ]
+        .into();
+
+        assert!(categories.remove_category(&"german".to_string()));
+        assert!(!categories.remove_category(&"german".to_string()));
+        assert_eq!(3, categories.pool.len());
+
+        categories
+            .rename_category(&"spanish".to_string(), "es".to_string())
+            .expect("rename");
+        assert_eq!(vec!["english", "es"], categories.categories());
+        assert!(categories
+            .rename_category(&"es".to_string(), "english".to_string())
+            .is_err());
+        assert!(matches!(
+            categories.rename_category(&"spanish".to_string(), "x".to_string()),
+            Err(Error::UnknownCategory(_))
+        ));
+    }
+
+    #[test]
+    fn test_prune_common() {
+        let mut categories: Categories<String> = vec![
+            ("spanish".to_string(), vec!["e", "a", "ñ", "o"]),
+            ("portuguese".to_string(), vec!["e", "ã", "a", "o"]),
+            ("catalan".to_string(), vec!["e", "à", "l·l", "a"]),
+        ]
+        .into();
+        assert!(categories.prune_common(1).is_err());
+
+        categories.prune_common(3).expect("prune");
+        assert_eq!(vec!["ñ", "o"], categories.categories[0].to_vec());
+        assert_eq!(Some(0), categories.categories[0].ngrams.position("ñ"));
+
+        categories.prune_common(2).expect("prune");
+        assert_eq!(vec!["ñ"], categories.categories[0].to_vec());
+        assert_eq!(vec!["ã"], categories.categories[1].to_vec());
+        assert_eq!(4, categories.pool.len());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_round_trip() {
+        let path = TempFile::new("model.enc");
+        let path = path.path();
+        let key = [7u8; 32];
+        let model = learn_from_directory("tests").expect("learn");
+        model.persist_encrypted(path, &key).expect("persist");
+
+        let content = std::fs::read(path).expect("read");
+        assert!(!String::from_utf8_lossy(&content).contains("english"));
+
+        let loaded = load_encrypted::<String>(path, &key).expect("load");
+        assert_eq!(model.categories(), loaded.categories());
+        assert!(load_encrypted::<String>(path, &[8u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_persist_atomic() {
+        let path = TempFile::new("atomic.json");
+        let path = path.path();
+        let model = learn_from_directory("tests").expect("learn");
+        model.persist_atomic(path).expect("persist");
+
+        assert_eq!(
+            model.categories(),
+            load::<String>(path).expect("load").categories()
+        );
+        assert!(model.persist_atomic("/nonexistent/dir/model.json").is_err());
+    }
+
+    #[test]
+    fn test_add_sample() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.add_sample("english".to_string(), "aaa bbb", 1);
+        categories.add_sample("english".to_string(), "bbb", 3);
+        categories.add_sample("english".to_string(), "ccc", 0);
+
+        let ngrams = &categories.categories[0].ngrams;
+        assert_eq!(1, categories.categories.len());
+        assert_eq!(Some(12), ngrams.ngram("b").map(|n| n.score()));
+        assert_eq!(Some(3), ngrams.ngram("a").map(|n| n.score()));
+        assert!(ngrams.position("c").is_none());
+    }
+
+    #[test]
+    fn test_update_category() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.add_category("english".to_string(), "the house");
+        let english = "english".to_string();
+        categories
+            .update_category(&english, "the big house")
+            .expect("update");
+
+        let expected = Ngrams::new("the house", 5)
+            .merge(&Ngrams::new("the big house", 5), 1);
+        assert_eq!(1, categories.categories.len());
+        assert_eq!(expected.counts(), categories.categories[0].ngrams.counts());
+        assert_eq!(expected.to_vec(), categories.categories[0].to_vec());
+
+        let error = categories
+            .update_category(&"spanish".to_string(), "la casa")
+            .expect_err("unknown category");
+        assert!(
+            matches!(error, Error::UnknownCategory(name) if name == "spanish")
+        );
+    }
+
+    #[test]
+    fn test_fold_diacritics() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.set_fold_diacritics(true);
+        categories.add_category("spanish".to_string(), "canción");
+
+        assert!(categories.categories[0].ngrams.position("ció").is_none());
+        assert_eq!(
+            categories.get_distances("canción"),
+            categories.get_distances("cancion")
+        );
+
+        let json = serde_json::to_vec(&categories).expect("serialize");
+        assert!(from_bytes::<String>(&json).expect("load").fold_diacritics);
+    }
+
+    #[test]
+    fn test_get_category_from_bytes() {
+        let model = learn_from_directory("tests").expect("learn");
+        let text = "El niño comió en la cocina, después salió al jardín \
+            con su señora madre para recoger las últimas flores del año.";
+        let expected = model.get_category(text);
+        assert_eq!(Some("spanish".to_string()), expected);
+
+        let utf16 = text.encode_utf16().flat_map(u16::to_le_bytes);
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(utf16);
+        assert_eq!(expected, model.get_category_from_bytes(&bytes));
+        assert_eq!(expected, model.get_category_from_bytes(text.as_bytes()));
+
+        #[cfg(feature = "charset")]
+        {
+            let latin1 = text.chars().map(|c| c as u8).collect::<Vec<_>>();
+            assert_eq!(
+                model.get_distances(text),
+                model.get_distances(&encoding::decode(&latin1).0)
+            );
+            assert_eq!(expected, model.get_category_from_bytes(&latin1));
+        }
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn test_get_category_from_markup() {
+        let model = learn_from_directory("tests").expect("learn");
+        let spanish = "El gobierno anunció hoy que la nueva ley entrará en \
+            vigor el próximo año, después de meses de debate en el congreso.";
+        let script =
+            "<script>var the = 'the house of the world and the';</script>";
+
+        let html = format!(
+            "<html><head>{}<style>.the {{ color: red }}</style></head>\
+            <body><p>{}</p></body></html>",
+            script, spanish
+        );
+        assert_eq!(
+            Some("spanish".to_string()),
+            model.get_category_from_html(&html)
+        );
+
+        let markdown = format!(
+            "{}\n\n```js\nthe.house.of(the_world);\nthe_end();\n```\n",
+            spanish
+        );
+        assert_eq!(
+            model.get_category(spanish),
+            model.get_category_from_markdown(&markdown)
+        );
+    }
+
+    #[test]
+    fn test_normalization() {
+        let pipeline = Pipeline::new()
+            .then(Normalization::StripHtml)
+            .then(Normalization::StripUrls);
+        let mut categories: Categories<String> = CategoriesBuilder::new()
+            .with_normalization(pipeline.clone())
+            .build()
+            .expect("build");
+        let html = "<p>la casa es <b>grande</b></p> https://example.com/casa";
+        categories.add_category("spanish".to_string(), html);
+        categories
+            .add_category_from_reader("stream".to_string(), html.as_bytes())
+            .expect("read");
+
+        assert_eq!(
+            categories.categories[0].to_vec(),
+            categories.categories[1].to_vec()
+        );
+        assert!(categories.categories[0].ngrams.position("<").is_none());
+        assert!(categories.categories[0].ngrams.position("http").is_none());
+
+        // tags split by a whitespace, or between chunks, are removed as a whole
+        let html = "hello world <span class=\"zzqq wwvv\">bonjour</span>";
+        let mut whole = categories.profile_builder();
+        whole.feed(html);
+        let mut chunks = categories.profile_builder();
+        html.split_inclusive(' ')
+            .for_each(|chunk| chunks.feed(chunk));
+        let profile = categories.profile(html);
+        assert!(profile.position("zzqq").is_none());
+        assert_eq!(profile.to_vec(), whole.finish().to_vec());
+        assert_eq!(profile.to_vec(), chunks.finish().to_vec());
+        assert_eq!(
+            categories.get_distances("la casa es grande"),
+            categories.get_distances("<i>la casa</i> es grande www.casa.es")
+        );
+
+        let json = serde_json::to_vec(&categories).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(&pipeline, loaded.normalization());
+        assert!(serde_json::to_string(&Categories::<String>::new())
+            .expect("serialize")
+            .find("normalization")
+            .is_none());
+    }
+
+    #[test]
+    fn test_get_category_with_confidence() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.add_category("english".to_string(), "the house is big");
+        categories.add_category("spanish".to_string(), "la casa es grande");
+
+        let (name, exact) = categories
+            .get_category_with_confidence("the house is big")
+            .expect("category");
+        assert_eq!("english", name);
+
+        let (_, partial) = categories
+            .get_category_with_confidence("the house")
+            .expect("category");
+        assert!(exact > partial);
+        assert!(partial > 0.0 && exact <= 1.0);
+        assert_eq!(
+            categories.get_category("the house"),
+            categories
+                .get_category_with_confidence("the house")
+                .map(|(name, _)| name)
+        );
+
+        // the confidence is measured on the sampled text, as the detection
+        let head = Sampling::Head { bytes: 16 };
+        categories.set_sampling(head).expect("sampling");
+        let text = "the house is big, la casa es grande y tiene un jardín";
+        let score = match categories.detect(text) {
+            Detection::Match(_, score) => score,
+            detection => panic!("{:?}", detection),
+        };
+        assert_eq!(
+            Some(("english".to_string(), 1.0 - score.normalized)),
+            categories.get_category_with_confidence(text)
+        );
+        assert_eq!(
+            Some(exact),
+            categories
+                .get_category_with_confidence(text)
+                .map(|(_, c)| c)
+        );
+    }
+
+    #[test]
+    fn test_get_categories_normalized() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let short = "la casa es grande";
+        let long = "la casa es grande, tiene un jardín con muchos árboles y \
+            una piscina donde los niños juegan todas las tardes de verano";
+
+        for text in [short, long] {
+            let raw = categories.get_categories(text).expect("categories");
+            let normalized = categories
+                .get_categories_normalized(text)
+                .expect("normalized");
+            assert_eq!(raw.len(), normalized.len());
+            assert_eq!(raw[0].0, normalized[0].0);
+            assert!(normalized.iter().all(|(_, d)| (0.0..=1.0).contains(d)));
+        }
+
+        let unrelated = categories
+            .get_categories_normalized("zzzz qqqq")
+            .expect("normalized");
+        assert!(unrelated.iter().all(|(_, d)| *d > 0.9));
+    }
+
+    #[test]
+    fn test_languages() {
+        let model = learn_from_directory("tests").expect("learn");
+        let text = "la casa es grande y tiene un jardín con muchos árboles";
+        assert_eq!(Some(Lang::Spa), model.get_language(text));
+
+        let only = |languages: &[&str]| {
+            model
+                .get_categories_for_languages(text, languages)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["english"], only(&["en"]));
+        assert_eq!(vec!["english"], only(&["ENG", "fra"]));
+        assert_eq!(vec!["spanish"], only(&["spanish"]));
+        assert!(only(&["de"]).is_empty());
+    }
+
+    #[test]
+    fn test_scripts() {
+        let mut model = learn_from_directory("tests").expect("learn");
+        assert_eq!(
+            Some(&[Script::Latin][..]),
+            model.scripts(&"english".into())
+        );
+
+        let chinese = "我们今天去公园散步然后吃饭";
+        assert_eq!(None, model.get_categories(chinese));
+        assert_eq!(None, model.get_category(chinese));
+
+        model
+            .set_scripts(&"spanish".into(), vec![])
+            .expect("set scripts");
+        assert_eq!(
+            vec!["spanish"],
+            model
+                .get_categories(chinese)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        );
+        assert!(model.set_scripts(&"german".into(), vec![]).is_err());
+
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(Some(&[][..]), loaded.scripts(&"spanish".into()));
+        let migrated = migrate::<String>(&json).expect("migrate");
+        assert_eq!(
+            Some(&[Script::Latin][..]),
+            migrated.scripts(&"spanish".into())
+        );
+    }
+
+    #[test]
+    fn test_category_metadata() {
+        let mut model = learn_from_directory("tests").expect("learn");
+        let english = "english".to_string();
+        assert!(model
+            .category_metadata(&english)
+            .expect("english")
+            .is_empty());
+
+        model
+            .set_category_metadata(&english, "iso", "en")
+            .expect("set metadata");
+        model
+            .set_category_metadata(&english, "description", "English")
+            .expect("set metadata");
+        assert!(model
+            .set_category_metadata(&"german".into(), "iso", "de")
+            .is_err());
+
+        let mut metadata = HashMap::new();
+        metadata.insert("iso".to_string(), "pt".to_string());
+        model.add_category_with_metadata(
+            "portuguese".to_string(),
+            "a casa é grande e tem um jardim",
+            metadata.clone(),
+        );
+
+        let json = serde_json::to_string(&model).expect("serialize");
+        assert!(
+            json.contains(r#""metadata":{"description":"English","iso":"en"}"#)
+        );
+        let loaded = from_bytes::<String>(json.as_bytes()).expect("load");
+        assert_eq!(
+            Some("en"),
+            loaded
+                .category_metadata(&english)
+                .and_then(|m| m.get("iso"))
+                .map(String::as_str)
+        );
+        assert_eq!(
+            Some(&metadata),
+            loaded.category_metadata(&"portuguese".into())
+        );
+        assert_eq!(None, loaded.category_metadata(&"german".into()));
+    }
+
+    #[test]
+    fn test_get_categories_filtered() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let text = "la casa es grande y tiene un jardín con muchos árboles";
+        let english = ["english".to_string()];
+
+        let allowed = categories
+            .get_categories_filtered(text, &english)
+            .expect("categories");
+        assert_eq!(
+            vec!["english"],
+            allowed.iter().map(|c| &c.0).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            categories
+                .get_distances(text)
+                .iter()
+                .find(|c| c.0 == "english"),
+            allowed.first()
+        );
+
+        let denied = categories
+            .get_categories_excluding(text, &["spanish".to_string()])
+            .expect("categories");
+        assert_eq!(allowed, denied);
+        assert_eq!(None, categories.get_categories_filtered(text, &[]));
+    }
+
+    #[test]
+    fn test_token_mode() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.set_token_mode(TokenMode::Word);
+        categories
+            .add_category("sports".to_string(), "the match ended in a goal");
+        categories
+            .add_category("cooking".to_string(), "bake the bread in the oven");
+
+        assert!(categories.categories[0].ngrams.position(" goal ").is_some());
+        assert_eq!(
+            Some("cooking".to_string()),
+            categories.get_category("put the bread in the oven")
+        );
+
+        let json = serde_json::to_vec(&categories).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(TokenMode::Word, loaded.mode);
+        assert_eq!(
+            categories.get_distances("a goal"),
+            loaded.get_distances("a goal")
+        );
+    }
+
+    #[test]
+    fn test_ngram_lengths() {
+        let mut categories: Categories<String> = Categories::new();
+        assert!(categories.set_ngram_lengths(0, 3).is_err());
+        assert!(categories.set_ngram_lengths(4, 3).is_err());
+        categories.set_ngram_lengths(2, 5).expect("valid lengths");
+        categories.add_category("english".to_string(), "the house");
+
+        let ngrams = categories.categories[0].to_vec();
+        assert!(ngrams.contains(&"_hous"));
+        assert!(ngrams.iter().all(|n| n.len() >= 2));
+
+        let json = serde_json::to_string(&categories).expect("serialize");
+        assert!(json.contains("\"min_ngram_len\":2,\"max_ngram_len\":5"));
+        let loaded = from_bytes::<String>(json.as_bytes()).expect("load");
+        assert_eq!(
+            categories.get_distances("a house"),
+            loaded.get_distances("a house")
+        );
+
+        let json = json.replace("\"max_ngram_len\":5", "\"max_ngram_len\":1");
+        assert!(from_bytes::<String>(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_profile_size() {
+        let text =
+            std::fs::read_to_string("tests/english.sample").expect("read");
+        let mut categories: Categories<String> = Categories::new();
+        categories.add_category("english".to_string(), &text);
+        assert_eq!(400, categories.categories[0].to_vec().len());
+
+        assert!(categories.set_profile_size(0).is_err());
+        categories.set_profile_size(800).expect("valid size");
+        categories.add_category("long".to_string(), &text);
+        assert_eq!(800, categories.categories[1].to_vec().len());
+
+        let json = serde_json::to_vec(&categories).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(800, loaded.profile_size);
+        assert_eq!(
+            categories.get_distances(&text),
+            loaded.get_distances(&text)
+        );
+
+        categories.set_profile_size(10).expect("valid size");
+        assert_eq!(10, categories.categories[0].to_vec().len());
+    }
+
+    #[test]
+    fn test_builder() {
+        let categories = CategoriesBuilder::new()
+            .with_ngram_lengths(2, 3)
+            .with_profile_size(50)
+            .with_threshold(0.1)
+            .with_token_mode(TokenMode::Mixed)
+            .with_metric(Metric::ChiSquare)
+            .learn_from_directory("tests")
+            .expect("learn");
+
+        assert_eq!(Threshold::Relative(0.1), categories.threshold());
+        assert_eq!(TokenMode::Mixed, categories.mode);
+        assert_eq!(Metric::ChiSquare, categories.metric);
+        assert_eq!(2..4, categories.ngram_lengths());
+        assert!(categories
+            .categories
+            .iter()
+            .all(|category| category.to_vec().len() == 50));
+
+        let invalid = CategoriesBuilder::new().with_threshold(2.0);
+        assert!(matches!(
+            invalid.build::<String>(),
+            Err(Error::InvalidSetting(_))
+        ));
+        assert!(CategoriesBuilder::new()
+            .with_profile_size(0)
+            .build::<String>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut categories: Categories<String> = Categories::new();
+        assert_eq!(Detection::Unknown, categories.detect("hola"));
+
+        categories.add_category("english".to_string(), "the house is big");
+        categories.add_category("spanish".to_string(), "la casa es grande");
+        match categories.detect("the house is big") {
+            Detection::Match(name, score) => {
+                assert_eq!("english", name);
+                assert!(score.normalized < 1.0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        categories.add_category("british".to_string(), "the house is big");
+        let ambiguous = categories.detect("the house");
+        assert!(matches!(ambiguous, Detection::Ambiguous(c) if c.len() == 2));
+        assert_eq!(None, categories.get_category("the house"));
+
+        categories.set_min_input_len(5);
+        assert_eq!(Detection::TooShort, categories.detect("the"));
+    }
+
+    #[test]
+    fn test_exact_match_with_every_metric() {
+        for metric in [
+            Metric::OutOfPlace,
+            Metric::JensenShannon,
+            Metric::ChiSquare,
+            Metric::Cosine,
+        ]
+        .iter()
+        {
+            let mut categories: Categories<String> = Categories::new();
+            categories.set_metric(*metric);
+            categories.add_category("english".to_string(), "the house is big");
+            categories.add_category("spanish".to_string(), "la casa es grande");
+
+            assert_eq!(
+                Some("english".to_string()),
+                categories.get_category("the house is big"),
+                "{:?}",
+                metric
+            );
+        }
+    }
+
+    #[test]
+    fn test_ambiguity_policy() {
+        let mut categories: Categories<String> = CategoriesBuilder::new()
+            .with_ambiguity_policy(AmbiguityPolicy::ReturnBest)
+            .build()
+            .expect("build");
+        categories.add_category("english".to_string(), "the house is big");
+        categories.add_category("british".to_string(), "the house is big");
+        categories.add_category("spanish".to_string(), "la casa es grande");
+        let best = categories.get_category("the house").expect("best guess");
+        assert_ne!("spanish", best);
+        assert_eq!(vec![best], categories.get_category_candidates("the house"));
+
+        categories.set_ambiguity_policy(AmbiguityPolicy::ReturnAll);
+        assert_eq!(2, categories.get_category_candidates("the house").len());
+        assert!(categories
+            .get_category_with_confidence("the house")
+            .is_some());
+
+        categories.set_ambiguity_policy(AmbiguityPolicy::ReturnNone);
+        assert_eq!(None, categories.get_category("the house"));
+        assert!(categories.get_category_candidates("the house").is_empty());
+        assert_eq!(
+            vec!["spanish".to_string()],
+            categories.get_category_candidates("la casa")
+        );
+    }
+
+    #[test]
+    fn test_detect_lines() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        categories.set_min_input_len(1);
+        categories.set_short_text_len(20);
+        let text = "Hola, ¿cómo estás?\n\nThe weather is nice today\nbom dia";
+
+        let lines = categories.detect_lines(text).collect::<Vec<_>>();
+        assert_eq!(
+            vec![1, 2, 3, 4],
+            lines.iter().map(|(n, _)| *n).collect::<Vec<_>>()
+        );
+        assert_eq!(Detection::TooShort, lines[1].1);
+        for ((_, detection), line) in lines.into_iter().zip(text.lines()) {
+            assert_eq!(categories.detect(line), detection);
+        }
+    }
+
+    #[test]
+    fn test_min_input_len() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        assert!(categories.get_categories("la").is_some());
+
+        categories.set_min_input_len(10);
+        assert_eq!(None, categories.get_categories("la"));
+        assert_eq!(None, categories.get_category("la  ¿? ¡!"));
+        assert!(categories
+            .get_categories("la casa es grande y bonita")
+            .is_some());
+    }
+
+    #[test]
+    fn test_short_text_mode() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        categories.set_short_text_len(20);
+
+        let distances = categories.short_text_distances("el año", |_| true);
+        assert_eq!(2, distances.len());
+        assert!(distances[0].1 < categories.get_distances("el año")[0].1);
+        assert_eq!(
+            Some("spanish".to_string()),
+            categories.get_category("el año")
+        );
+        assert_eq!(
+            categories.get_categories_from_ngrams(
+                &categories.profile("la casa es grande y muy bonita")
+            ),
+            categories.get_categories("la casa es grande y muy bonita")
+        );
+    }
+
+    #[test]
+    fn test_missing_penalty() {
+        let mut categories: Categories<String> = Categories::new();
+        assert_eq!(400, categories.missing_penalty());
+        categories.set_profile_size(800).expect("valid size");
+        assert_eq!(800, categories.missing_penalty());
+        assert!(categories.set_missing_penalty(0).is_err());
+
+        categories.add_category("english".to_string(), "the house is big");
+        let unrelated = categories.get_distances("zzz")[0].1;
+        categories.set_missing_penalty(10).expect("valid penalty");
+        assert!(categories.get_distances("zzz")[0].1 < unrelated);
+
+        let json = serde_json::to_string(&categories).expect("serialize");
+        assert!(json.contains("\"missing_penalty\":10"));
+        let loaded = from_bytes::<String>(json.as_bytes()).expect("load");
+        assert_eq!(10, loaded.missing_penalty());
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let mut categories: Categories<String> = CategoriesBuilder::new()
+            .with_stopwords(vec!["The", "and"])
+            .build()
+            .expect("valid settings");
+        categories.add_category("english".to_string(), "the house and the car");
+        let profile = &categories.categories[0].ngrams;
+        assert!(profile.ngram("_th").is_none());
+        assert!(profile.ngram("and_").is_none());
+        assert!(profile.ngram("_car").is_some());
+
+        let json = serde_json::to_string(&categories).expect("serialize");
+        assert!(json.contains("\"stopwords\":[\"and\",\"the\"]"));
+        let loaded = from_bytes::<String>(json.as_bytes()).expect("load");
+        assert_eq!(
+            categories.get_distances("the car"),
+            loaded.get_distances("the car")
+        );
+    }
+
+    #[test]
+    fn test_keep_counts() {
+        let first = "the house is big and the garden is green";
+        let second = "where is the station, the train is late";
+        let mut trained: Categories<String> = Categories::new();
+        trained.add_sample("english".to_string(), first, 1);
+
+        let compact = serde_json::to_string(&trained).expect("serialize");
+        assert!(!compact.contains("\"counts\""));
+
+        trained.set_keep_counts(true);
+        let full = serde_json::to_string(&trained).expect("serialize");
+        assert!(full.contains("\"counts\":true"));
+        assert!(full.contains("[\"the_\",2]"));
+
+        let mut loaded = from_bytes::<String>(full.as_bytes()).expect("load");
+        assert_eq!(full, serde_json::to_string(&loaded).expect("serialize"));
+        loaded.add_sample("english".to_string(), second, 1);
+        trained.add_sample("english".to_string(), second, 1);
+        assert_eq!(trained.to_vec(), loaded.to_vec());
+
+        let mut from_compact =
+            from_bytes::<String>(compact.as_bytes()).expect("load");
+        from_compact.add_sample("english".to_string(), second, 1);
+        assert_ne!(trained.to_vec(), from_compact.to_vec());
+    }
+
+    #[test]
+    fn test_metric() {
+        let mut categories: Categories<String> = Categories::new();
+        categories.set_metric(Metric::JensenShannon);
+        for name in &["english", "spanish"] {
+            let path = format!("tests/{}.sample", name);
+            let text = std::fs::read_to_string(path).expect("sample");
+            categories.add_category(name.to_string(), &text);
+        }
+
+        let json = serde_json::to_string(&categories).expect("serialize");
+        assert!(json.contains("\"metric\":\"jensen_shannon\""));
+        assert_eq!(
+            Metric::JensenShannon,
+            from_bytes::<String>(json.as_bytes()).expect("load").metric
+        );
+        assert_eq!(
+            Some("spanish".to_string()),
+            categories
+                .get_category("el perro de la casa es muy grande y bonito")
+        );
+    }
+
+    #[test]
+    fn test_sampling() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        let english = std::fs::read_to_string("tests/english.sample")
+            .expect("read sample");
+        let spanish = std::fs::read_to_string("tests/spanish.sample")
+            .expect("read sample");
+        let text = format!("{}\n{}", english, spanish);
+
+        assert!(categories
+            .set_sampling(Sampling::Head { bytes: 0 })
+            .is_err());
+        categories
+            .set_sampling(Sampling::Head { bytes: 400 })
+            .expect("sampling");
+        assert_eq!(Some("english".to_string()), categories.get_category(&text));
+        categories
+            .set_sampling(Sampling::Chunks {
+                count: 1,
+                size: 400,
+            })
+            .expect("sampling");
+        assert_eq!(Some("english".to_string()), categories.get_category(&text));
+
+        let json = serde_json::to_string(&categories).expect("serialize");
+        assert!(json.contains("\"sampling\":{\"chunks\":"));
+        let loaded = from_bytes::<String>(json.as_bytes()).expect("load");
+        assert_eq!(categories.sampling(), loaded.sampling());
+
+        let invalid = json.replace("\"count\":1", "\"count\":0");
+        assert!(from_bytes::<String>(invalid.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_nearest_matches_every_distance() {
+        let english = std::fs::read_to_string("tests/english.sample")
+            .expect("read sample");
+        let spanish = std::fs::read_to_string("tests/spanish.sample")
+            .expect("read sample");
+
+        let mut categories: Categories<String> = Categories::new();
+        categories.set_threshold(0.5);
+        for (i, chunk) in english.as_bytes().chunks(600).enumerate() {
+            let chunk = String::from_utf8_lossy(chunk);
+            categories.add_category(format!("english{}", i), &chunk);
+        }
+        for (i, chunk) in spanish.as_bytes().chunks(600).enumerate() {
+            let chunk = String::from_utf8_lossy(chunk);
+            categories.add_category(format!("spanish{}", i), &chunk);
+        }
+
+        // deserialized by hand, without interning the ngrams
+        let json = serde_json::to_string(&categories).expect("serialize");
+        let mut raw: Categories<String> =
+            serde_json::from_str(&json).expect("deserialize");
+        raw.set_threshold(0.5);
+
+        for text in &[&english[..300], &spanish[..300], "the cat and the dog"] {
+            let expected =
+                categories.candidates(categories.get_distances(text));
+            assert_eq!(expected, categories.get_categories(text));
+            assert_eq!(expected, raw.get_categories(text));
+        }
+    }
+
+    #[test]
+    fn test_shortlist() {
+        let mut categories = learn_from_directory("tests").expect("learn");
+        let spanish = std::fs::read_to_string("tests/spanish.sample")
+            .expect("read sample");
+        for (i, chunk) in spanish.as_bytes().chunks(1000).enumerate() {
+            let chunk = String::from_utf8_lossy(chunk);
+            categories.add_category(format!("spanish{}", i), &chunk);
+        }
+        let text = "el perro de la casa es muy grande y bonito";
+        let expected = categories.get_categories(text);
+
+        categories.set_shortlist(categories.categories().len() - 1);
+        assert_eq!(expected, categories.get_categories(text));
+        categories.set_shortlist(1);
+        let found = categories.get_categories(text).expect("candidates");
+        assert_eq!(1, found.len());
+        assert!(found[0].0.starts_with("spanish"));
+
+        categories.add_sample("english".to_string(), "perro grande", 1000);
+        let perr = categories.pool.id("perr").expect("interned");
+        assert!(categories.index.get(perr).contains(&0));
+        categories.retain(|name| name != "english");
+        assert_eq!(None, categories.pool.id("the_"));
+        for (id, category) in categories.categories.iter().enumerate() {
+            assert!(category.ids.iter().all(|ngram| {
+                categories.index.get(*ngram).contains(&(id as u32))
+            }));
+        }
+    }
 }
@@ -0,0 +1,317 @@
+//! # Classifier
+//!
+//! Classification backends. `Categories` compares ngram rankings, which works best on
+//! texts of a few sentences; `NaiveBayes` scores every ngram of the text with its
+//! probability in each category (like langid.py), which is much more accurate on short
+//! texts. Both implement the `Classifier` trait.
+#[cfg(feature = "training")]
+use crate::category::read_samples;
+use crate::category::Categories;
+use crate::error::{Error, Result};
+use crate::ngram::Ngrams;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+
+const DEFAULT_THRESHOLD: f32 = 0.03;
+
+/// Ngrams length, as in `Ngrams::new` (1 to 4 chars)
+const NGRAM_LENGTH: u8 = 5;
+
+/// Scale of the log-probabilities turned into integer distances
+const LOG_PROBABILITY_SCALE: f32 = 1000.0;
+
+fn default_threshold() -> f32 {
+    DEFAULT_THRESHOLD
+}
+
+/// Common classification API of the backends
+pub trait Classifier<T> {
+    /// Returns a sorted list of categories which are candidates and their distance (the
+    /// lower the better). None is returned if there are no categories.
+    fn get_categories(&self, sample: &str) -> Option<Vec<(T, u64)>>;
+
+    /// Returns a single category for a given text, None if there are several candidates.
+    fn get_category(&self, sample: &str) -> Option<T> {
+        let mut candidates = self.get_categories(sample)?;
+        if candidates.len() == 1 {
+            candidates.pop().map(|(category, _)| category)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Classifier<T> for Categories<T>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    fn get_categories(&self, sample: &str) -> Option<Vec<(T, u64)>> {
+        Categories::get_categories(self, sample)
+    }
+
+    fn get_category(&self, sample: &str) -> Option<T> {
+        Categories::get_category(self, sample)
+    }
+}
+
+/// Naive Bayes classifier over character ngrams.
+///
+/// The model stores, for every ngram seen while learning, its log-probability in each
+/// category (with add-one smoothing). A text is scored with the sum of the log-probabilities
+/// of its ngrams, ngrams never seen while learning are ignored. Distances are the negated
+/// scores, scaled by a thousand.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    bound = "T: Serialize, for<'a> T: Deserialize<'a>",
+    deny_unknown_fields
+)]
+pub struct NaiveBayes<T>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    /// Version of the file format
+    version: String,
+
+    /// Names of the categories
+    categories: Vec<T>,
+
+    /// Log-probability of each ngram in each category, in the order of `categories`
+    log_probabilities: BTreeMap<String, Vec<f32>>,
+
+    /// Runtime configuration, see `Categories::set_threshold`
+    #[serde(
+        skip_deserializing,
+        skip_serializing,
+        default = "default_threshold"
+    )]
+    threshold: f32,
+}
+
+impl<T> NaiveBayes<T>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    /// Learns a model from (category, text) samples. Samples of the same category are
+    /// learned together.
+    pub fn learn<'a, I>(samples: I) -> NaiveBayes<T>
+    where
+        I: IntoIterator<Item = (T, &'a str)>,
+    {
+        let mut categories: Vec<T> = Vec::new();
+        let mut counts: Vec<HashMap<SmolStr, u64>> = Vec::new();
+
+        for (name, text) in samples {
+            let id = match categories.iter().position(|c| *c == name) {
+                Some(id) => id,
+                None => {
+                    categories.push(name);
+                    counts.push(HashMap::new());
+                    counts.len() - 1
+                }
+            };
+
+            for (ngram, count) in
+                Ngrams::parse_text(text, NGRAM_LENGTH as usize)
+            {
+                *counts[id].entry(ngram).or_insert(0) += count;
+            }
+        }
+
+        let mut log_probabilities: BTreeMap<String, Vec<f32>> = BTreeMap::new();
+        for ngram in counts.iter().flat_map(|counts| counts.keys()) {
+            log_probabilities
+                .entry(ngram.to_string())
+                .or_insert_with(Vec::new);
+        }
+
+        let vocabulary = log_probabilities.len() as f64;
+        for counts in counts.iter() {
+            let total = counts.values().sum::<u64>() as f64 + vocabulary;
+            for (ngram, probabilities) in log_probabilities.iter_mut() {
+                let count =
+                    counts.get(ngram.as_str()).copied().unwrap_or(0) as f64;
+                probabilities.push(((count + 1.0) / total).ln() as f32);
+            }
+        }
+
+        NaiveBayes {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            categories,
+            log_probabilities,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+
+    /// Returns all the categories of the model
+    pub fn categories(&self) -> Vec<T> {
+        self.categories.clone()
+    }
+
+    /// Sets the threshold, see `Categories::set_threshold`
+    pub fn set_threshold(
+        &mut self,
+        threshold: f32,
+    ) -> std::result::Result<(), &str> {
+        if !(threshold > 0.0 && threshold < 1.0) {
+            return Err("The value has to between 0 and 1");
+        }
+
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    /// Returns every category and its distance to a given text, best first. Unlike
+    /// `get_categories` no threshold is applied.
+    pub fn get_distances(&self, sample: &str) -> Vec<(T, u64)> {
+        let mut scores = vec![0.0_f32; self.categories.len()];
+
+        for (ngram, count) in Ngrams::parse_text(sample, NGRAM_LENGTH as usize)
+        {
+            if let Some(probabilities) =
+                self.log_probabilities.get(ngram.as_str())
+            {
+                scores
+                    .iter_mut()
+                    .zip(probabilities)
+                    .for_each(|(score, p)| *score += p * count as f32);
+            }
+        }
+
+        let mut distances = self
+            .categories
+            .iter()
+            .cloned()
+            .zip(scores)
+            .map(|(name, score)| {
+                (name, (-score * LOG_PROBABILITY_SCALE).round() as u64)
+            })
+            .collect::<Vec<(T, u64)>>();
+
+        distances.sort_by(|a, b| a.1.cmp(&b.1));
+        distances
+    }
+
+    /// Stores the model in a JSON file
+    pub fn persist(&self, output: &str) -> Result<()> {
+        let j = serde_json::to_string(&self)?;
+        File::create(output)?.write_all(j.as_bytes())?;
+        Ok(())
+    }
+
+    /// Checks the model is usable: there is at least one category and every ngram has a
+    /// probability for each category
+    pub fn validate(&self) -> Result<()> {
+        if self.categories.is_empty() {
+            return Err(Error::invalid("model has no categories"));
+        }
+
+        match self
+            .log_probabilities
+            .iter()
+            .find(|(_, p)| p.len() != self.categories.len())
+        {
+            Some((ngram, _)) => Err(Error::invalid(format!(
+                "ngram {:?} does not have a probability for every category",
+                ngram
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Classifier<T> for NaiveBayes<T>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    fn get_categories(&self, sample: &str) -> Option<Vec<(T, u64)>> {
+        let distances = self.get_distances(sample);
+
+        let best_candidate = distances.first()?;
+        let threshold: u64 =
+            ((1.0 + self.threshold) * best_candidate.1 as f32) as u64;
+
+        Some(distances.into_iter().filter(|p| threshold >= p.1).collect())
+    }
+}
+
+impl NaiveBayes<String> {
+    /// Learns a model from a directory of samples, like `learn_from_directory`
+    #[cfg(feature = "training")]
+    pub fn learn_from_directory(path: &str) -> Result<NaiveBayes<String>> {
+        let (samples, _) = read_samples(path)?;
+        if samples.is_empty() {
+            return Err(Error::EmptyCorpus(path.to_string()));
+        }
+
+        let samples = samples
+            .iter()
+            .map(|(name, text)| (name.clone(), text.as_str()));
+        Ok(NaiveBayes::learn(samples))
+    }
+}
+
+/// Loads a Naive Bayes model from the content of a model file
+pub fn from_bytes<T>(bytes: &[u8]) -> Result<NaiveBayes<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let model: NaiveBayes<T> =
+        serde_json::from_slice(bytes).map_err(Error::serde)?;
+    model.validate()?;
+
+    Ok(model)
+}
+
+/// Loads a Naive Bayes model stored with `NaiveBayes::persist`
+pub fn load<T>(path: &str) -> Result<NaiveBayes<T>>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    from_bytes(&std::fs::read(path)?)
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn naive_bayes() {
+        let model = NaiveBayes::learn_from_directory("tests").expect("learn");
+        assert_eq!(vec!["english", "spanish"], model.categories());
+
+        assert_eq!(
+            Some("english".to_string()),
+            model.get_category("the house is big")
+        );
+        assert_eq!(
+            Some("spanish".to_string()),
+            model.get_category("la casa es grande")
+        );
+
+        let json = serde_json::to_vec(&model).expect("serialize");
+        let loaded = from_bytes::<String>(&json).expect("load");
+        assert_eq!(model.get_distances("hola"), loaded.get_distances("hola"));
+        let empty =
+            br#"{"version":"0","categories":[],"log_probabilities":{}}"#;
+        assert!(from_bytes::<String>(empty).is_err());
+    }
+
+    #[test]
+    fn same_api() {
+        fn classify<C: Classifier<String>>(classifier: &C) -> Option<String> {
+            classifier.get_category("la casa es muy grande y bonita")
+        }
+
+        let categories =
+            crate::category::learn_from_directory("tests").expect("learn");
+        let model = NaiveBayes::learn_from_directory("tests").expect("learn");
+        let expected =
+            categories.get_category("la casa es muy grande y bonita");
+
+        assert_eq!(Some("spanish".to_string()), classify(&model));
+        assert_eq!(expected, classify(&categories));
+    }
+}
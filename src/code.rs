@@ -0,0 +1,158 @@
+//! # Code
+//!
+//! Identification of programming languages, with a model learned from the samples of
+//! `corpus/code` and embedded in the library (`code` feature). Code is a domain of its own:
+//! natural-language models leave it out of Markdown documents (see
+//! `Categories::get_category_from_markdown`), and this model tells the language of the
+//! code blocks themselves:
+//!
+//! ```ignore
+//! let codecat = textcat::code::codecat();
+//! codecat.detect("fn main() {\n    println!(\"hi\");\n}"); // Some("rust")
+//! ```
+use crate::category::{from_bytes, Categories, CategoriesBuilder};
+use crate::markup::{code_blocks, CodeBlock};
+use crate::ngram::{Metric, TokenMode};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Content of the embedded model file
+const MODEL: &[u8] = include_bytes!("../models/code.json");
+
+/// Name of a programming language of the embedded model ("rust", "python", "shell"...)
+pub type Language = String;
+
+/// Other names of the languages, as found in the info string of code fences
+const ALIASES: [(&str, &str); 12] = [
+    ("bash", "shell"),
+    ("cpp", "c"),
+    ("golang", "go"),
+    ("h", "c"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("py", "python"),
+    ("python3", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sh", "shell"),
+    ("zsh", "shell"),
+];
+
+/// Settings of the embedded model, to learn other programming languages the same way:
+/// words and symbols tell languages apart as much as their characters, and frequencies
+/// are more reliable than ranks on short snippets
+pub fn builder() -> CategoriesBuilder {
+    CategoriesBuilder::new()
+        .with_token_mode(TokenMode::Mixed)
+        .with_metric(Metric::JensenShannon)
+}
+
+/// Classifier of programming languages
+#[derive(Clone)]
+pub struct CodeCat {
+    categories: Categories<Language>,
+}
+
+/// Returns a classifier with the embedded model. It panics if the embedded model is not a
+/// valid model file.
+pub fn codecat() -> CodeCat {
+    CodeCat {
+        categories: from_bytes(MODEL).expect("invalid embedded code model"),
+    }
+}
+
+impl CodeCat {
+    /// Returns the programming language of a piece of code, or None when it can't be told
+    pub fn detect(&self, code: &str) -> Option<Language> {
+        self.categories.get_category(code)
+    }
+
+    /// Returns the code blocks of a Markdown document with their language. The info
+    /// string of the fence ("```rust") is trusted when it names a language of the model,
+    /// the code is classified otherwise.
+    pub fn detect_blocks<'a>(
+        &self,
+        markdown: &'a str,
+    ) -> Vec<(CodeBlock<'a>, Option<Language>)> {
+        code_blocks(markdown)
+            .into_iter()
+            .map(|block| {
+                let language = self
+                    .language_of_info(block.info)
+                    .or_else(|| self.detect(&block.code));
+                (block, language)
+            })
+            .collect()
+    }
+
+    /// Language of the model named by the info string of a fence, if any
+    fn language_of_info(&self, info: &str) -> Option<Language> {
+        let info = info.to_lowercase();
+        let name = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == info)
+            .map_or(info.as_str(), |(_, name)| name);
+
+        self.categories
+            .categories()
+            .into_iter()
+            .find(|category| category == name)
+    }
+
+    /// Returns the embedded model
+    pub fn categories(&self) -> &Categories<Language> {
+        &self.categories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_code() {
+        let codecat = codecat();
+        assert_eq!(
+            Some("python".to_string()),
+            codecat.detect("def greet(name):\n    print(f\"hello {name}\")\n")
+        );
+        assert_eq!(
+            Some("sql".to_string()),
+            codecat.detect("SELECT id, name FROM users WHERE age > 30;")
+        );
+
+        let readme = "Instala el paquete:\n\n```sh\nnpm i x\n```\n\n\
+            ```\npackage main\n\nimport \"fmt\"\n\nfunc main() {\n\tfmt.Println(\"hi\")\n}\n```\n";
+        let languages = codecat
+            .detect_blocks(readme)
+            .into_iter()
+            .map(|(_, language)| language)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![Some("shell".to_string()), Some("go".to_string())],
+            languages
+        );
+    }
+
+    /// The embedded model is learned from `corpus/code`. Run the test with
+    /// `TEXTCAT_UPDATE_MODELS=1` to write it again after changing the corpus or the
+    /// settings.
+    #[cfg(feature = "training")]
+    #[test]
+    fn embedded_model_is_up_to_date() {
+        let model = builder()
+            .learn_from_directory(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/corpus/code"
+            ))
+            .expect("learn");
+        let json = serde_json::to_vec(&model).expect("serialize");
+
+        if std::env::var_os("TEXTCAT_UPDATE_MODELS").is_some() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/models/code.json");
+            std::fs::write(path, json).expect("write");
+            return;
+        }
+        assert!(MODEL == json.as_slice(), "run with TEXTCAT_UPDATE_MODELS=1");
+    }
+}
@@ -0,0 +1,267 @@
+//! # Default model
+//!
+//! Model of the languages of `corpus/languages` (English, Spanish, French, German, Italian,
+//! Portuguese, Dutch, Swedish, Polish and Russian, named after their ISO 639-1 codes),
+//! embedded in the library, so programs can detect languages without shipping a model.
+//! `build.rs` learns the profiles from the samples when the library is built, so the model
+//! always matches the corpus. Each language has its own feature, `lang-es` for Spanish,
+//! and only the enabled ones are compiled in (`all-languages`, a default feature, enables
+//! them all):
+//!
+//! ```
+//! let textcat = textcat::default::textcat();
+//! let text = "Mañana vamos a la playa con los niños y comemos en casa";
+//! assert_eq!(Some("es".to_string()), textcat.detect(text));
+//! ```
+use crate::category::{
+    Categories, DEFAULT_MAX_NGRAM_LEN, DEFAULT_PROFILE_SIZE,
+};
+use crate::lang::Lang;
+use crate::ngram::Ngrams;
+use crate::script::{self, Script};
+use crate::threshold::Threshold;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+include!(concat!(env!("OUT_DIR"), "/languages.rs"));
+
+/// Name of a category of the embedded model, the ISO 639-1 code of its language
+pub type Language = String;
+
+/// Profile of an embedded language, in static tables generated at build time: nothing is
+/// decoded or allocated to use it
+#[derive(Debug)]
+pub struct LanguageProfile {
+    name: &'static str,
+    /// Ngrams in rank order
+    ngrams: &'static [&'static str],
+    /// Same ngrams with their rank, sorted bytewise
+    ranks: &'static [(&'static str, u16)],
+    /// Scripts of the profile (see `script::of_profile`)
+    scripts: &'static [Script],
+}
+
+impl LanguageProfile {
+    /// Returns the ISO 639-1 code of the language
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the ngrams of the profile in rank order
+    pub fn ngrams(&self) -> &'static [&'static str] {
+        self.ngrams
+    }
+
+    /// Returns the rank of an ngram in the profile, a binary search of the sorted table
+    pub fn position(&self, ngram: &str) -> Option<usize> {
+        self.ranks
+            .binary_search_by(|(other, _)| (*other).cmp(ngram))
+            .ok()
+            .map(|i| self.ranks[i].1 as usize)
+    }
+
+    /// Returns true if texts of the given dominant script are compared with the language
+    /// (see `Categories::get_categories`)
+    fn accepts(&self, script: Option<Script>) -> bool {
+        match script {
+            Some(script) if !self.scripts.is_empty() => {
+                self.scripts.contains(&script)
+            }
+            _ => true,
+        }
+    }
+
+    /// Out-of-place distance to the ngrams of a text, as `Categories` measures it with the
+    /// default settings
+    fn distance(&self, sample: &Ngrams) -> u64 {
+        self.ngrams
+            .iter()
+            .map(|ngram| {
+                sample
+                    .position(ngram)
+                    .map_or(DEFAULT_PROFILE_SIZE as u64, |pos| pos as u64)
+            })
+            .sum()
+    }
+}
+
+/// Classifier with the embedded model. It only refers to the static tables of the model,
+/// creating it costs nothing.
+#[derive(Debug, Clone, Copy)]
+pub struct TextCat {
+    languages: &'static [LanguageProfile],
+}
+
+impl Default for TextCat {
+    fn default() -> Self {
+        TextCat::new()
+    }
+}
+
+/// Returns a classifier with the embedded model
+pub fn textcat() -> TextCat {
+    TextCat::new()
+}
+
+impl TextCat {
+    /// Returns a classifier with the embedded model
+    pub const fn new() -> TextCat {
+        TextCat {
+            languages: LANGUAGES,
+        }
+    }
+
+    /// Returns the language of a text, or None when it can't be told (the text is too
+    /// short or close to several languages, see `Categories::detect`). This is the main
+    /// entry point of the library, `categories` gives access to the lower level API.
+    pub fn detect(&self, text: &str) -> Option<Language> {
+        self.single(self.candidates(text, |_| true))
+    }
+
+    /// Same as `detect`, as a strongly typed language (see `lang::Lang`)
+    pub fn get_language(&self, text: &str) -> Option<Lang> {
+        Lang::find(&self.detect(text)?)
+    }
+
+    /// Same as `detect`, considering only the given languages, by name or ISO 639 code
+    pub fn detect_among(
+        &self,
+        text: &str,
+        languages: &[&str],
+    ) -> Option<Language> {
+        let languages = languages
+            .iter()
+            .map(|name| (*name, Lang::find(name)))
+            .collect::<Vec<_>>();
+
+        self.single(self.candidates(text, |name| {
+            let lang = Lang::find(name);
+            languages.iter().any(|(other, language)| {
+                *other == name || (lang.is_some() && *language == lang)
+            })
+        }))
+    }
+
+    /// Returns the profiles of the embedded languages
+    pub fn languages(&self) -> &'static [LanguageProfile] {
+        self.languages
+    }
+
+    /// Builds the embedded model as `Categories`, for the lower level API. Unlike the
+    /// classifier it copies the profiles.
+    pub fn categories(&self) -> Categories<Language> {
+        self.languages
+            .iter()
+            .map(|language| {
+                (language.name.to_string(), language.ngrams.to_vec())
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// Languages for which `keep` returns true within the default threshold of the
+    /// closest one, as `Categories::get_categories` returns them
+    fn candidates<F>(&self, text: &str, keep: F) -> Vec<(&'static str, u64)>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let sample = Ngrams::new(text, DEFAULT_MAX_NGRAM_LEN as u8 + 1);
+        let script = script::dominant(text);
+
+        let mut distances = self
+            .languages
+            .iter()
+            .filter(|language| keep(language.name) && language.accepts(script))
+            .map(|language| (language.name, language.distance(&sample)))
+            .collect::<Vec<_>>();
+        distances.sort_by_key(|(_, distance)| *distance);
+
+        let cutoff = match distances.first() {
+            Some((_, best)) => Threshold::default().cutoff(*best),
+            None => return distances,
+        };
+        distances.retain(|(_, distance)| *distance < cutoff);
+        distances
+    }
+
+    /// Returns the language if there is a single candidate
+    fn single(&self, candidates: Vec<(&'static str, u64)>) -> Option<Language> {
+        match candidates.as_slice() {
+            [(language, _)] => Some(language.to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "all-languages")]
+    #[test]
+    fn detect_languages() {
+        let textcat = textcat();
+        for (text, language) in [
+            ("The weather is nice today, let's go for a walk", "en"),
+            (
+                "Mañana vamos a la playa y comemos en casa de mis padres",
+                "es",
+            ),
+            (
+                "Le chat de la voisine dort toute la journée au soleil",
+                "fr",
+            ),
+            ("Wir fahren morgen mit dem Zug nach Hause", "de"),
+            ("Questa mattina il treno per la città era in ritardo", "it"),
+            ("Amanhã vamos à praia com os nossos amigos", "pt"),
+            ("Morgen gaan we met de fiets naar het strand", "nl"),
+            ("Vi åker till sjön i morgon och badar hela dagen", "sv"),
+            ("Jutro jedziemy nad morze z przyjaciółmi", "pl"),
+            ("Завтра мы поедем на море с друзьями", "ru"),
+        ]
+        .iter()
+        {
+            assert_eq!(Some(language.to_string()), textcat.detect(text));
+        }
+
+        let text = "Mañana vamos a la playa con los niños y comemos en casa";
+        assert_eq!(Some(Lang::Spa), textcat.get_language(text));
+        assert_eq!(
+            Some("es".to_string()),
+            textcat.detect_among(text, &["spanish", "pt"])
+        );
+    }
+
+    /// `build.rs` learns the profiles with the extraction code of the library (src/extract.rs)
+    /// but without `Categories`
+    #[cfg(all(feature = "training", feature = "all-languages"))]
+    #[test]
+    fn embedded_model_matches_the_corpus() {
+        let model = crate::category::learn_from_directory(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/corpus/languages"
+        ))
+        .expect("learn");
+
+        let textcat = textcat();
+        let categories = textcat.categories();
+        assert_eq!(model.to_vec(), categories.to_vec());
+
+        for language in textcat.languages() {
+            for (rank, ngram) in language.ngrams().iter().enumerate() {
+                assert_eq!(Some(rank), language.position(ngram));
+            }
+            assert_eq!(None, language.position("_zzz"));
+            let scripts = script::of_profile(language.ngrams().iter().copied());
+            assert_eq!(scripts, language.scripts);
+        }
+
+        // the classifier measures distances as `Categories` does, on the static tables
+        for sample in ["tests/english.sample", "tests/spanish.sample"].iter() {
+            let sample = std::fs::read_to_string(sample).expect("read");
+            for text in sample.lines().chain(Some("hola, ¿cómo estás?")) {
+                assert_eq!(categories.get_category(text), textcat.detect(text));
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! # Encoding
+//!
+//! Decoding of texts which may not be UTF-8, so legacy files are classified from their real
+//! characters instead of replacement characters (see `Categories::get_category_from_bytes`).
+//! A byte-order mark always tells UTF-8 and UTF-16 apart. Without one, the `charset`
+//! feature guesses UTF-16 from its zero bytes and falls back to Latin-1 for any other text
+//! which is not valid UTF-8; without the feature invalid sequences are replaced.
+use alloc::borrow::Cow;
+use alloc::string::String;
+
+/// Encoding of a text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, with or without byte-order mark
+    Utf8,
+    /// UTF-16, little endian
+    Utf16Le,
+    /// UTF-16, big endian
+    Utf16Be,
+    /// Latin-1, decoded as Windows-1252, its superset used by most legacy files (it has
+    /// "€", "œ" and typographic quotes where Latin-1 has control characters)
+    Latin1,
+}
+
+/// Characters of Windows-1252 from 0x80 to 0x9F. Bytes undefined in Windows-1252 are
+/// kept as the Latin-1 control characters.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ',
+    '\u{8D}', 'Ž', '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜',
+    '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decodes a text, detecting its encoding as described in the module documentation.
+/// The byte-order mark is not part of the text. Valid UTF-8 is borrowed.
+pub fn decode(bytes: &[u8]) -> (Cow<'_, str>, Encoding) {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return (String::from_utf8_lossy(rest), Encoding::Utf8);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return (decode_as(rest, Encoding::Utf16Le), Encoding::Utf16Le);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return (decode_as(rest, Encoding::Utf16Be), Encoding::Utf16Be);
+    }
+
+    let encoding = guess(bytes);
+    (decode_as(bytes, encoding), encoding)
+}
+
+/// Decodes a text in a given encoding, replacing invalid sequences
+pub fn decode_as(bytes: &[u8], encoding: Encoding) -> Cow<'_, str> {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8_lossy(bytes),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let units = bytes.chunks(2).map(|pair| match pair {
+                [a, b] if encoding == Encoding::Utf16Le => {
+                    u16::from_le_bytes([*a, *b])
+                }
+                [a, b] => u16::from_be_bytes([*a, *b]),
+                // odd trailing byte
+                _ => 0xFFFD,
+            });
+            Cow::Owned(
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect(),
+            )
+        }
+        Encoding::Latin1 => Cow::Owned(
+            bytes
+                .iter()
+                .map(|b| match *b {
+                    0x80..=0x9F => WINDOWS_1252[*b as usize - 0x80],
+                    b => b as char,
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Encoding of a text without byte-order mark
+#[cfg(feature = "charset")]
+fn guess(bytes: &[u8]) -> Encoding {
+    // UTF-16 texts have a zero byte in (almost) every other position, as most of their
+    // characters are ASCII or, for other scripts, spaces and punctuation
+    let sample = &bytes[..bytes.len().min(4096)];
+    let zeros = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    let pairs = sample.len() / 2;
+    if pairs >= 2 && odd * 4 > pairs && even * 16 < pairs {
+        return Encoding::Utf16Le;
+    }
+    if pairs >= 2 && even * 4 > pairs && odd * 16 < pairs {
+        return Encoding::Utf16Be;
+    }
+
+    if core::str::from_utf8(bytes).is_ok() {
+        Encoding::Utf8
+    } else {
+        Encoding::Latin1
+    }
+}
+
+/// Encoding of a text without byte-order mark, always UTF-8 without the `charset` feature
+#[cfg(not(feature = "charset"))]
+fn guess(_bytes: &[u8]) -> Encoding {
+    Encoding::Utf8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Encodes a text in UTF-16
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let text = "canción ñandú";
+        assert_eq!((text.into(), Encoding::Utf8), decode(text.as_bytes()));
+
+        let bom = [&b"\xEF\xBB\xBF"[..], text.as_bytes()].concat();
+        assert_eq!((text.into(), Encoding::Utf8), decode(&bom));
+
+        let le = [&b"\xFF\xFE"[..], &utf16(text, true)].concat();
+        assert_eq!((text.into(), Encoding::Utf16Le), decode(&le));
+
+        let be = [&b"\xFE\xFF"[..], &utf16(text, false)].concat();
+        assert_eq!((text.into(), Encoding::Utf16Be), decode(&be));
+
+        assert_eq!("€ “ñ”", decode_as(b"\x80 \x93\xF1\x94", Encoding::Latin1));
+    }
+
+    #[cfg(feature = "charset")]
+    #[test]
+    fn guess_encoding() {
+        let text = "el niño comió en la cocina";
+        let le = utf16(text, true);
+        assert_eq!((text.into(), Encoding::Utf16Le), decode(&le));
+        assert_eq!(
+            (text.into(), Encoding::Utf16Be),
+            decode(&utf16(text, false))
+        );
+
+        let latin1 = text.chars().map(|c| c as u8).collect::<Vec<_>>();
+        assert_eq!((text.into(), Encoding::Latin1), decode(&latin1));
+    }
+
+    #[cfg(not(feature = "charset"))]
+    #[test]
+    fn replace_invalid_sequences() {
+        assert_eq!(("ni\u{FFFD}o".into(), Encoding::Utf8), decode(b"ni\xF1o"));
+    }
+}
@@ -0,0 +1,113 @@
+//! # Error
+//!
+//! Errors reported by the library.
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use core::fmt::Display;
+
+/// Error type of every fallible operation of the library
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Reading or writing a file or a stream failed
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// A model or training data could not be serialized or deserialized
+    #[error("malformed data: {0}")]
+    Serde(#[source] Box<dyn core::error::Error + Send + Sync>),
+
+    /// A line of JSON-lines training data could not be parsed
+    #[error("line {line}: {source}")]
+    InvalidSample {
+        /// Line number, starting at 1
+        line: usize,
+        /// Parsing error
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The path of a corpus directory is not a valid pattern
+    #[cfg(feature = "training")]
+    #[error("invalid corpus path: {0}")]
+    Glob(#[from] glob::PatternError),
+
+    /// The model is not usable (see `Categories::validate`)
+    #[error("invalid model: {0}")]
+    InvalidModel(String),
+
+    /// A setting is out of its valid range
+    #[error("invalid setting: {0}")]
+    InvalidSetting(String),
+
+    /// There was nothing to learn from
+    #[error("no samples to learn from in {0}")]
+    EmptyCorpus(String),
+
+    /// The model was written by an incompatible version of the library (see
+    /// `category::migrate`)
+    #[error(
+        "model version {0} is not compatible with this library, migrate it"
+    )]
+    IncompatibleVersion(String),
+
+    /// The category does not exist in the model
+    #[error("unknown category {0}")]
+    UnknownCategory(String),
+
+    /// The name is neither an ISO 639 code nor the name of a known language (see
+    /// `lang::Lang`)
+    #[error("unknown language {0}")]
+    UnknownLanguage(String),
+
+    /// The operation needs a feature which is not enabled
+    #[error("{0}")]
+    Unsupported(String),
+
+    /// Two models could not be merged
+    #[error(transparent)]
+    Merge(#[from] MergeError),
+}
+
+/// Error of `Categories::merge`
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum MergeError {
+    /// The models were trained with different settings, so their profiles can't be compared
+    #[error("the models have different {setting}: {ours} and {theirs}")]
+    IncompatibleSettings {
+        /// Name of the setting
+        setting: &'static str,
+        /// Value in the model merged into
+        ours: String,
+        /// Value in the merged model
+        theirs: String,
+    },
+
+    /// Both models have a category with the same name
+    #[error("category {0} is in both models")]
+    DuplicateCategory(String),
+}
+
+impl Error {
+    /// Wraps a serialization error from any format
+    pub(crate) fn serde<E>(e: E) -> Error
+    where
+        E: core::error::Error + Send + Sync + 'static,
+    {
+        Error::Serde(Box::new(e))
+    }
+
+    /// An invalid model error
+    pub(crate) fn invalid<E: Display>(e: E) -> Error {
+        Error::InvalidModel(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::serde(e)
+    }
+}
+
+/// Result type of the library
+pub type Result<T> = core::result::Result<T, Error>;
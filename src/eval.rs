@@ -0,0 +1,414 @@
+//! # Eval
+//!
+//! Measures how well a model classifies labeled texts: precision, recall and F1 of every
+//! category, the overall accuracy and the confusion matrix.
+#[cfg(feature = "training")]
+use crate::category::read_sample_files;
+use crate::category::Categories;
+#[cfg(feature = "training")]
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// Scores of a single category
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryScore<T> {
+    /// The category
+    pub category: T,
+
+    /// Fraction of the texts classified as this category which belong to it
+    pub precision: f32,
+
+    /// Fraction of the texts of this category which were classified as it
+    pub recall: f32,
+
+    /// Harmonic mean of the precision and the recall
+    pub f1: f32,
+
+    /// Number of texts of this category
+    pub support: usize,
+}
+
+/// Result of `evaluate`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalReport<T> {
+    /// Scores of every category, in the order of the model followed by the labels which
+    /// are not in the model
+    pub scores: Vec<CategoryScore<T>>,
+
+    /// Fraction of the texts classified as their label
+    pub accuracy: f32,
+
+    /// Number of texts of each label (rows) classified as each category (columns), in the
+    /// order of `scores`
+    pub confusion: Vec<Vec<usize>>,
+
+    /// Number of texts of each label which were not classified, as they were ambiguous (see
+    /// `Categories::get_category`)
+    pub unclassified: Vec<usize>,
+}
+
+/// Fraction which is 0 when there is nothing to divide
+fn ratio(part: usize, total: usize) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f32 / total as f32
+    }
+}
+
+/// Classifies every labeled text with the model and compares the result with its label
+pub fn evaluate<T>(
+    model: &Categories<T>,
+    labeled: &[(T, String)],
+) -> EvalReport<T>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let mut categories = model.categories();
+    for (label, _) in labeled {
+        if !categories.contains(label) {
+            categories.push(label.clone());
+        }
+    }
+
+    let position = |category: &T| categories.iter().position(|c| c == category);
+    let mut confusion = vec![vec![0; categories.len()]; categories.len()];
+    let mut unclassified = vec![0; categories.len()];
+
+    for (label, text) in labeled {
+        let row = position(label).expect("labels are in the categories");
+        match model.get_category(text).as_ref().and_then(position) {
+            Some(column) => confusion[row][column] += 1,
+            None => unclassified[row] += 1,
+        }
+    }
+
+    let correct = (0..categories.len()).map(|i| confusion[i][i]).sum();
+    let scores = categories
+        .iter()
+        .enumerate()
+        .map(|(i, category)| {
+            let predicted = confusion.iter().map(|row| row[i]).sum();
+            let support = confusion[i].iter().sum::<usize>() + unclassified[i];
+            let precision = ratio(confusion[i][i], predicted);
+            let recall = ratio(confusion[i][i], support);
+            let f1 = if precision + recall > 0.0 {
+                2.0 * precision * recall / (precision + recall)
+            } else {
+                0.0
+            };
+
+            CategoryScore {
+                category: category.clone(),
+                precision,
+                recall,
+                f1,
+                support,
+            }
+        })
+        .collect();
+
+    EvalReport {
+        scores,
+        accuracy: ratio(correct, labeled.len()),
+        confusion,
+        unclassified,
+    }
+}
+
+/// Result of a k-fold cross-validation, see `learn_with_cross_validation`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidation<T> {
+    /// Evaluation of every fold with samples to classify
+    pub folds: Vec<EvalReport<T>>,
+
+    /// Scores of every category averaged over the folds where it has samples, the support
+    /// is the total
+    pub scores: Vec<CategoryScore<T>>,
+
+    /// Accuracy averaged over the folds
+    pub accuracy: f32,
+}
+
+impl<T> CrossValidation<T>
+where
+    T: PartialEq<T> + Clone,
+{
+    fn new(folds: Vec<EvalReport<T>>) -> CrossValidation<T> {
+        let mut scores: Vec<(CategoryScore<T>, usize)> = Vec::new();
+        let scored = folds.iter().flat_map(|fold| fold.scores.iter());
+        for score in scored.filter(|score| score.support > 0) {
+            match scores
+                .iter_mut()
+                .find(|(s, _)| s.category == score.category)
+            {
+                Some((sum, n)) => {
+                    sum.precision += score.precision;
+                    sum.recall += score.recall;
+                    sum.f1 += score.f1;
+                    sum.support += score.support;
+                    *n += 1;
+                }
+                None => scores.push((score.clone(), 1)),
+            }
+        }
+
+        let scores = scores
+            .into_iter()
+            .map(|(mut score, n)| {
+                score.precision /= n as f32;
+                score.recall /= n as f32;
+                score.f1 /= n as f32;
+                score
+            })
+            .collect();
+        let accuracy = folds.iter().map(|fold| fold.accuracy).sum::<f32>()
+            / folds.len().max(1) as f32;
+
+        CrossValidation {
+            folds,
+            scores,
+            accuracy,
+        }
+    }
+}
+
+/// Learns categories from a directory, like `learn_from_directory`, and measures how well
+/// they generalize with k-fold cross-validation: the samples of every category are split in
+/// `k` folds (sample `i` goes to fold `i % k`), and each fold is classified, sample by
+/// sample, by a model learned from the other folds. Returns the model learned from every
+/// sample along with the metrics.
+///
+/// The samples of a category are its files (`english/*`), so the lines of a document are
+/// never on both sides of a fold. A category learned from a single file has its lines as
+/// samples instead. Folds left without samples, when `k` is larger than their number, are
+/// skipped.
+///
+/// `CategoriesBuilder::learn_with_cross_validation` does the same with other settings, to
+/// compare e.g. ngram lengths or profile sizes.
+#[cfg(feature = "training")]
+pub fn learn_with_cross_validation(
+    path: &str,
+    k: usize,
+) -> Result<(Categories<String>, CrossValidation<String>)> {
+    cross_validate(Categories::new(), path, k)
+}
+
+/// Cross-validates the settings of an empty model, see `learn_with_cross_validation`
+#[cfg(feature = "training")]
+pub(crate) fn cross_validate(
+    empty: Categories<String>,
+    path: &str,
+    k: usize,
+) -> Result<(Categories<String>, CrossValidation<String>)> {
+    if k < 2 {
+        return Err(Error::InvalidSetting(
+            "cross-validation needs at least 2 folds".to_string(),
+        ));
+    }
+
+    let (files, _) = read_sample_files(path)?;
+    if files.is_empty() {
+        return Err(Error::EmptyCorpus(path.to_string()));
+    }
+
+    let samples = files
+        .iter()
+        .map(|(name, texts)| {
+            let samples = match texts.as_slice() {
+                [text] => text.lines().collect::<Vec<_>>(),
+                texts => texts.iter().map(String::as_str).collect(),
+            };
+            let samples = samples
+                .into_iter()
+                .filter(|sample| !sample.trim().is_empty())
+                .collect::<Vec<_>>();
+            (name, samples)
+        })
+        .collect::<Vec<_>>();
+
+    // with more folds than samples some folds have nothing to classify
+    let folds = (0..k)
+        .filter_map(|fold| {
+            let mut model = empty.with_same_options();
+            let mut labeled = Vec::new();
+
+            for (name, samples) in samples.iter() {
+                let mut training = String::new();
+                for (i, sample) in samples.iter().enumerate() {
+                    if i % k == fold {
+                        labeled.push(((*name).clone(), sample.to_string()));
+                    } else {
+                        training.push_str(sample);
+                        training.push('\n');
+                    }
+                }
+
+                if !training.is_empty() {
+                    model.add_category((*name).clone(), &training);
+                }
+            }
+
+            if labeled.is_empty() {
+                return None;
+            }
+            Some(evaluate(&model, &labeled))
+        })
+        .collect();
+
+    let mut model = empty;
+    for (name, texts) in files {
+        model.add_category(name, &texts.join("\n"));
+    }
+
+    Ok((model, CrossValidation::new(folds)))
+}
+
+impl<T: Display> Display for EvalReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self
+            .scores
+            .iter()
+            .map(|score| score.category.to_string())
+            .collect::<Vec<_>>();
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+
+        writeln!(f, "{:w$}  precision  recall     f1  support", "", w = width)?;
+        for (name, score) in names.iter().zip(self.scores.iter()) {
+            writeln!(
+                f,
+                "{:w$}  {:>9.3}  {:>6.3}  {:>5.3}  {:>7}",
+                name,
+                score.precision,
+                score.recall,
+                score.f1,
+                score.support,
+                w = width
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "accuracy: {:.3}", self.accuracy)?;
+        writeln!(f)?;
+
+        write!(f, "{:w$}", "", w = width)?;
+        for name in names.iter() {
+            write!(f, "  {:>w$}", name, w = width)?;
+        }
+        writeln!(f, "  {:>w$}", "unknown", w = width.max(7))?;
+        for (i, name) in names.iter().enumerate() {
+            write!(f, "{:w$}", name, w = width)?;
+            for count in self.confusion[i].iter() {
+                write!(f, "  {:>w$}", count, w = width)?;
+            }
+            writeln!(f, "  {:>w$}", self.unclassified[i], w = width.max(7))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+    use super::*;
+    use crate::category::learn_from_directory;
+
+    #[test]
+    fn evaluate_model() {
+        let model = learn_from_directory("tests").expect("learn");
+        let labeled = vec![
+            (
+                "english".to_string(),
+                "the house is big and red".to_string(),
+            ),
+            ("english".to_string(), "where is the station".to_string()),
+            (
+                "spanish".to_string(),
+                "la casa es grande y roja".to_string(),
+            ),
+            ("french".to_string(), "la maison est grande".to_string()),
+        ];
+
+        let report = evaluate(&model, &labeled);
+        let names = report
+            .scores
+            .iter()
+            .map(|s| s.category.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["english", "spanish", "french"], names);
+        assert_eq!(
+            labeled.len(),
+            report.confusion.iter().flatten().sum::<usize>()
+                + report.unclassified.iter().sum::<usize>()
+        );
+        assert_eq!(0, report.confusion[2][2]);
+        assert_eq!(0.0, report.scores[2].recall);
+        assert_eq!(2, report.scores[0].support);
+        assert!(report.accuracy > 0.0 && report.accuracy <= 0.75);
+
+        let text = report.to_string();
+        assert!(text.contains("accuracy: "));
+        assert!(text.contains("unknown"));
+    }
+
+    #[test]
+    fn cross_validation() {
+        assert!(learn_with_cross_validation("tests", 1).is_err());
+
+        let (model, metrics) =
+            learn_with_cross_validation("tests", 4).expect("learn");
+        assert_eq!(vec!["english", "spanish"], model.categories());
+        assert_eq!(4, metrics.folds.len());
+        assert!(metrics.accuracy > 0.5 && metrics.accuracy <= 1.0);
+
+        let lines = std::fs::read_to_string("tests/english.sample")
+            .expect("read")
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+        assert_eq!("english", metrics.scores[0].category);
+        assert_eq!(lines, metrics.scores[0].support);
+
+        let (_, small) = crate::category::CategoriesBuilder::new()
+            .with_profile_size(20)
+            .learn_with_cross_validation("tests", 4)
+            .expect("learn");
+        assert_ne!(metrics, small);
+    }
+
+    #[test]
+    fn cross_validation_by_file() {
+        let dir = std::env::temp_dir()
+            .join(format!("textcat-folds-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for name in ["english", "spanish"].iter() {
+            let sample = format!("tests/{}.sample", name);
+            let text = std::fs::read_to_string(sample).expect("read");
+            let lines = text.lines().collect::<Vec<_>>();
+            std::fs::create_dir_all(dir.join(name)).expect("mkdir");
+            for (i, chunk) in lines.chunks(lines.len() / 3 + 1).enumerate() {
+                let file = dir.join(name).join(format!("{}.txt", i));
+                std::fs::write(file, chunk.join("\n")).expect("write");
+            }
+        }
+
+        let path = dir.to_str().expect("path");
+        let result = learn_with_cross_validation(path, 3);
+        let more_folds = learn_with_cross_validation(path, 5);
+        let _ = std::fs::remove_dir_all(&dir);
+        let (model, metrics) = result.expect("learn");
+
+        // every file is classified whole, by a model learned from the other files
+        assert_eq!(vec!["english", "spanish"], model.categories());
+        assert_eq!(3, metrics.scores[0].support);
+        assert_eq!(3, metrics.scores[1].support);
+        assert_eq!(1.0, metrics.accuracy);
+
+        // folds without files are left out of the averages
+        let (_, metrics) = more_folds.expect("learn");
+        assert_eq!(3, metrics.folds.len());
+        assert_eq!(3, metrics.scores[0].support);
+        assert_eq!(1.0, metrics.scores[0].recall);
+        assert_eq!(1.0, metrics.accuracy);
+    }
+}
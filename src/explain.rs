@@ -0,0 +1,230 @@
+//! # Explain
+//!
+//! Breaks the distance between a text and each category down to the ngrams it is made of,
+//! to find out why a text was classified as it was (see `Categories::explain`).
+use crate::category::Categories;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+use serde::{Deserialize, Serialize};
+
+/// Ngrams listed for each category by the `Display` of an explanation
+const DISPLAYED_NGRAMS: usize = 10;
+
+/// Share of an ngram of a category profile in the distance to a text
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NgramContribution {
+    /// The ngram
+    pub ngram: String,
+
+    /// Position of the ngram in the category profile, 0 for the best ranked
+    pub category_rank: usize,
+
+    /// Position of the ngram in the profile of the text
+    pub sample_rank: usize,
+
+    /// What the ngram adds to the out-of-place distance
+    pub penalty: u64,
+}
+
+/// Breakdown of the distance between a text and a category
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryExplanation<T> {
+    /// The category
+    pub category: T,
+
+    /// Distance between the category and the text, as returned by
+    /// `Categories::get_distances`
+    pub distance: u64,
+
+    /// Whether the category is a candidate (see `Categories::get_categories`)
+    pub candidate: bool,
+
+    /// Number of ngrams of the category profile missing from the text, each of them adds
+    /// the missing ngram penalty
+    pub missing: usize,
+
+    /// Distance added by each missing ngram (see `Categories::missing_penalty`)
+    pub missing_penalty: u64,
+
+    /// Ngrams of the category profile found in the text, those adding the most first
+    pub ngrams: Vec<NgramContribution>,
+}
+
+/// Result of `Categories::explain`
+///
+/// The penalties follow the out-of-place measure, the default metric: the distance is the
+/// sum of the penalties of the ngrams found in the text plus the penalties of the missing
+/// ones. With other metrics (see `Categories::set_metric`) the penalties still show how
+/// well the profiles match, but they don't add up to the distance.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Explanation<T> {
+    /// Ngrams of the text, best ranked first
+    pub sample: Vec<String>,
+
+    /// Breakdown for every category, closest first
+    pub categories: Vec<CategoryExplanation<T>>,
+}
+
+/// Explains the distances of a text to the categories of a model, see
+/// `Categories::explain`
+pub(crate) fn explain<T>(model: &Categories<T>, sample: &str) -> Explanation<T>
+where
+    for<'a> T: PartialEq<T> + Serialize + Deserialize<'a> + Clone,
+{
+    let ngrams = model.profile(sample);
+    let candidates = model
+        .get_categories_from_ngrams(&ngrams)
+        .unwrap_or_default();
+    let missing_penalty = model.missing_penalty();
+
+    let categories = model
+        .distances(&ngrams)
+        .into_iter()
+        .map(|(category, distance)| {
+            let profile = model
+                .profile_of(&category)
+                .map(|profile| profile.to_vec())
+                .unwrap_or_default();
+            let mut found = profile
+                .iter()
+                .enumerate()
+                .filter_map(|(category_rank, ngram)| {
+                    let sample_rank = ngrams.position(ngram)?;
+                    Some(NgramContribution {
+                        ngram: ngram.to_string(),
+                        category_rank,
+                        sample_rank,
+                        penalty: sample_rank as u64,
+                    })
+                })
+                .collect::<Vec<_>>();
+            found.sort_by(|a, b| {
+                b.penalty
+                    .cmp(&a.penalty)
+                    .then(a.category_rank.cmp(&b.category_rank))
+            });
+
+            CategoryExplanation {
+                candidate: candidates.iter().any(|(c, _)| *c == category),
+                category,
+                distance,
+                missing: profile.len() - found.len(),
+                missing_penalty,
+                ngrams: found,
+            }
+        })
+        .collect();
+
+    Explanation {
+        sample: ngrams.to_vec().into_iter().map(String::from).collect(),
+        categories,
+    }
+}
+
+impl<T: Display> Display for Explanation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, category) in self.categories.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(
+                f,
+                "{}: {}{}",
+                category.category,
+                category.distance,
+                if category.candidate {
+                    " (candidate)"
+                } else {
+                    ""
+                }
+            )?;
+            writeln!(
+                f,
+                "  {} ngrams missing from the text, {} each",
+                category.missing, category.missing_penalty
+            )?;
+
+            if category.ngrams.is_empty() {
+                continue;
+            }
+
+            let shown =
+                &category.ngrams[..category.ngrams.len().min(DISPLAYED_NGRAMS)];
+            let width = shown
+                .iter()
+                .map(|n| n.ngram.chars().count() + 2)
+                .max()
+                .unwrap_or(0)
+                .max(5);
+
+            writeln!(f, "  {:w$}  profile  text  penalty", "ngram", w = width)?;
+            for ngram in shown {
+                writeln!(
+                    f,
+                    "  {:w$}  {:>7}  {:>4}  {:>7}",
+                    format!("\"{}\"", ngram.ngram),
+                    ngram.category_rank,
+                    ngram.sample_rank,
+                    ngram.penalty,
+                    w = width
+                )?;
+            }
+            if category.ngrams.len() > shown.len() {
+                writeln!(
+                    f,
+                    "  ... {} more",
+                    category.ngrams.len() - shown.len()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+    use crate::category::learn_from_directory;
+
+    #[test]
+    fn explain() {
+        let model = learn_from_directory("tests").expect("learn");
+        let text = "la casa es grande y tiene un jardín con muchos árboles";
+        let explanation = model.explain(text);
+
+        let distances = model.get_distances(text);
+        assert_eq!(distances.len(), explanation.categories.len());
+        for (category, (name, distance)) in
+            explanation.categories.iter().zip(distances)
+        {
+            assert_eq!(name, category.category);
+            assert_eq!(
+                distance,
+                category.ngrams.iter().map(|n| n.penalty).sum::<u64>()
+                    + category.missing as u64 * category.missing_penalty
+            );
+        }
+
+        let spanish = &explanation.categories[0];
+        assert_eq!("spanish", spanish.category);
+        assert!(spanish.candidate);
+        assert!(spanish
+            .ngrams
+            .windows(2)
+            .all(|w| w[0].penalty >= w[1].penalty));
+        assert_eq!(
+            Some(spanish.ngrams[0].sample_rank),
+            explanation
+                .sample
+                .iter()
+                .position(|n| *n == spanish.ngrams[0].ngram)
+        );
+
+        let text = explanation.to_string();
+        assert!(text.starts_with("spanish: "));
+        assert!(text.contains("profile  text  penalty"));
+    }
+}
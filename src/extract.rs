@@ -0,0 +1,70 @@
+//! # Extraction
+//!
+//! Steps of the extraction of character ngrams which don't depend on the settings of a
+//! model. build.rs compiles this module too, so the embedded model (see `default`) is
+//! learned exactly as the library learns profiles.
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::ops::Range;
+
+/// Joins lowercased words, each preceded by `_`: the form ngrams are extracted from
+pub(crate) fn join_words<'a, I>(words: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut normalized = String::new();
+    for word in words {
+        normalized.push('_');
+        normalized.push_str(word);
+    }
+    normalized
+}
+
+/// Calls `f` with the length and the slice of every ngram (of `lengths`) of a normalized
+/// text ending after the `from` offset (in chars), in a single scan: the ngrams starting
+/// at each char, shortest first. Single digits and punctuation are not ngrams.
+pub(crate) fn for_each_window<'a, F>(
+    text: &'a str,
+    lengths: Range<usize>,
+    from: usize,
+    mut f: F,
+) where
+    F: FnMut(usize, &'a str),
+{
+    // Byte offset of every char, and of the end of the text
+    let bounds = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(Some(text.len()))
+        .collect::<Vec<usize>>();
+    let chars = bounds.len() - 1;
+
+    for i in 0..chars {
+        for len in lengths.clone() {
+            if len == 0 || i + len <= from {
+                continue;
+            }
+            if i + len > chars {
+                break;
+            }
+
+            let window = &text[bounds[i]..bounds[i + len]];
+            if len == 1
+                && window
+                    .chars()
+                    .all(|c| c.is_numeric() || c.is_ascii_punctuation())
+            {
+                continue;
+            }
+
+            f(len, window);
+        }
+    }
+}
+
+/// Order of the ngrams of a profile: the most frequent first, ties broken by the ngrams
+/// themselves (in reverse bytewise order), so profiles don't depend on hashing
+pub(crate) fn by_rank(a: (&str, u64), b: (&str, u64)) -> Ordering {
+    b.1.cmp(&a.1).then_with(|| b.0.cmp(a.0))
+}
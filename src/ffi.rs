@@ -0,0 +1,141 @@
+//! # FFI
+//!
+//! C interface, built as a `cdylib` with the `ffi` feature, for PHP, Ruby, C and any other
+//! language with a C FFI. The declarations are in `include/textcat.h`.
+//!
+//! Functions returning a handle return NULL on error. Strings are UTF-8 and NUL-terminated.
+use crate::category::{from_bytes, load, Categories};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+/// Classifier handle
+pub struct TextCat {
+    categories: Categories<String>,
+
+    /// Category names returned by `textcat_detect`, which live as long as the handle
+    names: Vec<(String, CString)>,
+}
+
+impl TextCat {
+    fn new(categories: Categories<String>) -> *mut TextCat {
+        let names = categories
+            .categories()
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), CString::new(name).ok()?)))
+            .collect();
+
+        Box::into_raw(Box::new(TextCat { categories, names }))
+    }
+}
+
+/// Creates a classifier from the content of a model file (`len` bytes at `model`)
+///
+/// # Safety
+///
+/// `model` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn textcat_new(
+    model: *const u8,
+    len: usize,
+) -> *mut TextCat {
+    if model.is_null() {
+        return ptr::null_mut();
+    }
+
+    match from_bytes(std::slice::from_raw_parts(model, len)) {
+        Ok(categories) => TextCat::new(categories),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Creates a classifier from a model file
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn textcat_load(path: *const c_char) -> *mut TextCat {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    match CStr::from_ptr(path).to_str().map(load) {
+        Ok(Ok(categories)) => TextCat::new(categories),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Returns the category of a text, or NULL if there is no single candidate (see
+/// `Categories::get_category`). The string belongs to the handle, it must not be freed and
+/// is valid until `textcat_free`.
+///
+/// # Safety
+///
+/// `handle` must come from `textcat_new` or `textcat_load` and not be freed, `text` must be
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn textcat_detect(
+    handle: *const TextCat,
+    text: *const c_char,
+) -> *const c_char {
+    let (handle, text) = match (handle.as_ref(), text.is_null()) {
+        (Some(handle), false) => (handle, CStr::from_ptr(text)),
+        _ => return ptr::null(),
+    };
+
+    let category = handle
+        .categories
+        .get_category(&text.to_string_lossy())
+        .and_then(|category| handle.names.iter().find(|(n, _)| *n == category));
+
+    match category {
+        Some((_, name)) => name.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Frees a classifier. NULL is ignored.
+///
+/// # Safety
+///
+/// `handle` must come from `textcat_new` or `textcat_load`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn textcat_free(handle: *mut TextCat) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        let model =
+            crate::category::learn_from_directory("tests").expect("learn");
+        let json = serde_json::to_vec(&model).expect("serialize");
+
+        unsafe {
+            assert!(textcat_new(b"{}".as_ptr(), 2).is_null());
+            let handle = textcat_new(json.as_ptr(), json.len());
+            assert!(!handle.is_null());
+
+            let text = CString::new(
+                std::fs::read("tests/spanish.sample").expect("read"),
+            )
+            .expect("text");
+            let category = textcat_detect(handle, text.as_ptr());
+            assert_eq!(
+                Some("spanish"),
+                category
+                    .as_ref()
+                    .map(|_| CStr::from_ptr(category).to_str().expect("utf-8"))
+            );
+            assert!(textcat_detect(handle, ptr::null()).is_null());
+
+            textcat_free(handle);
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! # gRPC
+//!
+//! gRPC classification service (`grpc` feature), see `proto/textcat.proto` for the interface.
+//!
+//! When an admin token is configured, the admin RPCs (add/remove categories, persist) are
+//! enabled for requests with an `authorization: Bearer <token>` header. Changes are applied
+//! to a copy of the model which is then swapped in, classification is never blocked nor sees
+//! a half updated model.
+#![allow(missing_docs)]
+use crate::category::Categories;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Types and service definitions generated from `proto/textcat.proto`
+pub mod proto {
+    tonic::include_proto!("textcat");
+}
+
+use proto::text_cat_server::{TextCat, TextCatServer};
+use proto::{
+    AddCategoryRequest, AddCategoryResponse, Candidate, ClassifyBatchRequest,
+    ClassifyBatchResponse, ClassifyRequest, ClassifyResponse,
+    ListCategoriesRequest, ListCategoriesResponse, PersistModelRequest,
+    PersistModelResponse, RemoveCategoryRequest, RemoveCategoryResponse,
+};
+
+/// gRPC service which classifies texts with a model
+#[derive(Clone)]
+pub struct Service {
+    categories: Arc<RwLock<Arc<Categories<String>>>>,
+    /// Serializes the admin operations
+    admin: Arc<Mutex<()>>,
+    admin_token: Option<String>,
+    model_path: Option<String>,
+}
+
+impl Service {
+    /// Creates a new service for a given model
+    pub fn new(categories: Categories<String>) -> Service {
+        Service {
+            categories: Arc::new(RwLock::new(Arc::new(categories))),
+            admin: Arc::new(Mutex::new(())),
+            admin_token: None,
+            model_path: None,
+        }
+    }
+
+    /// Enables the admin RPCs for requests bearing the given token
+    pub fn with_admin_token(mut self, token: &str) -> Service {
+        self.admin_token = Some(token.to_string());
+        self
+    }
+
+    /// Sets the file where `PersistModel` stores the model
+    pub fn with_model_path(mut self, path: &str) -> Service {
+        self.model_path = Some(path.to_string());
+        self
+    }
+
+    /// Wraps the service to be added to a tonic server
+    pub fn into_server(self) -> TextCatServer<Service> {
+        TextCatServer::new(self)
+    }
+
+    /// Current model
+    fn model(&self) -> Arc<Categories<String>> {
+        match self.categories.read() {
+            Ok(categories) => categories.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the model by an updated copy
+    fn swap(&self, categories: Categories<String>) {
+        let mut current = match self.categories.write() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *current = Arc::new(categories);
+    }
+
+    /// Checks the request is allowed to use the admin RPCs
+    fn authorize<R>(&self, request: &Request<R>) -> Result<(), Status> {
+        let token = self.admin_token.as_ref().ok_or_else(|| {
+            Status::permission_denied("admin operations are disabled")
+        })?;
+
+        let provided = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing admin token"))?;
+
+        if !crate::constant_time_eq(provided.as_bytes(), token.as_bytes()) {
+            return Err(Status::unauthenticated("invalid admin token"));
+        }
+
+        Ok(())
+    }
+
+    fn classify_text(
+        categories: &Categories<String>,
+        text: &str,
+    ) -> ClassifyResponse {
+        let candidates = categories.get_categories(text).unwrap_or_default();
+
+        ClassifyResponse {
+            category: match candidates.as_slice() {
+                [(category, _)] => Some(category.clone()),
+                _ => None,
+            },
+            candidates: candidates
+                .into_iter()
+                .map(|(category, distance)| Candidate { category, distance })
+                .collect(),
+        }
+    }
+}
+
+type ResponseStream =
+    Pin<Box<dyn Stream<Item = Result<ClassifyResponse, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl TextCat for Service {
+    async fn classify(
+        &self,
+        request: Request<ClassifyRequest>,
+    ) -> Result<Response<ClassifyResponse>, Status> {
+        Ok(Response::new(Self::classify_text(
+            &self.model(),
+            &request.get_ref().text,
+        )))
+    }
+
+    async fn classify_batch(
+        &self,
+        request: Request<ClassifyBatchRequest>,
+    ) -> Result<Response<ClassifyBatchResponse>, Status> {
+        let model = self.model();
+        let results = request
+            .get_ref()
+            .texts
+            .iter()
+            .map(|text| Self::classify_text(&model, text))
+            .collect();
+
+        Ok(Response::new(ClassifyBatchResponse { results }))
+    }
+
+    type ClassifyStreamStream = ResponseStream;
+
+    async fn classify_stream(
+        &self,
+        request: Request<Streaming<ClassifyRequest>>,
+    ) -> Result<Response<Self::ClassifyStreamStream>, Status> {
+        let model = self.model();
+        let output = request.into_inner().map(move |request| {
+            Ok(Self::classify_text(&model, &request?.text))
+        });
+
+        Ok(Response::new(Box::pin(output)))
+    }
+
+    async fn list_categories(
+        &self,
+        _request: Request<ListCategoriesRequest>,
+    ) -> Result<Response<ListCategoriesResponse>, Status> {
+        Ok(Response::new(ListCategoriesResponse {
+            categories: self.model().categories(),
+        }))
+    }
+
+    async fn add_category(
+        &self,
+        request: Request<AddCategoryRequest>,
+    ) -> Result<Response<AddCategoryResponse>, Status> {
+        self.authorize(&request)?;
+        let _admin = self.admin.lock();
+        let request = request.into_inner();
+
+        let mut categories = (*self.model()).clone();
+        if categories.categories().contains(&request.name) {
+            return Err(Status::already_exists(format!(
+                "category {} already exists",
+                request.name
+            )));
+        }
+
+        categories.add_category(request.name, &request.text);
+        categories
+            .validate()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let names = categories.categories();
+        self.swap(categories);
+
+        Ok(Response::new(AddCategoryResponse { categories: names }))
+    }
+
+    async fn remove_category(
+        &self,
+        request: Request<RemoveCategoryRequest>,
+    ) -> Result<Response<RemoveCategoryResponse>, Status> {
+        self.authorize(&request)?;
+        let _admin = self.admin.lock();
+        let name = request.into_inner().name;
+
+        let mut categories = (*self.model()).clone();
+        let before = categories.categories().len();
+        categories.retain(|category| *category != name);
+        let removed = categories.categories().len() != before;
+
+        if removed && categories.categories().is_empty() {
+            return Err(Status::failed_precondition(
+                "the last category cannot be removed",
+            ));
+        }
+
+        if removed {
+            self.swap(categories);
+        }
+
+        Ok(Response::new(RemoveCategoryResponse { removed }))
+    }
+
+    async fn persist_model(
+        &self,
+        request: Request<PersistModelRequest>,
+    ) -> Result<Response<PersistModelResponse>, Status> {
+        self.authorize(&request)?;
+        let _admin = self.admin.lock();
+
+        let path = self.model_path.as_ref().ok_or_else(|| {
+            Status::failed_precondition("no model file configured")
+        })?;
+
+        self.model()
+            .persist_atomic(path)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(PersistModelResponse {}))
+    }
+}
+
+#[cfg(all(test, feature = "training"))]
+mod test {
+    use super::*;
+    use crate::category::learn_from_directory;
+
+    #[tokio::test]
+    async fn test_classify_batch() {
+        let categories = learn_from_directory("tests").expect("learn");
+        let texts = vec![
+            "the quick brown fox jumps over the lazy dog".to_string(),
+            "el rápido zorro marrón salta sobre el perro perezoso".to_string(),
+        ];
+        let expected = texts
+            .iter()
+            .map(|text| categories.get_category(text))
+            .collect::<Vec<_>>();
+
+        let service = Service::new(categories);
+        let response = service
+            .classify_batch(Request::new(ClassifyBatchRequest { texts }))
+            .await
+            .expect("classify")
+            .into_inner();
+
+        let categories = response
+            .results
+            .iter()
+            .map(|r| r.category.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(expected, categories);
+    }
+
+    #[tokio::test]
+    async fn test_list_categories() {
+        let service =
+            Service::new(learn_from_directory("tests").expect("learn"));
+        let response = service
+            .list_categories(Request::new(ListCategoriesRequest {}))
+            .await
+            .expect("list")
+            .into_inner();
+
+        assert_eq!(vec!["english", "spanish"], response.categories);
+    }
+
+    fn admin_request<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        let value = format!("Bearer {}", token).parse().expect("header");
+        request.metadata_mut().insert("authorization", value);
+        request
+    }
+
+    #[tokio::test]
+    async fn test_admin_operations() {
+        let path = std::env::temp_dir()
+            .join(format!("textcat-grpc-admin-{}.json", std::process::id()));
+        let path = path.to_str().expect("path");
+        let service =
+            Service::new(learn_from_directory("tests").expect("learn"))
+                .with_admin_token("secret")
+                .with_model_path(path);
+        let add = || AddCategoryRequest {
+            name: "german".to_string(),
+            text: "der schnelle braune Fuchs springt".to_string(),
+        };
+
+        let denied = service.add_category(Request::new(add())).await;
+        assert_eq!(
+            tonic::Code::Unauthenticated,
+            denied.expect_err("auth").code()
+        );
+        let denied = service.add_category(admin_request(add(), "wrong")).await;
+        assert_eq!(
+            tonic::Code::Unauthenticated,
+            denied.expect_err("auth").code()
+        );
+
+        let response = service
+            .add_category(admin_request(add(), "secret"))
+            .await
+            .expect("add")
+            .into_inner();
+        assert_eq!(vec!["english", "spanish", "german"], response.categories);
+
+        let request = RemoveCategoryRequest {
+            name: "english".to_string(),
+        };
+        let response = service
+            .remove_category(admin_request(request, "secret"))
+            .await
+            .expect("remove")
+            .into_inner();
+        assert!(response.removed);
+
+        service
+            .persist_model(admin_request(PersistModelRequest {}, "secret"))
+            .await
+            .expect("persist");
+        let persisted = crate::category::load::<String>(path).expect("load");
+        let _ = std::fs::remove_file(path);
+        assert_eq!(vec!["german", "spanish"], persisted.categories());
+    }
+}
@@ -0,0 +1,303 @@
+//! # HTTP
+//!
+//! HTTP classification service (`http` feature), to deploy the detector as a sidecar:
+//!
+//! - `POST /detect`: classifies the request body, returns the category (null when it can't
+//!   be told) and the candidates with their distance and normalized score, as JSON.
+//! - `GET /categories`: names of the categories of the model, as JSON.
+//! - `POST /models`: replaces the model by the one in the request body (any format `load`
+//!   accepts). Only enabled with an admin token, for requests with an
+//!   `authorization: Bearer <token>` header. The new model is swapped in, classification
+//!   is never blocked nor sees a half updated model. It is only written to a file when a
+//!   path is set with `Service::with_model_path`.
+use crate::category::{from_bytes, Categories, Detection, Score};
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Largest model accepted by `POST /models`
+const MAX_MODEL_SIZE: usize = 256 * 1024 * 1024;
+
+/// Error response, a status and a message
+type Rejection = (StatusCode, String);
+
+/// Candidate category of a text
+#[derive(Debug, Serialize)]
+struct Candidate {
+    category: String,
+    distance: u64,
+    /// Distance from 0 (identical) to 1 (no ngram in common)
+    score: f32,
+}
+
+/// Response of `POST /detect`
+#[derive(Debug, Serialize)]
+struct DetectResponse {
+    category: Option<String>,
+    candidates: Vec<Candidate>,
+}
+
+/// Response of `POST /models`
+#[derive(Debug, Serialize)]
+struct ModelResponse {
+    categories: Vec<String>,
+}
+
+/// HTTP service which classifies texts with a model
+#[derive(Clone)]
+pub struct Service {
+    categories: Arc<RwLock<Arc<Categories<String>>>>,
+    /// Serializes the model uploads
+    admin: Arc<Mutex<()>>,
+    admin_token: Option<String>,
+    model_path: Option<String>,
+}
+
+impl Service {
+    /// Creates a new service for a given model
+    pub fn new(categories: Categories<String>) -> Service {
+        Service {
+            categories: Arc::new(RwLock::new(Arc::new(categories))),
+            admin: Arc::new(Mutex::new(())),
+            admin_token: None,
+            model_path: None,
+        }
+    }
+
+    /// Enables `POST /models` for requests bearing the given token
+    pub fn with_admin_token(mut self, token: &str) -> Service {
+        self.admin_token = Some(token.to_string());
+        self
+    }
+
+    /// Sets the file where uploaded models are stored (as JSON), so they survive a restart.
+    /// Uploaded models are only kept in memory otherwise.
+    pub fn with_model_path(mut self, path: &str) -> Service {
+        self.model_path = Some(path.to_string());
+        self
+    }
+
+    /// Routes of the service, to be served with `axum::serve`
+    pub fn into_router(self) -> Router {
+        Router::new()
+            .route("/detect", post(detect))
+            .route("/categories", get(categories))
+            .route(
+                "/models",
+                post(upload_model).layer(DefaultBodyLimit::max(MAX_MODEL_SIZE)),
+            )
+            .with_state(self)
+    }
+
+    /// Current model
+    fn model(&self) -> Arc<Categories<String>> {
+        match self.categories.read() {
+            Ok(categories) => categories.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the model
+    fn swap(&self, categories: Categories<String>) {
+        let mut current = match self.categories.write() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *current = Arc::new(categories);
+    }
+
+    /// Decodes an uploaded model, stores it if there is a model path and swaps it in.
+    /// Uploads are serialized, and it blocks: decoding a large model and writing it take a
+    /// while.
+    fn install(&self, model: &[u8]) -> Result<Vec<String>, Rejection> {
+        let _admin = match self.admin.lock() {
+            Ok(admin) => admin,
+            // a failed upload never leaves a half written file (see `persist_atomic`)
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let categories = from_bytes::<String>(model)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        if let Some(path) = self.model_path.as_ref() {
+            categories.persist_atomic(path).map_err(|e| {
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            })?;
+        }
+
+        let names = categories.categories();
+        self.swap(categories);
+        Ok(names)
+    }
+
+    /// Checks the request is allowed to upload models
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), Rejection> {
+        let token = self.admin_token.as_ref().ok_or_else(|| {
+            (
+                StatusCode::FORBIDDEN,
+                "model uploads are disabled".to_string(),
+            )
+        })?;
+
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                (StatusCode::UNAUTHORIZED, "missing admin token".to_string())
+            })?;
+
+        if !crate::constant_time_eq(provided.as_bytes(), token.as_bytes()) {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "invalid admin token".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+async fn detect(
+    State(service): State<Service>,
+    text: String,
+) -> Json<DetectResponse> {
+    let candidate = |(category, score): (String, Score)| Candidate {
+        category,
+        distance: score.distance,
+        score: score.normalized,
+    };
+
+    let response = match service.model().detect(&text) {
+        Detection::Match(category, score) => DetectResponse {
+            category: Some(category.clone()),
+            candidates: vec![candidate((category, score))],
+        },
+        Detection::Ambiguous(candidates) => DetectResponse {
+            category: None,
+            candidates: candidates.into_iter().map(candidate).collect(),
+        },
+        _ => DetectResponse {
+            category: None,
+            candidates: Vec::new(),
+        },
+    };
+
+    Json(response)
+}
+
+async fn categories(State(service): State<Service>) -> Json<Vec<String>> {
+    Json(service.model().categories())
+}
+
+async fn upload_model(
+    State(service): State<Service>,
+    headers: HeaderMap,
+    model: Bytes,
+) -> Result<Json<ModelResponse>, Rejection> {
+    service.authorize(&headers)?;
+
+    let names = tokio::task::spawn_blocking(move || service.install(&model))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(ModelResponse { categories: names }))
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+    use super::*;
+    use crate::category::learn_from_directory;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn call(
+        router: &Router,
+        request: Request<Body>,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = router.clone().oneshot(request).await.expect("response");
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn post(uri: &str, body: impl Into<Body>) -> Request<Body> {
+        Request::post(uri).body(body.into()).expect("request")
+    }
+
+    #[tokio::test]
+    async fn detect_and_list() {
+        let model = learn_from_directory("tests").expect("learn");
+        let router = Service::new(model).into_router();
+
+        let text = std::fs::read("tests/spanish.sample").expect("read");
+        let (status, body) = call(&router, post("/detect", text)).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("spanish", body["category"]);
+        assert_eq!("spanish", body["candidates"][0]["category"]);
+        assert!(body["candidates"][0]["score"].as_f64().expect("score") < 1.0);
+
+        let (status, body) = call(
+            &router,
+            Request::get("/categories")
+                .body(Body::empty())
+                .expect("request"),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(serde_json::json!(["english", "spanish"]), body);
+    }
+
+    #[tokio::test]
+    async fn upload_model() {
+        let model = learn_from_directory("tests").expect("learn");
+        let mut english = model.clone();
+        english.retain(|name| name == "english");
+        let upload = serde_json::to_vec(&english).expect("serialize");
+
+        let router = Service::new(model.clone()).into_router();
+        let (status, _) = call(&router, post("/models", upload.clone())).await;
+        assert_eq!(StatusCode::FORBIDDEN, status);
+
+        let path = std::env::temp_dir()
+            .join(format!("textcat-http-{}.json", std::process::id()));
+        let router = Service::new(model)
+            .with_admin_token("secret")
+            .with_model_path(path.to_str().expect("path"))
+            .into_router();
+        let upload_as = |token: &str, body: Vec<u8>| {
+            Request::post("/models")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::from(body))
+                .expect("request")
+        };
+
+        let (status, _) =
+            call(&router, upload_as("wrong", upload.clone())).await;
+        assert_eq!(StatusCode::UNAUTHORIZED, status);
+        let (status, _) =
+            call(&router, upload_as("secret", b"{}".to_vec())).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+
+        let (status, body) = call(&router, upload_as("secret", upload)).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(serde_json::json!(["english"]), body["categories"]);
+
+        let (_, body) =
+            call(&router, post("/detect", "hola, ¿cómo estás?")).await;
+        assert_eq!("english", body["category"]);
+
+        let stored =
+            crate::category::load::<String>(path.to_str().expect("path"))
+                .expect("stored model");
+        assert_eq!(vec!["english"], stored.categories());
+        std::fs::remove_file(path).expect("remove");
+    }
+}
@@ -0,0 +1,119 @@
+//! # Index
+//!
+//! Inverted index of the category profiles of a model, from each ngram to the categories
+//! whose profile has it. With many categories it shortlists the few sharing the most ngrams
+//! with a text (see `Categories::set_shortlist`), and only those are measured. Ngrams are
+//! referred to by their id in the pool of the model (see `Interner::intern_id`).
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Categories of each ngram, by their position in the model
+#[derive(Debug, Clone, Default)]
+pub struct InvertedIndex {
+    /// Categories of each ngram id
+    postings: Vec<Vec<u32>>,
+}
+
+impl InvertedIndex {
+    /// Creates a new empty index
+    pub fn new() -> InvertedIndex {
+        InvertedIndex::default()
+    }
+
+    /// Adds the ngrams of the category at a given position
+    pub fn add(&mut self, id: usize, ngrams: &[u32]) {
+        for ngram in ngrams {
+            let ngram = *ngram as usize;
+            if ngram >= self.postings.len() {
+                self.postings.resize_with(ngram + 1, Vec::new);
+            }
+            if self.postings[ngram].last() != Some(&(id as u32)) {
+                self.postings[ngram].push(id as u32);
+            }
+        }
+    }
+
+    /// Removes the ngrams of the category at a given position
+    pub fn remove(&mut self, id: usize, ngrams: &[u32]) {
+        for ngram in ngrams {
+            if let Some(postings) = self.postings.get_mut(*ngram as usize) {
+                postings.retain(|category| *category != id as u32);
+            }
+        }
+    }
+
+    /// Categories which have a given ngram
+    pub fn get(&self, ngram: u32) -> &[u32] {
+        self.postings
+            .get(ngram as usize)
+            .map_or(&[], |postings| postings.as_slice())
+    }
+
+    /// Number of distinct ngrams in the index
+    pub fn len(&self) -> usize {
+        self.postings.iter().filter(|p| !p.is_empty()).count()
+    }
+
+    /// Returns true if the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the positions of the (at most) `size` categories sharing the most ngrams
+    /// with a text, among the `categories` first ones for which `keep` returns true. Ties
+    /// are broken by position, and the result is sorted by position.
+    pub fn shortlist<I, F>(
+        &self,
+        ngrams: I,
+        categories: usize,
+        size: usize,
+        keep: F,
+    ) -> Vec<usize>
+    where
+        I: IntoIterator<Item = u32>,
+        F: Fn(usize) -> bool,
+    {
+        let mut overlap = vec![0usize; categories];
+        for ngram in ngrams {
+            for id in self.get(ngram) {
+                if let Some(count) = overlap.get_mut(*id as usize) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let mut ids =
+            (0..categories).filter(|id| keep(*id)).collect::<Vec<_>>();
+        ids.sort_by(|a, b| overlap[*b].cmp(&overlap[*a]).then(a.cmp(b)));
+        ids.truncate(size);
+        ids.sort_unstable();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortlist() {
+        let mut index = InvertedIndex::new();
+        index.add(0, &[0, 1, 2]);
+        index.add(1, &[2, 3]);
+        index.add(2, &[4, 5]);
+
+        assert_eq!(&[0, 1], index.get(2));
+        assert_eq!(6, index.len());
+
+        let text = vec![2, 3, 0];
+        assert_eq!(vec![0, 1], index.shortlist(text.clone(), 3, 2, |_| true));
+        assert_eq!(vec![1], index.shortlist(text.clone(), 3, 1, |id| id > 0));
+        assert_eq!(vec![1, 2], index.shortlist(text, 3, 2, |id| id != 0));
+
+        index.remove(1, &[2, 3]);
+        assert_eq!(&[0], index.get(2));
+        assert!(index.get(3).is_empty());
+        assert!(index.get(9).is_empty());
+        assert_eq!(5, index.len());
+    }
+}
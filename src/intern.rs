@@ -0,0 +1,68 @@
+//! # Intern
+//!
+//! Numbering of ngrams. Each distinct ngram of a model gets a small id, so profiles can be
+//! compared as lists of ids without hashing strings. The pool of a model is its only ngram
+//! lookup table: category profiles drop their own once they have ids.
+//!
+//! The pool doesn't store the ngrams of the categories: each profile keeps its ngrams, as
+//! short strings stored inline (see `ngram::Ngram`), so an ngram found in several
+//! categories is still stored once per category.
+use crate::collections::HashMap;
+use smol_str::SmolStr;
+
+/// Ids of the distinct ngrams of a model
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    ids: HashMap<SmolStr, u32>,
+}
+
+impl Interner {
+    /// Creates a new empty pool
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Returns the id of a given string, adding it to the pool if needed. Ids are given in
+    /// order, from 0.
+    pub fn intern_id(&mut self, value: &str) -> u32 {
+        if let Some(id) = self.ids.get(value) {
+            return *id;
+        }
+
+        let id = self.ids.len() as u32;
+        self.ids.insert(value.into(), id);
+        id
+    }
+
+    /// Returns the id of a string, None if it is not in the pool
+    pub fn id(&self, value: &str) -> Option<u32> {
+        self.ids.get(value).copied()
+    }
+
+    /// Returns the number of distinct strings in the pool
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns true if the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_ids() {
+        let mut pool = Interner::new();
+        assert_eq!(0, pool.intern_id("the_"));
+        assert_eq!(1, pool.intern_id("ion"));
+        assert_eq!(0, pool.intern_id(&"the_".to_string()));
+
+        assert_eq!(Some(1), pool.id("ion"));
+        assert_eq!(None, pool.id("xyz"));
+        assert_eq!(2, pool.len());
+    }
+}
@@ -0,0 +1,202 @@
+//! # Lang
+//!
+//! Languages with their ISO 639 codes. Models name their categories freely, usually after
+//! the language ("english"), `Lang` maps such names, and ISO 639-1 or 639-3 codes, to a
+//! strongly typed language:
+//!
+//! ```
+//! use textcat::lang::Lang;
+//!
+//! let lang: Lang = "english".parse().unwrap();
+//! assert_eq!(Lang::Eng, lang);
+//! assert_eq!(Some("en"), lang.code_639_1());
+//! assert_eq!("eng", lang.code_639_3());
+//! assert_eq!(Some(Lang::Eng), Lang::find("en"));
+//! ```
+use crate::error::Error;
+use alloc::string::ToString;
+use core::fmt::{self, Display};
+use core::str::FromStr;
+
+macro_rules! languages {
+    ($($lang:ident => $iso1:expr, $iso3:literal, $name:literal;)*) => {
+        /// A language, named after its ISO 639-3 code
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Lang {
+            $(
+                #[doc = $name]
+                $lang,
+            )*
+        }
+
+        /// Every language with its ISO 639-1 code, if it has one, its ISO 639-3 code and
+        /// its English name
+        const LANGUAGES: &[(Lang, Option<&str>, &str, &str)] = &[
+            $((Lang::$lang, $iso1, $iso3, $name),)*
+        ];
+    };
+}
+
+languages! {
+    Afr => Some("af"), "afr", "Afrikaans";
+    Ara => Some("ar"), "ara", "Arabic";
+    Aze => Some("az"), "aze", "Azerbaijani";
+    Bel => Some("be"), "bel", "Belarusian";
+    Ben => Some("bn"), "ben", "Bengali";
+    Bos => Some("bs"), "bos", "Bosnian";
+    Bre => Some("br"), "bre", "Breton";
+    Bul => Some("bg"), "bul", "Bulgarian";
+    Cat => Some("ca"), "cat", "Catalan";
+    Ceb => None, "ceb", "Cebuano";
+    Ces => Some("cs"), "ces", "Czech";
+    Cym => Some("cy"), "cym", "Welsh";
+    Dan => Some("da"), "dan", "Danish";
+    Deu => Some("de"), "deu", "German";
+    Ell => Some("el"), "ell", "Greek";
+    Eng => Some("en"), "eng", "English";
+    Epo => Some("eo"), "epo", "Esperanto";
+    Est => Some("et"), "est", "Estonian";
+    Eus => Some("eu"), "eus", "Basque";
+    Fas => Some("fa"), "fas", "Persian";
+    Fin => Some("fi"), "fin", "Finnish";
+    Fra => Some("fr"), "fra", "French";
+    Gle => Some("ga"), "gle", "Irish";
+    Glg => Some("gl"), "glg", "Galician";
+    Guj => Some("gu"), "guj", "Gujarati";
+    Haw => None, "haw", "Hawaiian";
+    Heb => Some("he"), "heb", "Hebrew";
+    Hin => Some("hi"), "hin", "Hindi";
+    Hrv => Some("hr"), "hrv", "Croatian";
+    Hun => Some("hu"), "hun", "Hungarian";
+    Hye => Some("hy"), "hye", "Armenian";
+    Ind => Some("id"), "ind", "Indonesian";
+    Isl => Some("is"), "isl", "Icelandic";
+    Ita => Some("it"), "ita", "Italian";
+    Jpn => Some("ja"), "jpn", "Japanese";
+    Kan => Some("kn"), "kan", "Kannada";
+    Kat => Some("ka"), "kat", "Georgian";
+    Kaz => Some("kk"), "kaz", "Kazakh";
+    Kor => Some("ko"), "kor", "Korean";
+    Lat => Some("la"), "lat", "Latin";
+    Lav => Some("lv"), "lav", "Latvian";
+    Lit => Some("lt"), "lit", "Lithuanian";
+    Mal => Some("ml"), "mal", "Malayalam";
+    Mar => Some("mr"), "mar", "Marathi";
+    Mkd => Some("mk"), "mkd", "Macedonian";
+    Mon => Some("mn"), "mon", "Mongolian";
+    Msa => Some("ms"), "msa", "Malay";
+    Nep => Some("ne"), "nep", "Nepali";
+    Nld => Some("nl"), "nld", "Dutch";
+    Nor => Some("no"), "nor", "Norwegian";
+    Pan => Some("pa"), "pan", "Punjabi";
+    Pol => Some("pl"), "pol", "Polish";
+    Por => Some("pt"), "por", "Portuguese";
+    Ron => Some("ro"), "ron", "Romanian";
+    Rus => Some("ru"), "rus", "Russian";
+    Slk => Some("sk"), "slk", "Slovak";
+    Slv => Some("sl"), "slv", "Slovenian";
+    Som => Some("so"), "som", "Somali";
+    Spa => Some("es"), "spa", "Spanish";
+    Sqi => Some("sq"), "sqi", "Albanian";
+    Srp => Some("sr"), "srp", "Serbian";
+    Swa => Some("sw"), "swa", "Swahili";
+    Swe => Some("sv"), "swe", "Swedish";
+    Tam => Some("ta"), "tam", "Tamil";
+    Tel => Some("te"), "tel", "Telugu";
+    Tgl => Some("tl"), "tgl", "Tagalog";
+    Tha => Some("th"), "tha", "Thai";
+    Tur => Some("tr"), "tur", "Turkish";
+    Ukr => Some("uk"), "ukr", "Ukrainian";
+    Urd => Some("ur"), "urd", "Urdu";
+    Uzb => Some("uz"), "uzb", "Uzbek";
+    Vie => Some("vi"), "vie", "Vietnamese";
+    Yor => Some("yo"), "yor", "Yoruba";
+    Zho => Some("zh"), "zho", "Chinese";
+    Zul => Some("zu"), "zul", "Zulu";
+}
+
+impl Lang {
+    fn entry(
+        self,
+    ) -> &'static (Lang, Option<&'static str>, &'static str, &'static str) {
+        &LANGUAGES[self as usize]
+    }
+
+    /// Returns every known language
+    pub fn all() -> impl Iterator<Item = Lang> {
+        LANGUAGES.iter().map(|entry| entry.0)
+    }
+
+    /// Two-letter ISO 639-1 code, not every language has one
+    pub fn code_639_1(self) -> Option<&'static str> {
+        self.entry().1
+    }
+
+    /// Three-letter ISO 639-3 code
+    pub fn code_639_3(self) -> &'static str {
+        self.entry().2
+    }
+
+    /// English name of the language
+    pub fn name(self) -> &'static str {
+        self.entry().3
+    }
+
+    /// Finds a language by its ISO 639-1 or 639-3 code or its English name, ignoring case.
+    /// Category names are usually one of them.
+    pub fn find(name: &str) -> Option<Lang> {
+        let name = name.trim();
+        LANGUAGES
+            .iter()
+            .find(|(_, iso1, iso3, english)| {
+                iso1.map_or(false, |code| code.eq_ignore_ascii_case(name))
+                    || iso3.eq_ignore_ascii_case(name)
+                    || english.eq_ignore_ascii_case(name)
+            })
+            .map(|entry| entry.0)
+    }
+
+    /// Returns true if a category name, or a code, stands for this language
+    pub fn matches(self, name: &str) -> bool {
+        Lang::find(name) == Some(self)
+    }
+}
+
+impl FromStr for Lang {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Lang, Error> {
+        Lang::find(name).ok_or_else(|| Error::UnknownLanguage(name.to_string()))
+    }
+}
+
+impl Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_and_names() {
+        for (i, lang) in Lang::all().enumerate() {
+            assert_eq!(i, lang as usize);
+            assert_eq!(Some(lang), Lang::find(lang.code_639_3()));
+            assert_eq!(Some(lang), Lang::find(lang.name()));
+            if let Some(code) = lang.code_639_1() {
+                assert_eq!(Some(lang), Lang::find(code));
+            }
+        }
+
+        assert_eq!(Some(Lang::Spa), Lang::find("spanish"));
+        assert_eq!(Some(Lang::Spa), Lang::find("ES"));
+        assert_eq!(None, Lang::Ceb.code_639_1());
+        assert!(Lang::Por.matches("pt"));
+        assert!(!Lang::Por.matches("es"));
+        assert!("klingon".parse::<Lang>().is_err());
+        assert_eq!("German", Lang::Deu.to_string());
+    }
+}
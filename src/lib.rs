@@ -1,7 +1,67 @@
 //! # Textcat
 //!
 //! Library to extract and categorize texts by ngrams.
+//!
+//! Without the `std` feature (on by default) the library is `no_std` + `alloc`: ngrams and
+//! in-memory models work, file I/O, training from directories and the bindings don't.
 #![deny(missing_docs)]
 #![allow(warnings)]
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "rkyv")]
+pub mod archive;
 pub mod category;
+#[cfg(feature = "std")]
+pub mod classifier;
+#[cfg(feature = "code")]
+pub mod code;
+pub mod default;
+pub mod encoding;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod eval;
+pub mod explain;
+mod extract;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod index;
+pub mod intern;
+pub mod lang;
+#[cfg(feature = "markup")]
+pub mod markup;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
 pub mod ngram;
+#[cfg(feature = "napi")]
+pub mod node;
+pub mod normalize;
+pub mod sampling;
+pub mod script;
+pub mod threshold;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::{Error, MergeError, Result};
+
+/// Hash maps of `std`, or of `hashbrown` without it
+mod collections {
+    #[cfg(not(feature = "std"))]
+    pub use hashbrown::{HashMap, HashSet};
+    #[cfg(feature = "std")]
+    pub use std::collections::{HashMap, HashSet};
+}
+
+/// Compares two secrets without leaking where they differ through timing
+#[cfg(any(feature = "grpc", feature = "http"))]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
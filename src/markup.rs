@@ -0,0 +1,390 @@
+//! # Markup
+//!
+//! Visible text of HTML pages and prose of Markdown documents, so scraped pages and READMEs
+//! are classified by their words rather than by their markup, scripts and code (see
+//! `Categories::get_category_from_html`).
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Elements whose content is never displayed as text
+const HIDDEN_ELEMENTS: [&str; 6] =
+    ["script", "style", "noscript", "template", "svg", "math"];
+
+/// Elements displayed within a line, their tags do not separate words
+const INLINE_ELEMENTS: [&str; 17] = [
+    "a", "abbr", "b", "bdi", "bdo", "cite", "em", "i", "kbd", "mark", "q", "s",
+    "small", "span", "strong", "sub", "sup",
+];
+
+/// Returns the visible text of an HTML document: tags, comments and the content of
+/// scripts, styles and other hidden elements are removed, and the common character
+/// references (`&amp;`, `&eacute;`, `&#233;`...) are decoded. Block elements are
+/// replaced with new lines.
+pub fn html_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find(|c| c == '<' || c == '&') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with('&') {
+            match decode_reference(rest) {
+                Some((decoded, len)) => {
+                    text.push(decoded);
+                    rest = &rest[len..];
+                }
+                None => {
+                    text.push('&');
+                    rest = &rest[1..];
+                }
+            }
+            continue;
+        }
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+
+        let name = tag_name(rest);
+        if name.is_empty() && !rest[1..].starts_with(|c| c == '!' || c == '?') {
+            text.push('<');
+            rest = &rest[1..];
+            continue;
+        }
+
+        rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        let closing = name.starts_with('/');
+        let element = name.trim_start_matches('/');
+
+        if !closing && is_one_of(element, &HIDDEN_ELEMENTS) {
+            rest = skip_element(rest, element);
+        }
+        if !is_one_of(element, &INLINE_ELEMENTS) {
+            text.push('\n');
+        }
+    }
+    text.push_str(rest);
+
+    text
+}
+
+/// Returns the prose of a Markdown document: fenced and indented code blocks, code spans,
+/// the URLs of links and images and autolinks are removed.
+pub fn markdown_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    for (line, kind) in markdown_lines(markdown) {
+        if kind == Line::Prose {
+            inline_text(line, &mut text);
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+/// Code block of a Markdown document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock<'a> {
+    /// Info string of the opening fence, usually the language ("rust" for "```rust"),
+    /// empty for indented blocks and fences without one
+    pub info: &'a str,
+    /// Code of the block, one line after another
+    pub code: String,
+}
+
+/// Returns the fenced and indented code blocks of a Markdown document, the parts left out
+/// by `markdown_text`
+pub fn code_blocks(markdown: &str) -> Vec<CodeBlock<'_>> {
+    let mut blocks: Vec<CodeBlock<'_>> = Vec::new();
+    let (mut fenced, mut indented) = (false, false);
+    let push = |blocks: &mut Vec<CodeBlock<'_>>, code: &str| {
+        if let Some(block) = blocks.last_mut() {
+            block.code.push_str(code);
+            block.code.push('\n');
+        }
+    };
+
+    for (line, kind) in markdown_lines(markdown) {
+        match kind {
+            Line::Fence if fenced => fenced = false,
+            Line::Fence => {
+                let info = line
+                    .trim()
+                    .trim_start_matches(|c| c == '`' || c == '~')
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default();
+                blocks.push(CodeBlock {
+                    info,
+                    code: String::new(),
+                });
+                (fenced, indented) = (true, false);
+            }
+            Line::Code if fenced => push(&mut blocks, line),
+            Line::Code => push(&mut blocks, ""),
+            Line::Indented => {
+                if !indented {
+                    blocks.push(CodeBlock {
+                        info: "",
+                        code: String::new(),
+                    });
+                    indented = true;
+                }
+                let code =
+                    line.strip_prefix('\t').unwrap_or_else(|| &line[4..]);
+                push(&mut blocks, code);
+            }
+            Line::Prose => indented = false,
+        }
+    }
+
+    for block in blocks.iter_mut() {
+        let len = block.code.trim_end_matches('\n').len();
+        block.code.truncate(len);
+    }
+
+    blocks
+}
+
+/// Kind of a line of Markdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line {
+    /// Text, headings, lists...
+    Prose,
+    /// Opening or closing code fence
+    Fence,
+    /// Line inside a fenced code block
+    Code,
+    /// Line of an indented code block, including its blank lines
+    Indented,
+}
+
+/// Splits a Markdown document into lines, telling apart prose and code
+fn markdown_lines(markdown: &str) -> Vec<(&str, Line)> {
+    let mut lines = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    let mut blank = true;
+    let mut indented_code = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+
+        if let Some((marker, len)) = fence {
+            let closes = indent < 4
+                && fence_of(trimmed)
+                    .map_or(false, |(m, l)| m == marker && l >= len);
+            if closes {
+                fence = None;
+                lines.push((line, Line::Fence));
+            } else {
+                lines.push((line, Line::Code));
+            }
+            continue;
+        }
+        if indent < 4 {
+            if let Some(opening) = fence_of(trimmed) {
+                fence = Some(opening);
+                lines.push((line, Line::Fence));
+                continue;
+            }
+        }
+
+        // indented code starts after a blank line and goes on through blank lines
+        let is_blank = trimmed.is_empty();
+        let is_indented = indent >= 4 || line.starts_with('\t');
+        indented_code = if is_blank {
+            indented_code
+        } else {
+            is_indented && (blank || indented_code)
+        };
+        blank = is_blank;
+
+        let kind = if indented_code && !is_blank {
+            Line::Indented
+        } else if indented_code {
+            Line::Code
+        } else {
+            Line::Prose
+        };
+        lines.push((line, kind));
+    }
+
+    lines
+}
+
+/// Marker and length of a code fence (three or more backticks or tildes)
+fn fence_of(line: &str) -> Option<(char, usize)> {
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == marker).count();
+    Some((marker, len)).filter(|_| len >= 3)
+}
+
+/// Appends a line of Markdown without code spans, link URLs and autolinks
+fn inline_text(line: &str, text: &mut String) {
+    let mut rest = line;
+
+    while let Some(start) = rest.find(|c| c == '`' || c == ']' || c == '<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let skipped = if rest.starts_with('`') {
+            let ticks = rest.chars().take_while(|c| *c == '`').count();
+            rest[ticks..]
+                .find(&rest[..ticks])
+                .map(|end| ticks + end + ticks)
+        } else if rest.starts_with("](") {
+            text.push(']');
+            rest[2..].find(')').map(|end| end + 3)
+        } else if rest.starts_with('<') && rest.contains("://") {
+            rest.find('>').map(|end| end + 1)
+        } else {
+            None
+        };
+
+        match skipped {
+            Some(len) => rest = &rest[len..],
+            None => {
+                let c = rest.chars().next().unwrap_or_default();
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    text.push_str(rest);
+}
+
+/// Lowercase name of the tag at the start of the text, with a leading `/` for closing
+/// tags. Empty if it is not a tag.
+fn tag_name(tag: &str) -> String {
+    let name = tag[1..]
+        .char_indices()
+        .take_while(|(i, c)| {
+            c.is_ascii_alphanumeric() || (*i == 0 && *c == '/')
+        })
+        .map(|(_, c)| c.to_ascii_lowercase())
+        .collect::<String>();
+
+    if name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || name.len() > 1 && name.starts_with('/')
+    {
+        name
+    } else {
+        String::new()
+    }
+}
+
+/// Returns true if an element is in a list of elements
+fn is_one_of(element: &str, elements: &[&str]) -> bool {
+    elements.iter().any(|e| *e == element)
+}
+
+/// Skips the content of an element, up to its closing tag
+fn skip_element<'a>(html: &'a str, element: &str) -> &'a str {
+    let mut rest = html;
+    while let Some(start) = rest.find("</") {
+        rest = &rest[start..];
+        if tag_name(rest) == ["/", element].concat() {
+            return rest.find('>').map_or("", |end| &rest[end + 1..]);
+        }
+        rest = &rest[2..];
+    }
+
+    ""
+}
+
+/// Decodes the character reference at the start of the text, with its length
+fn decode_reference(text: &str) -> Option<(char, usize)> {
+    let (end, _) = text.char_indices().take(12).find(|(_, c)| *c == ';')?;
+    let name = &text[1..end];
+
+    let decoded = if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(|c| c == 'x' || c == 'X') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        char::from_u32(code)?
+    } else {
+        match name {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            "nbsp" => ' ',
+            "aacute" => 'á',
+            "eacute" => 'é',
+            "iacute" => 'í',
+            "oacute" => 'ó',
+            "uacute" => 'ú',
+            "ntilde" => 'ñ',
+            "uuml" => 'ü',
+            "ouml" => 'ö',
+            "auml" => 'ä',
+            "ccedil" => 'ç',
+            "egrave" => 'è',
+            "agrave" => 'à',
+            "szlig" => 'ß',
+            _ => return None,
+        }
+    };
+
+    Some((decoded, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html() {
+        let page = "<html><head><title>Hola</title>\
+            <style>body { color: red }</style></head>\
+            <body><!-- menu --><p>La <b>casa</b> es grande &amp; bonita.</p>\
+            <script>if (a < b) { alert('</p>') }</script>\
+            <P>Canci&oacute;n &#241; &#xE9;</P> a < b &copy;</body></html>";
+        let text = html_text(page);
+        let words = text.split_whitespace().collect::<alloc::vec::Vec<_>>();
+
+        assert_eq!(
+            "Hola La casa es grande & bonita. Canción ñ é a < b &copy;",
+            words.join(" ")
+        );
+        assert!(text.contains("Hola\n"));
+        assert!(text.contains("La casa"));
+    }
+
+    #[test]
+    fn markdown() {
+        let readme = "# Título\n\
+            \n\
+            Texto con `código` y un [enlace](https://example.com).\n\
+            \n\
+            ```rust\n\
+            fn main() {}\n\
+            ```\n\
+            \n\
+            \x20   indented code\n\
+            \n\
+            Más texto <https://example.com>.\n\
+            ~~~\n\
+            ``` not a closing fence\n\
+            ~~~\n";
+
+        assert_eq!(
+            "# Título\n\nTexto con  y un [enlace].\n\n\nMás texto .\n",
+            markdown_text(readme)
+        );
+
+        let blocks = code_blocks(readme);
+        assert_eq!(3, blocks.len());
+        assert_eq!(
+            ("rust", "fn main() {}"),
+            (blocks[0].info, &*blocks[0].code)
+        );
+        assert_eq!(("", "indented code"), (blocks[1].info, &*blocks[1].code));
+        assert_eq!("``` not a closing fence", blocks[2].code);
+    }
+}
@@ -0,0 +1,91 @@
+//! # Mobile
+//!
+//! Swift and Kotlin bindings generated with UniFFI (`uniffi` feature). Build the library as
+//! a `cdylib` and generate the foreign code with:
+//!
+//! `cargo run --features uniffi --bin uniffi-bindgen generate --library <lib> --language swift`
+use crate::category::{from_bytes, load, Categories};
+use std::fmt;
+use std::sync::Arc;
+
+/// Errors reported to the foreign code
+#[derive(Debug, uniffi::Error)]
+pub enum TextCatError {
+    /// The model could not be read or is not valid
+    InvalidModel {
+        /// Description of the problem
+        message: String,
+    },
+}
+
+impl fmt::Display for TextCatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextCatError::InvalidModel { message } => {
+                write!(f, "invalid model: {}", message)
+            }
+        }
+    }
+}
+
+impl From<crate::Error> for TextCatError {
+    fn from(e: crate::Error) -> Self {
+        TextCatError::InvalidModel {
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Candidate category and its distance (the lower the better)
+#[derive(uniffi::Record)]
+pub struct Candidate {
+    /// Category name
+    pub category: String,
+    /// Distance to the text
+    pub distance: u64,
+}
+
+/// Text classifier
+#[derive(uniffi::Object)]
+pub struct TextCat {
+    categories: Categories<String>,
+}
+
+#[uniffi::export]
+impl TextCat {
+    /// Creates a classifier from the content of a model file
+    #[uniffi::constructor]
+    pub fn from_bytes(model: Vec<u8>) -> Result<Arc<Self>, TextCatError> {
+        Ok(Arc::new(TextCat {
+            categories: from_bytes(&model)?,
+        }))
+    }
+
+    /// Creates a classifier from a model file
+    #[uniffi::constructor]
+    pub fn load(path: String) -> Result<Arc<Self>, TextCatError> {
+        Ok(Arc::new(TextCat {
+            categories: load(&path)?,
+        }))
+    }
+
+    /// Returns the category of a text, or nil/null if it is unknown or ambiguous
+    pub fn detect(&self, text: String) -> Option<String> {
+        self.categories.get_category(&text)
+    }
+
+    /// Returns the candidate categories of a text, best first
+    pub fn candidates(&self, text: String) -> Vec<Candidate> {
+        self.categories
+            .get_categories(&text)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(category, distance)| Candidate { category, distance })
+            .collect()
+    }
+
+    /// Returns the names of all the categories in the model
+    pub fn categories(&self) -> Vec<String> {
+        self.categories.categories()
+    }
+}
@@ -1,21 +1,375 @@
 //! # NGram
 //!
 //! NGram module. This module is responsible for parsing and sorting ngrams from texts.
+use crate::collections::{HashMap, HashSet};
+use crate::extract;
+use crate::normalize::{Normalizer, Pipeline};
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::iter::FromIterator;
+use core::ops::Range;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::cmp::min;
-use std::collections::HashMap;
-use std::iter::FromIterator;
+use smol_str::format_smolstr;
+pub use smol_str::SmolStr;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Removes the stopwords from a text. Words are compared lowercased, the stopwords are
+/// expected to be lowercase.
+pub fn remove_stopwords<'a>(
+    text: &'a str,
+    stopwords: &HashSet<String>,
+) -> Cow<'a, str> {
+    if stopwords.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(
+        text.split_word_bounds()
+            .filter(|word| !stopwords.contains(&word.to_lowercase()))
+            .collect(),
+    )
+}
+
+/// Removes accents and other diacritics ("canción" becomes "cancion"), so texts typed
+/// without them still match.
+pub fn fold_diacritics(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(text.nfd().filter(|c| !is_combining_mark(*c)).collect())
+}
+
+/// Splits a text into words
+#[cfg(not(feature = "segmentation"))]
+fn words(text: &str) -> Vec<&str> {
+    text.unicode_words().collect()
+}
+
+/// Splits a text into words. Scripts written without spaces between words (Thai, Lao and
+/// Khmer) are split with a dictionary-based segmenter.
+#[cfg(feature = "segmentation")]
+fn words(text: &str) -> Vec<&str> {
+    use icu_segmenter::{options::WordBreakInvariantOptions, WordSegmenter};
+
+    let needs_dictionary = |c: char| {
+        matches!(c, '\u{0E00}'..='\u{0EFF}' // Thai and Lao
+            | '\u{1780}'..='\u{17FF}' // Khmer
+            | '\u{19E0}'..='\u{19FF}') // Khmer symbols
+    };
+
+    if !text.chars().any(needs_dictionary) {
+        return text.unicode_words().collect();
+    }
+
+    let segmenter =
+        WordSegmenter::new_dictionary(WordBreakInvariantOptions::default());
+    let mut start = 0;
+
+    segmenter
+        .segment_str(text)
+        .iter_with_word_type()
+        .filter_map(|(end, word_type)| {
+            let word = &text[start..end];
+            start = end;
+            Some(word).filter(|_| word_type.is_word_like())
+        })
+        .collect()
+}
+
+/// Longest ngram which fits in the packed keys of the ASCII fast path
+const MAX_PACKED_LEN: usize = 8;
+
+/// How ngrams touching a word boundary (those containing the `_` word separator, i.e.
+/// word prefixes and suffixes) are weighted against the ngrams inside words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundaries {
+    /// Every ngram counts the same
+    All,
+    /// The count of boundary ngrams is multiplied by the given factor
+    Emphasize(u64),
+    /// Only boundary ngrams are kept
+    Only,
+}
+
+impl Default for Boundaries {
+    fn default() -> Self {
+        Boundaries::All
+    }
+}
+
+impl Boundaries {
+    /// Returns true for the default mode
+    pub fn is_all(&self) -> bool {
+        *self == Boundaries::All
+    }
+
+    /// Applies the mode to a set of ngram counts
+    pub fn apply(&self, counts: &mut HashMap<SmolStr, u64>) {
+        match self {
+            Boundaries::All => {}
+            Boundaries::Emphasize(factor) => counts
+                .iter_mut()
+                .filter(|(ngram, _)| ngram.contains('_'))
+                .for_each(|(_, count)| *count = count.saturating_mul(*factor)),
+            Boundaries::Only => counts.retain(|ngram, _| ngram.contains('_')),
+        }
+    }
+}
+
+/// Which ngrams are extracted from texts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMode {
+    /// Character ngrams, the best fit to detect languages
+    Char,
+    /// Whole words and pairs of consecutive words, the best fit to detect topics
+    Word,
+    /// Both character and word ngrams
+    Mixed,
+}
+
+impl Default for TokenMode {
+    fn default() -> Self {
+        TokenMode::Char
+    }
+}
+
+impl TokenMode {
+    /// Returns true for the default mode
+    pub fn is_char(&self) -> bool {
+        *self == TokenMode::Char
+    }
+
+    fn chars(&self) -> bool {
+        *self != TokenMode::Word
+    }
+
+    fn words(&self) -> bool {
+        *self != TokenMode::Char
+    }
+}
+
+/// Longest text `NgramsBuilder` keeps waiting for a whitespace, in bytes
+const MAX_PENDING: usize = 64 * 1024;
+
+/// Default out-of-place distance of an ngram missing from the text (see `Ngrams::distance`)
+pub const MISSING_PENALTY: u64 = 5000;
+
+/// Scale applied to the divergence metrics, which are fractions, so every metric returns
+/// comparable integer distances.
+const DIVERGENCE_SCALE: f64 = 1_000_000.0;
+
+/// Float functions of `std`, taken from `libm` without it
+mod float {
+    #[cfg(feature = "std")]
+    pub fn ln(x: f64) -> f64 {
+        x.ln()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn ln(x: f64) -> f64 {
+        libm::log(x)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn sqrt(x: f64) -> f64 {
+        x.sqrt()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn sqrt(x: f64) -> f64 {
+        libm::sqrt(x)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn round(x: f64) -> f64 {
+        x.round()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn round(x: f64) -> f64 {
+        libm::round(x)
+    }
+}
+
+/// Scales a fractional distance to the integer distances used everywhere
+fn scaled(divergence: f64) -> u64 {
+    float::round(divergence * DIVERGENCE_SCALE) as u64
+}
+
+/// Distance between the profile of a category and the profile of a text. Lower is closer.
+pub trait Distance {
+    /// Measures the distance between a category profile and the profile of a text
+    fn distance(&self, category: &Ngrams, sample: &Ngrams) -> u64;
+
+    /// Largest distance a text can have to the given category profile, reached when they
+    /// have no ngram in common.
+    fn max_distance(&self, category: &Ngrams) -> u64;
+}
+
+/// Sum of the positions of the category ngrams in the text, ngrams missing from the text
+/// count as `missing_penalty` (see `Ngrams::distance_with_penalty`)
+#[derive(Debug, Clone, Copy)]
+pub struct OutOfPlace {
+    /// Distance of an ngram missing from the text
+    pub missing_penalty: u64,
+}
+
+impl Default for OutOfPlace {
+    fn default() -> Self {
+        OutOfPlace {
+            missing_penalty: MISSING_PENALTY,
+        }
+    }
+}
+
+impl Distance for OutOfPlace {
+    fn distance(&self, category: &Ngrams, sample: &Ngrams) -> u64 {
+        category.distance_with_penalty(sample, self.missing_penalty)
+    }
+
+    fn max_distance(&self, category: &Ngrams) -> u64 {
+        category.len() as u64 * self.missing_penalty
+    }
+}
+
+/// Jensen–Shannon divergence of the ngram frequencies, scaled by a million
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JensenShannon;
+
+impl Distance for JensenShannon {
+    fn distance(&self, category: &Ngrams, sample: &Ngrams) -> u64 {
+        scaled(category.jensen_shannon(sample))
+    }
+
+    fn max_distance(&self, _category: &Ngrams) -> u64 {
+        scaled(float::ln(2.0))
+    }
+}
+
+/// Chi-square distance of the ngram frequencies, scaled by a million
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChiSquare;
+
+impl Distance for ChiSquare {
+    fn distance(&self, category: &Ngrams, sample: &Ngrams) -> u64 {
+        scaled(category.chi_square(sample))
+    }
+
+    fn max_distance(&self, _category: &Ngrams) -> u64 {
+        scaled(2.0)
+    }
+}
+
+/// One minus the cosine similarity of the ngram frequency vectors, scaled by a million
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CosineSimilarity;
+
+impl Distance for CosineSimilarity {
+    fn distance(&self, category: &Ngrams, sample: &Ngrams) -> u64 {
+        scaled(1.0 - category.cosine_similarity(sample))
+    }
+
+    fn max_distance(&self, _category: &Ngrams) -> u64 {
+        scaled(1.0)
+    }
+}
+
+/// Distance measure of a model, stored with it so texts are classified with the same
+/// measure the model was built for. Lower is closer for every metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// See `OutOfPlace`
+    OutOfPlace,
+    /// See `JensenShannon`
+    JensenShannon,
+    /// See `ChiSquare`
+    ChiSquare,
+    /// See `CosineSimilarity`
+    Cosine,
+}
+
+impl Default for Metric {
+    fn default() -> Self {
+        Metric::OutOfPlace
+    }
+}
+
+impl Metric {
+    /// Returns true for the default metric
+    pub fn is_out_of_place(&self) -> bool {
+        *self == Metric::OutOfPlace
+    }
+
+    /// Same as `distance`, with the given distance for ngrams missing from the text when
+    /// the metric is out-of-place. The other metrics do not have such a penalty.
+    pub fn distance_with_penalty(
+        &self,
+        category: &Ngrams,
+        sample: &Ngrams,
+        missing_penalty: u64,
+    ) -> u64 {
+        match self {
+            Metric::OutOfPlace => {
+                OutOfPlace { missing_penalty }.distance(category, sample)
+            }
+            metric => metric.distance(category, sample),
+        }
+    }
+
+    /// Same as `max_distance`, see `distance_with_penalty`
+    pub fn max_distance_with_penalty(
+        &self,
+        category: &Ngrams,
+        missing_penalty: u64,
+    ) -> u64 {
+        match self {
+            Metric::OutOfPlace => {
+                OutOfPlace { missing_penalty }.max_distance(category)
+            }
+            metric => metric.max_distance(category),
+        }
+    }
+
+    /// Implementation of the metric
+    fn measure(&self) -> &'static dyn Distance {
+        match self {
+            Metric::OutOfPlace => &OutOfPlace {
+                missing_penalty: MISSING_PENALTY,
+            },
+            Metric::JensenShannon => &JensenShannon,
+            Metric::ChiSquare => &ChiSquare,
+            Metric::Cosine => &CosineSimilarity,
+        }
+    }
+}
+
+impl Distance for Metric {
+    fn distance(&self, category: &Ngrams, sample: &Ngrams) -> u64 {
+        self.measure().distance(category, sample)
+    }
+
+    fn max_distance(&self, category: &Ngrams) -> u64 {
+        self.measure().max_distance(category)
+    }
+}
+
 /// Ngram structure
 ///
-/// An ngram is a tuple the ngram (string) and its score
+/// An ngram is a tuple the ngram (string) and its score. Ngrams of up to 23 bytes, almost
+/// all of them, are stored inline without allocating (see `SmolStr`).
 #[derive(Debug, Clone)]
-pub struct Ngram((String, u64));
+pub struct Ngram((SmolStr, u64));
 
 impl Ngram {
     /// Returns a reference to the ngram
-    pub fn ngram(&self) -> &String {
+    pub fn ngram(&self) -> &str {
         &self.0 .0
     }
 
@@ -34,13 +388,26 @@ impl Serialize for Ngram {
     }
 }
 
+/// Serialized forms of an ngram: only the ngram, or an [ngram, count] pair (see
+/// `Ngrams::serialize_counts`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedNgram {
+    Ngram(SmolStr),
+    WithCount(SmolStr, u64),
+}
+
 impl<'de> Deserialize<'de> for Ngram {
     fn deserialize<D>(deserializer: D) -> Result<Ngram, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let str = Deserialize::deserialize(deserializer)?;
-        Ok(Ngram((str, 0)))
+        Ok(match Deserialize::deserialize(deserializer)? {
+            SerializedNgram::Ngram(str) => Ngram((str.into(), 0)),
+            SerializedNgram::WithCount(str, count) => {
+                Ngram((str.into(), count))
+            }
+        })
     }
 }
 
@@ -48,14 +415,17 @@ impl<'de> Deserialize<'de> for Ngram {
 #[derive(Debug, Clone)]
 pub struct Ngrams {
     ngrams: Vec<Ngram>,
-    index: HashMap<String, usize>,
+    /// Position of every ngram, None once dropped (see `drop_index`)
+    index: Option<HashMap<SmolStr, usize>>,
+    /// Whether the counts are serialized along the ngrams
+    with_counts: bool,
 }
 
 impl From<Vec<&str>> for Ngrams {
     fn from(value: Vec<&str>) -> Self {
         value
             .iter()
-            .map(|w| Ngram((w.to_string(), 0)))
+            .map(|w| Ngram(((*w).into(), 0)))
             .collect::<Vec<Ngram>>()
             .into()
     }
@@ -66,10 +436,14 @@ impl From<Vec<Ngram>> for Ngrams {
         let mut index = HashMap::new();
 
         for (pos, ngram) in ngrams.iter().enumerate() {
-            index.entry(ngram.ngram().clone()).or_insert(pos);
+            index.entry(ngram.0 .0.clone()).or_insert(pos);
         }
 
-        Ngrams { ngrams, index }
+        Ngrams {
+            ngrams,
+            index: Some(index),
+            with_counts: false,
+        }
     }
 }
 
@@ -79,7 +453,9 @@ impl<'de> Deserialize<'de> for Ngrams {
         D: Deserializer<'de>,
     {
         let ngrams: Vec<Ngram> = Deserialize::deserialize(deserializer)?;
-        Ok(ngrams.into())
+        let mut ngrams = Ngrams::from(ngrams);
+        ngrams.with_counts = ngrams.has_counts();
+        Ok(ngrams)
     }
 }
 
@@ -88,8 +464,12 @@ impl Serialize for Ngrams {
     where
         S: Serializer,
     {
-        let l = min(400, self.ngrams.len());
-        self.ngrams[0..l].serialize(serializer)
+        if self.with_counts {
+            serializer
+                .collect_seq(self.ngrams.iter().map(|n| (n.ngram(), n.score())))
+        } else {
+            self.ngrams.serialize(serializer)
+        }
     }
 }
 
@@ -97,25 +477,187 @@ impl Ngrams {
     /// Creates a new Ngrams structure from a given text
     /// (the ngrams length are from 2 ... length).
     pub fn new(text: &str, length: u8) -> Ngrams {
-        let mut ngrams = Ngrams::parse_text(text, length as usize)
+        Self::from_counts(Ngrams::parse_text(text, length as usize))
+    }
+
+    /// Same as `new`, weighting the ngrams which touch word boundaries as requested.
+    pub fn new_with_boundaries(
+        text: &str,
+        length: u8,
+        boundaries: Boundaries,
+    ) -> Ngrams {
+        let mut counts = Ngrams::parse_text(text, length as usize);
+        boundaries.apply(&mut counts);
+        Self::from_counts(counts)
+    }
+
+    /// Same as `new`, extracting the ngrams of the given mode. Word ngrams are the
+    /// lowercased words and pairs of consecutive words, each word preceded by a space and
+    /// the ngram ended by one (" the ", " the house "), so they never clash with
+    /// character ngrams.
+    pub fn new_with_mode(text: &str, length: u8, mode: TokenMode) -> Ngrams {
+        Self::new_with_options(
+            text,
+            1..length as usize,
+            Boundaries::All,
+            mode,
+            &HashSet::new(),
+        )
+    }
+
+    /// Same as `new`, with every option: the `lengths` of the character ngrams, to which
+    /// the boundaries apply, the token mode and the words left out of the text (see
+    /// `remove_stopwords`).
+    pub fn new_with_options(
+        text: &str,
+        lengths: Range<usize>,
+        boundaries: Boundaries,
+        mode: TokenMode,
+        stopwords: &HashSet<String>,
+    ) -> Ngrams {
+        let text = remove_stopwords(text, stopwords);
+        let mut counts = if mode.chars() {
+            Ngrams::parse_lengths(&text, lengths)
+        } else {
+            HashMap::new()
+        };
+        boundaries.apply(&mut counts);
+
+        if mode.words() {
+            Self::count_words(&text.to_lowercase(), None, &mut counts);
+        }
+
+        Self::from_counts(counts)
+    }
+
+    /// Creates a new Ngrams structure from precomputed ngram counts. The ngrams are ranked by
+    /// their count, ties are broken by comparing the ngrams bytewise in descending order.
+    /// The ranking is a total order, it doesn't depend on the iteration order of `counts`,
+    /// so the same text always yields the same profile.
+    pub fn from_counts<S>(counts: HashMap<S, u64>) -> Ngrams
+    where
+        S: Into<SmolStr>,
+    {
+        let mut ngrams = counts
             .into_iter()
-            .map(Ngram)
+            .map(|(ngram, score)| Ngram((ngram.into(), score)))
             .collect::<Vec<Ngram>>();
 
-        ngrams.sort_by(|a, b| {
-            if a.score() == b.score() {
-                b.ngram().cmp(&a.ngram())
-            } else {
-                b.score().cmp(&a.score())
-            }
+        ngrams.sort_unstable_by(|a, b| {
+            extract::by_rank((a.ngram(), a.score()), (b.ngram(), b.score()))
         });
 
         ngrams.into()
     }
 
+    /// Creates a new Ngrams structure from a list of (ngram, count) which is already sorted by
+    /// rank. The order is kept as is.
+    pub fn from_sorted(ngrams: Vec<(String, u64)>) -> Ngrams {
+        ngrams
+            .into_iter()
+            .map(|(ngram, score)| Ngram((ngram.into(), score)))
+            .collect::<Vec<Ngram>>()
+            .into()
+    }
+
+    /// Creates a new Ngrams structure from already tokenized text. The tokens take the place of
+    /// the words found by the built-in tokenizer: they are joined with `_` and the ngrams with
+    /// a length within `range` are extracted. Tokens are used as given (no lowercasing).
+    pub fn from_tokens<'a>(
+        tokens: impl Iterator<Item = &'a str>,
+        range: Range<usize>,
+    ) -> Ngrams {
+        let text = tokens
+            .flat_map(|token| core::iter::once('_').chain(token.chars()))
+            .collect::<String>();
+
+        let mut counts = HashMap::new();
+        Self::count_windows(&text, range, 0, &mut counts);
+        Self::from_counts(counts)
+    }
+
+    /// Returns the count of every ngram
+    pub fn counts(&self) -> HashMap<SmolStr, u64> {
+        self.ngrams
+            .iter()
+            .map(|n| (n.0 .0.clone(), n.score()))
+            .collect()
+    }
+
+    /// Returns true if the ngrams carry their counts. Ngrams loaded from a model file only
+    /// keep their rank.
+    pub fn has_counts(&self) -> bool {
+        self.ngrams.iter().any(|n| n.score() > 0)
+    }
+
+    /// Sets whether the ngrams are serialized with their counts, as [ngram, count] pairs,
+    /// instead of only the ngrams in rank order. Deserialized ngrams keep their counts when
+    /// they were stored.
+    pub fn serialize_counts(&mut self, counts: bool) {
+        self.with_counts = counts;
+    }
+
+    /// Returns a copy with counts derived from the rank (the last ngram counts 1, the one
+    /// before 2, ...), for ngrams which lost their counts.
+    pub fn with_rank_counts(&self) -> Ngrams {
+        let len = self.ngrams.len() as u64;
+        self.ngrams
+            .iter()
+            .enumerate()
+            .map(|(pos, n)| Ngram((n.0 .0.clone(), len - pos as u64)))
+            .collect::<Vec<Ngram>>()
+            .into()
+    }
+
+    /// Returns a new Ngrams structure with the counts of both, the counts of `other` being
+    /// multiplied by `weight`, ranked again.
+    pub fn merge(&self, other: &Ngrams, weight: u64) -> Ngrams {
+        let mut counts = self.counts();
+        for ngram in other.ngrams.iter() {
+            let count = counts.entry(ngram.0 .0.clone()).or_insert(0);
+            *count = count.saturating_add(ngram.score().saturating_mul(weight));
+        }
+
+        Self::from_counts(counts)
+    }
+
     /// Returns a vector of strings of ngrams sorted by the rank
     pub fn to_vec(&self) -> Vec<&str> {
-        self.ngrams.iter().map(|w| w.0 .0.as_str()).collect()
+        self.ngrams.iter().map(|w| w.ngram()).collect()
+    }
+
+    /// Keeps only the `len` best ranked ngrams
+    pub fn truncate(&mut self, len: usize) {
+        self.ngrams.truncate(len);
+        if let Some(index) = self.index.as_mut() {
+            index.retain(|_, pos| *pos < len);
+        }
+    }
+
+    /// Keeps only the ngrams for which the predicate returns true. The remaining ngrams
+    /// keep their relative order, so they move up in the ranking.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        self.ngrams.retain(|ngram| f(ngram.ngram()));
+        if self.index.is_some() {
+            self.index = Some(
+                self.ngrams
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(pos, ngram)| (ngram.0 .0.clone(), pos))
+                    .collect(),
+            );
+        }
+    }
+
+    /// Frees the table behind `position` and `ngram`, which then scan the ngrams. For
+    /// profiles looked up by other means, like the category profiles of a model, which
+    /// are looked up by the ids of their ngrams (see `Category::intern`).
+    pub(crate) fn drop_index(&mut self) {
+        self.index = None;
     }
 
     /// Splits the texts from ngrams, from start to end length. NGrams are in their own
@@ -125,65 +667,143 @@ impl Ngrams {
         start: usize,
         end: usize,
     ) -> Vec<Vec<String>> {
-        let text: Vec<char> = text
-            .to_lowercase()
-            .unicode_words()
-            .fold(String::new(), |a, b| a + "_" + b)
-            .chars()
-            .collect::<Vec<_>>();
+        let mut groups = vec![Vec::new(); end.saturating_sub(start)];
+        extract::for_each_window(
+            &Self::normalize(text),
+            start..end,
+            0,
+            |len, ngram| groups[len - start].push(ngram.to_string()),
+        );
 
-        let mut ngrams_set = Vec::new();
+        groups
+    }
 
-        let text_length = text.len();
+    /// Splits a given text into ngrams, grouped by length (shortest first) as
+    /// `split_and_group_by_ngrams` returns them, each group in the order of the text
+    pub fn split(text: &str, start: usize, end: usize) -> Vec<String> {
+        let mut ngrams = Vec::new();
+        extract::for_each_window(
+            &Self::normalize(text),
+            start..end,
+            0,
+            |len, ngram| ngrams.push((len, ngram.to_string())),
+        );
 
-        for len in start..end {
-            let mut ngrams = Vec::new();
+        // the sort is stable, the ngrams of each length keep their order
+        ngrams.sort_by_key(|(len, _)| *len);
+        ngrams.into_iter().map(|(_, ngram)| ngram).collect()
+    }
 
-            for i in 0..text_length {
-                if i + len > text_length {
-                    break;
-                }
+    /// Creates a HashMap of ngram -> count
+    ///
+    /// The text is normalized once and every ngram is a slice of it, only copied (inline,
+    /// without allocating, see `Ngram`) for each distinct ngram. Pure ASCII texts take a
+    /// faster path, with ngrams packed into integers.
+    pub fn parse_text(text: &str, length: usize) -> HashMap<SmolStr, u64> {
+        Self::parse_lengths(text, 1..length)
+    }
+
+    /// Same as `parse_text`, for the ngrams with a length (in chars) within `lengths`
+    pub fn parse_lengths(
+        text: &str,
+        lengths: Range<usize>,
+    ) -> HashMap<SmolStr, u64> {
+        if text.is_ascii() && lengths.end <= MAX_PACKED_LEN + 1 {
+            return Self::parse_ascii(text, lengths);
+        }
+
+        Self::parse_unicode(text, lengths)
+    }
+
+    /// ASCII specialization of `parse_text`. The normalized text is written once into a scratch
+    /// buffer and every ngram is packed into an u64 key, the ngrams are only created for the
+    /// distinct ones at the very end.
+    fn parse_ascii(text: &str, lengths: Range<usize>) -> HashMap<SmolStr, u64> {
+        let mut buf: Vec<u8> = Vec::with_capacity(text.len() + 1);
+        for word in text.unicode_words() {
+            buf.push(b'_');
+            buf.extend(word.bytes().map(|b| b.to_ascii_lowercase()));
+        }
 
+        let mut counts: HashMap<u64, u64> = HashMap::new();
+        for len in lengths.filter(|len| *len > 0) {
+            for window in buf.windows(len) {
                 if len == 1
-                    && (text[i].is_numeric() || text[i].is_ascii_punctuation())
+                    && (window[0].is_ascii_digit()
+                        || window[0].is_ascii_punctuation())
                 {
                     continue;
                 }
-                let ngram = String::from_iter(&text[i..i + len]);
-                if ngram.is_empty() {
-                    break;
-                }
 
-                ngrams.push(ngram);
+                let key = window.iter().fold(0_u64, |k, b| k << 8 | *b as u64);
+                *counts.entry(key).or_insert(0) += 1;
             }
-
-            ngrams_set.push(ngrams);
         }
 
-        ngrams_set
-    }
-
-    /// Splits a given text into ngrams
-    pub fn split(text: &str, start: usize, end: usize) -> Vec<String> {
-        Self::split_and_group_by_ngrams(text, start, end)
+        counts
             .into_iter()
-            .flatten()
+            .map(|(key, count)| {
+                let ngram = key
+                    .to_be_bytes()
+                    .iter()
+                    .skip_while(|b| **b == 0)
+                    .map(|b| *b as char)
+                    .collect::<SmolStr>();
+                (ngram, count)
+            })
             .collect()
     }
 
-    /// Creates a HashMap of ngram -> count
-    pub fn parse_text(text: &str, length: usize) -> HashMap<String, u64> {
-        let mut ngrams: HashMap<String, u64> = HashMap::new();
+    /// General (Unicode) implementation of `parse_text`
+    fn parse_unicode(
+        text: &str,
+        lengths: Range<usize>,
+    ) -> HashMap<SmolStr, u64> {
+        let mut ngrams: HashMap<SmolStr, u64> = HashMap::new();
+        Self::count_windows(&Self::normalize(text), lengths, 0, &mut ngrams);
+        ngrams
+    }
 
-        Self::split(&text, 1, length)
-            .iter()
-            .map(|ngram| {
-                let count = ngrams.entry((&ngram).to_string()).or_insert(0);
-                *count += 1;
-            })
-            .for_each(drop);
+    /// Lowercases the text and joins its words with `_`, the form ngrams are extracted from.
+    fn normalize(text: &str) -> String {
+        extract::join_words(words(&text.to_lowercase()))
+    }
 
-        ngrams
+    /// Counts the word ngrams (see `new_with_mode`) of a lowercased text. `previous` is the
+    /// last word before the text, if any, and the last word of the text is returned.
+    fn count_words(
+        text: &str,
+        previous: Option<String>,
+        ngrams: &mut HashMap<SmolStr, u64>,
+    ) -> Option<String> {
+        words(text).into_iter().fold(previous, |previous, word| {
+            *ngrams.entry(format_smolstr!(" {} ", word)).or_insert(0) += 1;
+            if let Some(previous) = previous {
+                *ngrams
+                    .entry(format_smolstr!(" {} {} ", previous, word))
+                    .or_insert(0) += 1;
+            }
+            Some(word.to_string())
+        })
+    }
+
+    /// Counts the ngrams (of `lengths`) of a normalized text. Only ngrams ending after the
+    /// `from` offset (in chars) are counted, the chars before it are context carried from a
+    /// previous call. Ngrams are slices of the text, only new ngrams are allocated.
+    fn count_windows(
+        text: &str,
+        lengths: Range<usize>,
+        from: usize,
+        ngrams: &mut HashMap<SmolStr, u64>,
+    ) {
+        extract::for_each_window(text, lengths, from, |_, window| match ngrams
+            .get_mut(window)
+        {
+            Some(count) => *count += 1,
+            None => {
+                ngrams.insert(window.into(), 1);
+            }
+        });
     }
 
     /// Very simple distance algorithm know as Out of place[1]
@@ -193,12 +813,138 @@ impl Ngrams {
     ///
     /// [1] https://www.researchgate.net/figure/Out-of-Place-Measure-Computation-adapted-from-Cavnar-and-Trenkle-1994_fig2_220746484
     pub fn distance(&self, another: &Ngrams) -> u64 {
+        self.distance_with_penalty(another, MISSING_PENALTY)
+    }
+
+    /// Same as `distance`, with the given distance for ngrams missing from the other set
+    /// instead of `MISSING_PENALTY`. A penalty close to the profile length keeps missing
+    /// ngrams comparable to misplaced ones.
+    pub fn distance_with_penalty(
+        &self,
+        another: &Ngrams,
+        missing_penalty: u64,
+    ) -> u64 {
         self.ngrams
             .iter()
-            .map(|n| another.position(n.ngram()).map_or(5000_u64, |v| v as u64))
+            .map(|n| {
+                another
+                    .position(n.ngram())
+                    .map_or(missing_penalty, |v| v as u64)
+            })
             .sum()
     }
 
+    /// Same as `distance`, but gives up as soon as the running sum exceeds `best_so_far`
+    /// and returns None. Categories which can't beat the best candidate are discarded
+    /// without summing their whole profile.
+    pub fn distance_with_cutoff(
+        &self,
+        another: &Ngrams,
+        best_so_far: u64,
+    ) -> Option<u64> {
+        self.distance_with_penalty_and_cutoff(
+            another,
+            MISSING_PENALTY,
+            best_so_far,
+        )
+    }
+
+    /// Same as `distance_with_cutoff`, with the given distance for missing ngrams (see
+    /// `distance_with_penalty`)
+    pub fn distance_with_penalty_and_cutoff(
+        &self,
+        another: &Ngrams,
+        missing_penalty: u64,
+        best_so_far: u64,
+    ) -> Option<u64> {
+        let mut distance = 0u64;
+        for n in self.ngrams.iter() {
+            distance += another
+                .position(n.ngram())
+                .map_or(missing_penalty, |v| v as u64);
+            if distance > best_so_far {
+                return None;
+            }
+        }
+
+        Some(distance)
+    }
+
+    /// Relative frequency of each ngram. Ngrams loaded from a model file, which only keep
+    /// their rank, use counts derived from it (see `with_rank_counts`).
+    fn frequencies(&self) -> HashMap<&str, f64> {
+        let len = self.ngrams.len() as u64;
+        let ranked = !self.has_counts();
+        let counts = self.ngrams.iter().enumerate().map(|(pos, n)| {
+            let count = if ranked { len - pos as u64 } else { n.score() };
+            (n.ngram(), count as f64)
+        });
+        let total = counts.clone().map(|(_, count)| count).sum::<f64>();
+
+        counts
+            .filter(|(_, count)| *count > 0.0)
+            .map(|(ngram, count)| (ngram, count / total))
+            .collect()
+    }
+
+    /// Calls `f` with the frequency of every ngram found in either set (0 when missing)
+    fn fold_frequencies<F>(&self, another: &Ngrams, f: F) -> f64
+    where
+        F: Fn(f64, f64) -> f64,
+    {
+        let p = self.frequencies();
+        let q = another.frequencies();
+
+        p.iter()
+            .map(|(ngram, p)| f(*p, q.get(ngram).copied().unwrap_or(0.0)))
+            .chain(
+                q.iter()
+                    .filter(|(ngram, _)| !p.contains_key(*ngram))
+                    .map(|(_, q)| f(0.0, *q)),
+            )
+            .sum()
+    }
+
+    /// Jensen–Shannon divergence between the ngram frequencies of both sets, from 0
+    /// (identical) to ln(2) (nothing in common).
+    pub fn jensen_shannon(&self, another: &Ngrams) -> f64 {
+        let kl =
+            |p: f64, m: f64| if p > 0.0 { p * float::ln(p / m) } else { 0.0 };
+
+        self.fold_frequencies(another, |p, q| {
+            let m = (p + q) / 2.0;
+            (kl(p, m) + kl(q, m)) / 2.0
+        })
+    }
+
+    /// Symmetric chi-square distance between the ngram frequencies of both sets, from 0
+    /// (identical) to 2 (nothing in common).
+    pub fn chi_square(&self, another: &Ngrams) -> f64 {
+        self.fold_frequencies(another, |p, q| (p - q) * (p - q) / (p + q))
+    }
+
+    /// Cosine similarity between the ngram frequencies of both sets, from 0 (nothing in
+    /// common) to 1 (same frequencies).
+    pub fn cosine_similarity(&self, another: &Ngrams) -> f64 {
+        let p = self.frequencies();
+        let q = another.frequencies();
+        let norm = |v: &HashMap<&str, f64>| {
+            float::sqrt(v.values().map(|x| x * x).sum::<f64>())
+        };
+
+        let dot = p
+            .iter()
+            .filter_map(|(ngram, p)| q.get(ngram).map(|q| p * q))
+            .sum::<f64>();
+        let norms = norm(&p) * norm(&q);
+
+        if norms > 0.0 {
+            (dot / norms).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
     /// Gets an ngram by their position
     pub fn get_by_position(&self, pos: usize) -> Option<&Ngram> {
         self.ngrams.get(pos)
@@ -217,18 +963,254 @@ impl Ngrams {
     /// Returns the position in which a given Ngram is located
     /// in the ranking or None if it is not found.
     pub fn position(&self, ngram: &str) -> Option<usize> {
-        self.index.get(ngram).map(|pos| *pos)
+        match &self.index {
+            Some(index) => index.get(ngram).copied(),
+            None => self.ngrams.iter().position(|n| n.ngram() == ngram),
+        }
     }
 
     /// Search for an ngram and returns the Ngram struct or None.
-    fn ngram(&self, ngram: &str) -> Option<&Ngram> {
-        self.index.get(ngram).map(|pos| &self.ngrams[*pos])
+    pub fn ngram(&self, ngram: &str) -> Option<&Ngram> {
+        self.position(ngram).map(|pos| &self.ngrams[pos])
+    }
+}
+
+/// Incremental Ngrams construction
+///
+/// Builds an ngram profile from a text which arrives in chunks (e.g. read from a stream). Chunks
+/// can be split anywhere, even in the middle of a word, the result is the same as calling
+/// `Ngrams::new` with the whole text.
+#[derive(Debug, Clone)]
+pub struct NgramsBuilder {
+    lengths: Range<usize>,
+    counts: HashMap<SmolStr, u64>,
+    /// Text received but not processed yet (the last, maybe incomplete, word)
+    pending: String,
+    /// Last normalized chars already processed, needed for ngrams crossing chunks
+    tail: String,
+    /// Incomplete UTF-8 sequence at the end of the last chunk of bytes
+    bytes: Vec<u8>,
+    boundaries: Boundaries,
+    fold_diacritics: bool,
+    normalization: Pipeline,
+    mode: TokenMode,
+    stopwords: HashSet<String>,
+    /// Counts of the word ngrams, kept apart as boundaries do not apply to them
+    word_counts: HashMap<SmolStr, u64>,
+    /// Last word processed, needed for word pairs crossing chunks
+    last_word: Option<String>,
+}
+
+impl NgramsBuilder {
+    /// Creates a new builder, `length` has the same meaning as in `Ngrams::new`
+    pub fn new(length: u8) -> NgramsBuilder {
+        NgramsBuilder {
+            lengths: 1..length as usize,
+            counts: HashMap::new(),
+            pending: String::new(),
+            tail: String::new(),
+            bytes: Vec::new(),
+            boundaries: Boundaries::All,
+            fold_diacritics: false,
+            normalization: Pipeline::new(),
+            mode: TokenMode::Char,
+            stopwords: HashSet::new(),
+            word_counts: HashMap::new(),
+            last_word: None,
+        }
+    }
+
+    /// Sets the lengths of the character ngrams, like `Ngrams::new_with_options`
+    pub fn with_lengths(mut self, lengths: Range<usize>) -> NgramsBuilder {
+        self.lengths = lengths;
+        self
+    }
+
+    /// Sets which ngrams are extracted (see `TokenMode`)
+    pub fn with_mode(mut self, mode: TokenMode) -> NgramsBuilder {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the words left out of the text (see `remove_stopwords`)
+    pub fn with_stopwords(
+        mut self,
+        stopwords: HashSet<String>,
+    ) -> NgramsBuilder {
+        self.stopwords = stopwords;
+        self
+    }
+
+    /// Enables diacritic folding of the text (see `fold_diacritics`)
+    pub fn with_diacritic_folding(mut self, fold: bool) -> NgramsBuilder {
+        self.fold_diacritics = fold;
+        self
+    }
+
+    /// Sets the normalizers applied to the text (see `Pipeline`). They see the text a
+    /// whole number of words at a time, once a whitespace follows them, and markup they
+    /// remove (e.g. HTML tags) only once it is closed (see `Normalizer::unfinished`).
+    pub fn with_normalization(
+        mut self,
+        normalization: Pipeline,
+    ) -> NgramsBuilder {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Sets how ngrams touching word boundaries are weighted (see `Boundaries`)
+    pub fn with_boundaries(mut self, boundaries: Boundaries) -> NgramsBuilder {
+        self.boundaries = boundaries;
+        self
+    }
+
+    /// Feeds a chunk of text. Texts without whitespaces (e.g. Chinese), or with markup
+    /// which is never closed, are processed every `MAX_PENDING` bytes, as if a word ended
+    /// there.
+    pub fn feed(&mut self, chunk: &str) {
+        self.pending.push_str(chunk);
+
+        // Words are complete only once a whitespace has been seen after them, and markup
+        // only once it is closed before the cut
+        let capped = self.pending.len() > MAX_PENDING;
+        let mut end = self.pending.len();
+        loop {
+            match self.pending[..end]
+                .char_indices()
+                .rev()
+                .find(|(_, c)| c.is_whitespace())
+            {
+                Some((pos, c)) => end = pos + c.len_utf8(),
+                // ngrams crossing the cut are counted with the chars kept in `tail`
+                None if capped => {
+                    end = self.pending.len();
+                    break;
+                }
+                None => return,
+            }
+
+            let closed = self.normalization.unfinished(&self.pending[..end]);
+            if closed == end || capped {
+                break;
+            }
+            end = closed;
+        }
+        let rest = self.pending.split_off(end);
+        let ready = core::mem::replace(&mut self.pending, rest);
+        self.normalize_and_process(&ready);
+    }
+
+    /// Feeds a chunk of raw bytes. UTF-8 sequences split between chunks are reassembled,
+    /// invalid sequences are replaced with U+FFFD.
+    pub fn feed_bytes(&mut self, chunk: &[u8]) {
+        self.bytes.extend_from_slice(chunk);
+
+        let mut text = String::new();
+        let mut bytes = core::mem::take(&mut self.bytes);
+        loop {
+            match core::str::from_utf8(&bytes) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let rest = bytes.split_off(e.valid_up_to());
+                    text.push_str(&String::from_utf8_lossy(&bytes));
+                    match e.error_len() {
+                        // Incomplete sequence, wait for the next chunk
+                        None => {
+                            self.bytes = rest;
+                            break;
+                        }
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            bytes = rest[len..].to_vec();
+                        }
+                    }
+                }
+            }
+        }
+
+        self.feed(&text);
+    }
+
+    /// Returns the ngrams of the text processed so far. The last word is not included
+    /// until a whitespace or the end of the text is seen.
+    pub fn ngrams(&self) -> Ngrams {
+        let mut counts = self.counts.clone();
+        self.boundaries.apply(&mut counts);
+        counts.extend(self.word_counts.clone());
+        Ngrams::from_counts(counts)
+    }
+
+    /// Processes any pending text and returns the ngrams
+    pub fn finish(mut self) -> Ngrams {
+        if !self.bytes.is_empty() {
+            let bytes = core::mem::take(&mut self.bytes);
+            self.feed(&String::from_utf8_lossy(&bytes));
+        }
+        let pending = core::mem::take(&mut self.pending);
+        self.normalize_and_process(&pending);
+        self.boundaries.apply(&mut self.counts);
+        self.counts.extend(self.word_counts);
+        Ngrams::from_counts(self.counts)
+    }
+
+    /// Normalizes and folds complete words of the text before processing them
+    fn normalize_and_process(&mut self, text: &str) {
+        let text = self.normalization.normalize(text);
+        if self.fold_diacritics {
+            self.process(&fold_diacritics(&text));
+        } else {
+            self.process(&text);
+        }
+    }
+
+    fn process(&mut self, text: &str) {
+        let text = remove_stopwords(text, &self.stopwords);
+        let text = text.as_ref();
+
+        if self.mode.words() {
+            let last_word = self.last_word.take();
+            self.last_word = Ngrams::count_words(
+                &text.to_lowercase(),
+                last_word,
+                &mut self.word_counts,
+            );
+        }
+
+        if !self.mode.chars() {
+            return;
+        }
+
+        let mut buf = core::mem::take(&mut self.tail);
+        let from = buf.chars().count();
+        buf.push_str(&Ngrams::normalize(text));
+
+        Ngrams::count_windows(
+            &buf,
+            self.lengths.clone(),
+            from,
+            &mut self.counts,
+        );
+
+        let keep = self.lengths.end.saturating_sub(2);
+        let start = match keep {
+            0 => buf.len(),
+            keep => {
+                buf.char_indices().rev().nth(keep - 1).map_or(0, |(i, _)| i)
+            }
+        };
+        self.tail = buf.split_off(start);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ngram::Ngrams;
+    use crate::ngram::{
+        fold_diacritics, remove_stopwords, Boundaries, Distance, Metric,
+        Ngrams, NgramsBuilder, TokenMode, MAX_PENDING,
+    };
 
     #[test]
     fn length() {
@@ -289,4 +1271,433 @@ mod tests {
         assert_eq!(true, ngrams.ngram(&"this".to_string()).is_some());
         assert_eq!(Some(5), ngrams.position(&"_t".to_string()))
     }
+
+    #[test]
+    fn ascii_fast_path_matches_unicode_path() {
+        let text = "Hi there, this isn't a test. 3.14 Something_else: needs to be done!";
+
+        assert_eq!(
+            Ngrams::parse_unicode(text, 1..5),
+            Ngrams::parse_ascii(text, 1..5)
+        );
+        assert_eq!(
+            Ngrams::parse_unicode(text, 1..9),
+            Ngrams::parse_ascii(text, 1..9)
+        );
+    }
+
+    #[test]
+    fn builder_matches_whole_text() {
+        let text = "hi there, this is a test. Something else needs to be done.";
+        let mut builder = NgramsBuilder::new(5);
+        for chunk in [
+            "hi th",
+            "ere, this is",
+            " a",
+            " test. Somethi",
+            "ng else ",
+            "needs to be done.",
+        ] {
+            builder.feed(chunk);
+        }
+
+        let streamed = builder.finish();
+        let ngrams = Ngrams::new(text, 5);
+
+        assert_eq!(ngrams.to_vec(), streamed.to_vec());
+        assert_eq!(
+            ngrams.get_by_position(0).map(|n| n.score()),
+            streamed.get_by_position(0).map(|n| n.score())
+        );
+    }
+
+    #[test]
+    fn from_counts_and_sorted() {
+        let counts = vec![
+            ("b".to_string(), 3),
+            ("a".to_string(), 3),
+            ("c".to_string(), 7),
+        ];
+        let ranked = Ngrams::from_counts(counts.iter().cloned().collect());
+        let sorted = Ngrams::from_sorted(counts);
+
+        assert_eq!(vec!["c", "b", "a"], ranked.to_vec());
+        assert_eq!(vec!["b", "a", "c"], sorted.to_vec());
+        assert_eq!(Some(7), sorted.ngram("c").map(|n| n.score()));
+    }
+
+    #[test]
+    fn from_tokens() {
+        let text = "hi there, this is a test. Something else needs to be done.";
+        let tokens = text.to_lowercase();
+        let tokens = tokens
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty());
+
+        assert_eq!(
+            Ngrams::new(text, 5).to_vec(),
+            Ngrams::from_tokens(tokens, 1..5).to_vec()
+        );
+        assert_eq!(
+            vec!["b_c", "ab_", "_cd", "_ab"],
+            Ngrams::from_tokens(vec!["ab", "cd"].into_iter(), 3..4).to_vec()
+        );
+    }
+
+    #[test]
+    fn builder_without_whitespaces() {
+        let text = "中文没有空格".repeat(8 * 1024);
+        let mut builder = NgramsBuilder::new(5);
+        for chunk in text.as_bytes().chunks(3 * 1024) {
+            builder.feed(core::str::from_utf8(chunk).expect("utf-8"));
+            assert!(builder.pending.len() <= MAX_PENDING);
+        }
+
+        assert_eq!(Ngrams::new(&text, 5).to_vec(), builder.finish().to_vec());
+    }
+
+    #[test]
+    fn builder_feed_bytes() {
+        let text = "canción de cuna, ñandú";
+        let mut builder = NgramsBuilder::new(5);
+        for chunk in text.as_bytes().chunks(3) {
+            builder.feed_bytes(chunk);
+        }
+
+        assert_eq!(Ngrams::new(text, 5).to_vec(), builder.finish().to_vec());
+    }
+
+    #[test]
+    fn boundaries() {
+        let text = "hi there, this is a test.";
+        let all = Ngrams::new(text, 5);
+        let only = Ngrams::new_with_boundaries(text, 5, Boundaries::Only);
+        let emphasized =
+            Ngrams::new_with_boundaries(text, 5, Boundaries::Emphasize(3));
+
+        assert!(only.to_vec().iter().all(|n| n.contains('_')));
+        assert!(all.ngram("his").is_some() && only.ngram("his").is_none());
+        assert_eq!(
+            3 * all.ngram("_th").expect("ngram").score(),
+            emphasized.ngram("_th").expect("ngram").score()
+        );
+        assert_eq!(
+            all.ngram("his").expect("ngram").score(),
+            emphasized.ngram("his").expect("ngram").score()
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let a = Ngrams::from_sorted(vec![
+            ("a".to_string(), 5),
+            ("b".to_string(), 4),
+        ]);
+        let b = Ngrams::from_sorted(vec![
+            ("b".to_string(), 1),
+            ("c".to_string(), 2),
+        ]);
+        let merged = a.merge(&b, 3);
+
+        assert_eq!(vec!["b", "c", "a"], merged.to_vec());
+        assert_eq!(Some(7), merged.ngram("b").map(|n| n.score()));
+    }
+
+    #[test]
+    fn token_modes() {
+        let text = "The house, the HOUSE is big";
+        let words = Ngrams::new_with_mode(text, 5, TokenMode::Word);
+
+        assert_eq!(Some(2), words.ngram(" the ").map(|n| n.score()));
+        assert_eq!(Some(2), words.ngram(" the house ").map(|n| n.score()));
+        assert_eq!(Some(1), words.ngram(" house the ").map(|n| n.score()));
+        assert!(words.ngram("the").is_none());
+
+        let mixed = Ngrams::new_with_mode(text, 5, TokenMode::Mixed);
+        let chars = Ngrams::new(text, 5);
+        assert_eq!(words.len() + chars.len(), mixed.len());
+        let char_mode = Ngrams::new_with_mode(text, 5, TokenMode::Char);
+        assert_eq!(chars.to_vec(), char_mode.to_vec());
+
+        let mut builder = NgramsBuilder::new(5).with_mode(TokenMode::Mixed);
+        builder.feed("The house, the HO");
+        builder.feed("USE is big");
+        assert_eq!(mixed.to_vec(), builder.finish().to_vec());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut ngrams = Ngrams::new("hello world", 5);
+        let best = ngrams.to_vec()[..3]
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        let dropped = ngrams
+            .get_by_position(3)
+            .expect("ngram")
+            .ngram()
+            .to_string();
+        ngrams.truncate(3);
+
+        assert_eq!(best, ngrams.to_vec());
+        assert_eq!(None, ngrams.position(&dropped));
+        assert_eq!(Some(2), ngrams.position(&best[2]));
+    }
+
+    #[test]
+    fn drop_index() {
+        let indexed = Ngrams::new("hello world, hello", 5);
+        let mut ngrams = indexed.clone();
+        ngrams.drop_index();
+
+        for ngram in indexed.to_vec() {
+            assert_eq!(indexed.position(ngram), ngrams.position(ngram));
+        }
+        assert!(ngrams.ngram("xyz").is_none());
+
+        ngrams.retain(|ngram| !ngram.starts_with('_'));
+        ngrams.truncate(3);
+        assert_eq!(Some(2), ngrams.position(ngrams.to_vec()[2]));
+    }
+
+    #[test]
+    fn lengths() {
+        let text = "Hello World, hello código";
+        let ngrams = Ngrams::new_with_options(
+            text,
+            2..4,
+            Boundaries::All,
+            TokenMode::Char,
+            &Default::default(),
+        );
+
+        assert!(ngrams
+            .to_vec()
+            .iter()
+            .all(|n| (2..4).contains(&n.chars().count())));
+        assert_eq!(Some(2), ngrams.ngram("_he").map(|n| n.score()));
+
+        let ascii = Ngrams::parse_lengths("hello world", 3..5);
+        let unicode = Ngrams::parse_lengths("hello world\u{e9}", 3..5);
+        assert!(ascii.keys().all(|n| unicode.contains_key(n)));
+        assert!(ascii.keys().all(|n| (3..5).contains(&n.chars().count())));
+
+        let mut builder = NgramsBuilder::new(5).with_lengths(2..4);
+        builder.feed("Hello Wor");
+        builder.feed("ld, hello código");
+        assert_eq!(ngrams.to_vec(), builder.finish().to_vec());
+    }
+
+    #[test]
+    fn split() {
+        let groups = Ngrams::split_and_group_by_ngrams("Él, 1 año", 1, 3);
+        assert_eq!(vec!["é", "l", "a", "ñ", "o"], groups[0]);
+        assert_eq!(
+            vec!["_é", "él", "l_", "_1", "1_", "_a", "añ", "ño"],
+            groups[1]
+        );
+
+        assert_eq!(groups.concat(), Ngrams::split("Él, 1 año", 1, 3));
+    }
+
+    #[test]
+    fn stopwords() {
+        let stopwords = ["the", "of"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            " house   cards",
+            remove_stopwords("The house of the cards", &stopwords)
+        );
+
+        let text = "The house of the cards";
+        let ngrams = Ngrams::new_with_options(
+            text,
+            1..5,
+            Boundaries::All,
+            TokenMode::Mixed,
+            &stopwords,
+        );
+        assert!(ngrams.ngram(" the ").is_none());
+        assert!(ngrams.ngram(" house cards ").is_some());
+
+        let mut builder = NgramsBuilder::new(5)
+            .with_mode(TokenMode::Mixed)
+            .with_stopwords(stopwords);
+        builder.feed("The house of ");
+        builder.feed("the cards");
+        assert_eq!(ngrams.to_vec(), builder.finish().to_vec());
+    }
+
+    #[test]
+    fn divergences() {
+        let a = Ngrams::from_sorted(vec![
+            ("a".to_string(), 3),
+            ("b".to_string(), 1),
+        ]);
+        let b = Ngrams::from_sorted(vec![("c".to_string(), 2)]);
+
+        assert_eq!(0.0, a.jensen_shannon(&a));
+        assert_eq!(0.0, a.chi_square(&a));
+        assert!((a.jensen_shannon(&b) - 2_f64.ln()).abs() < 1e-9);
+        assert!((a.chi_square(&b) - 2.0).abs() < 1e-9);
+        assert_eq!(0, Metric::JensenShannon.distance(&a, &a));
+        assert_eq!(2_000_000, Metric::ChiSquare.distance(&a, &b));
+
+        assert!((a.cosine_similarity(&a) - 1.0).abs() < 1e-9);
+        assert_eq!(0.0, a.cosine_similarity(&b));
+        assert_eq!(0, Metric::Cosine.distance(&a, &a));
+        assert_eq!(
+            Metric::Cosine.max_distance(&a),
+            Metric::Cosine.distance(&a, &b)
+        );
+        let c = Ngrams::from_sorted(vec![("a".to_string(), 1)]);
+        // a = (3, 1), c = (1, 0): cos = 3 / sqrt(10)
+        assert!((a.cosine_similarity(&c) - 3.0 / 10_f64.sqrt()).abs() < 1e-9);
+
+        // ranked ngrams, as loaded from a model file, fall back to rank counts
+        let ranked: Ngrams = vec!["a", "b"].into();
+        assert!(ranked.jensen_shannon(&a) > 0.0);
+        assert!(ranked.jensen_shannon(&a) < ranked.jensen_shannon(&b));
+    }
+
+    #[test]
+    fn inline_ngrams() {
+        let text = "hi there, this is a test. Something else needs to be done.";
+        let ngrams = Ngrams::new_with_mode(text, 5, TokenMode::Mixed);
+        assert!(ngrams
+            .ngrams
+            .iter()
+            .all(|ngram| !ngram.0 .0.is_heap_allocated()));
+
+        let json = serde_json::to_string(&ngrams).expect("serialize");
+        let loaded: Ngrams = serde_json::from_str(&json).expect("load");
+        assert_eq!(ngrams.to_vec(), loaded.to_vec());
+    }
+
+    #[test]
+    fn distance_with_cutoff() {
+        let text = "hi there, this is a test. Something else needs to be done.";
+        let ngrams = Ngrams::new(text, 5);
+        let other = Ngrams::new("the house of the cards", 5);
+        let distance = ngrams.distance(&other);
+
+        assert_eq!(
+            Some(distance),
+            ngrams.distance_with_cutoff(&other, distance)
+        );
+        assert_eq!(None, ngrams.distance_with_cutoff(&other, distance - 1));
+        assert_eq!(
+            Some(ngrams.distance_with_penalty(&other, 10)),
+            ngrams.distance_with_penalty_and_cutoff(&other, 10, u64::MAX)
+        );
+    }
+
+    #[test]
+    fn diacritics() {
+        assert_eq!(
+            "cancion uber Francais",
+            fold_diacritics("canción über Français")
+        );
+        assert_eq!("plain", fold_diacritics("plain"));
+
+        let mut builder = NgramsBuilder::new(5).with_diacritic_folding(true);
+        builder.feed("cancio");
+        builder.feed("\u{301}n");
+        assert_eq!(
+            Ngrams::new("cancion", 5).to_vec(),
+            builder.finish().to_vec()
+        );
+    }
+
+    #[cfg(feature = "segmentation")]
+    #[test]
+    fn dictionary_segmentation() {
+        assert_eq!(vec!["ทุก", "สอง", "สัปดาห์"], super::words("ทุกสองสัปดาห์"));
+        assert_eq!(vec!["hello", "world"], super::words("hello, world"));
+    }
+}
+
+#[cfg(test)]
+mod properties {
+    use crate::ngram::{Ngrams, MISSING_PENALTY};
+    use proptest::prelude::*;
+
+    /// Texts mixing scripts, punctuation and whitespace
+    fn text() -> impl Strategy<Value = String> {
+        "[a-zA-Zñéü абв日本 .,!\n]{0,200}"
+    }
+
+    /// A ranked profile of distinct ngrams
+    fn profile() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::btree_set("[a-e_]{1,3}", 0..40)
+            .prop_map(|set| set.into_iter().collect::<Vec<_>>())
+            .prop_shuffle()
+    }
+
+    fn ngrams(profile: &[String]) -> Ngrams {
+        Ngrams::from(profile.iter().map(|s| s.as_str()).collect::<Vec<_>>())
+    }
+
+    proptest! {
+        #[test]
+        fn ranks_are_stable(text in text()) {
+            let (ngrams, again) = (Ngrams::new(&text, 5), Ngrams::new(&text, 5));
+            prop_assert_eq!(ngrams.to_vec(), again.to_vec());
+
+            let scores = (0..ngrams.len())
+                .map(|pos| ngrams.get_by_position(pos).unwrap().score())
+                .collect::<Vec<_>>();
+            prop_assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        }
+
+        #[test]
+        fn position_matches_get_by_position(text in text()) {
+            let ngrams = Ngrams::new(&text, 5);
+            for pos in 0..ngrams.len() {
+                let ngram = ngrams.get_by_position(pos).unwrap().ngram();
+                prop_assert_eq!(Some(pos), ngrams.position(ngram));
+            }
+            prop_assert!(ngrams.get_by_position(ngrams.len()).is_none());
+        }
+
+        #[test]
+        fn distance_to_itself_is_minimum(a in profile(), b in profile()) {
+            let (a, b) = (ngrams(&a), ngrams(&b));
+            let len = a.len() as u64;
+            let minimum = len * len.saturating_sub(1) / 2;
+
+            prop_assert_eq!(minimum, a.distance(&a));
+            prop_assert!(a.distance(&b) >= minimum);
+        }
+
+        #[test]
+        fn distance_symmetry(a in profile(), b in profile()) {
+            // profiles ranking the same ngrams are as far from each other either way
+            let mut shuffled = a.clone();
+            shuffled.reverse();
+            let (a, b, shuffled) = (ngrams(&a), ngrams(&b), ngrams(&shuffled));
+            prop_assert_eq!(a.distance(&shuffled), shuffled.distance(&a));
+
+            // ngrams missing from the other profile are counted the same way both ways
+            let missing = |x: &Ngrams, y: &Ngrams| {
+                (x.distance_with_penalty(y, MISSING_PENALTY)
+                    - x.distance_with_penalty(y, 0))
+                    / MISSING_PENALTY
+            };
+            prop_assert_eq!(
+                missing(&a, &b) + b.len() as u64,
+                missing(&b, &a) + a.len() as u64
+            );
+        }
+
+        #[test]
+        fn serialization_keeps_order(text in text(), counts: bool) {
+            let mut ngrams = Ngrams::new(&text, 5);
+            ngrams.serialize_counts(counts);
+
+            let json = serde_json::to_string(&ngrams).unwrap();
+            let decoded: Ngrams = serde_json::from_str(&json).unwrap();
+            prop_assert_eq!(ngrams.to_vec(), decoded.to_vec());
+            prop_assert_eq!(json, serde_json::to_string(&decoded).unwrap());
+        }
+    }
 }
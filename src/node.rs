@@ -0,0 +1,74 @@
+//! # Node
+//!
+//! Native Node.js bindings (N-API), built as a `cdylib` with the `napi` feature. Models are
+//! loaded either from a path or from a `Buffer`.
+
+// The napi macros generate undocumented glue code
+#![allow(missing_docs)]
+use crate::category::{from_bytes, load, Categories};
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+/// Candidate category and its distance (the lower the better)
+#[napi(object)]
+pub struct Candidate {
+    /// Category name
+    pub category: String,
+    /// Distance to the text
+    pub distance: f64,
+}
+
+/// Text classifier exposed to Node.js
+#[napi]
+pub struct TextCat {
+    categories: Categories<String>,
+}
+
+fn to_napi_error(e: crate::Error) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+#[napi]
+impl TextCat {
+    /// Creates a classifier from the content of a model file
+    #[napi(constructor)]
+    pub fn new(model: Buffer) -> napi::Result<TextCat> {
+        Ok(TextCat {
+            categories: from_bytes(&model).map_err(to_napi_error)?,
+        })
+    }
+
+    /// Creates a classifier from a model file
+    #[napi(factory)]
+    pub fn load(path: String) -> napi::Result<TextCat> {
+        Ok(TextCat {
+            categories: load(&path).map_err(to_napi_error)?,
+        })
+    }
+
+    /// Returns the category of a text, or null if it is unknown or ambiguous
+    #[napi]
+    pub fn detect(&self, text: String) -> Option<String> {
+        self.categories.get_category(&text)
+    }
+
+    /// Returns the candidate categories of a text, best first
+    #[napi]
+    pub fn candidates(&self, text: String) -> Vec<Candidate> {
+        self.categories
+            .get_categories(&text)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(category, distance)| Candidate {
+                category,
+                distance: distance as f64,
+            })
+            .collect()
+    }
+
+    /// Returns the names of all the categories in the model
+    #[napi]
+    pub fn categories(&self) -> Vec<String> {
+        self.categories.categories()
+    }
+}
@@ -0,0 +1,489 @@
+//! # Normalize
+//!
+//! Preprocessing of texts before their ngrams are extracted. A `Pipeline` of normalizers is
+//! stored with a model (see `Categories::set_normalization`), so texts are normalized the
+//! same way to learn and to classify.
+use crate::ngram::fold_diacritics;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{is_nfc, is_nfkc, UnicodeNormalization};
+
+/// Transformation of a text before its ngrams are extracted
+pub trait Normalizer {
+    /// Returns the normalized text, borrowed when there is nothing to change
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str>;
+
+    /// Returns the offset where markup which is not closed yet starts, or the length of the
+    /// text. Texts read in chunks (see `NgramsBuilder::feed`) are held from there until
+    /// more of them arrives, as the normalizer needs the whole markup to remove it.
+    fn unfinished(&self, text: &str) -> usize {
+        text.len()
+    }
+}
+
+/// Unicode canonical composition (NFC): "e" followed by a combining acute accent becomes
+/// "é", as most texts are typed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nfc;
+
+impl Normalizer for Nfc {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if is_nfc(text) {
+            return Cow::Borrowed(text);
+        }
+
+        Cow::Owned(text.nfc().collect())
+    }
+}
+
+/// Unicode compatibility composition (NFKC): on top of NFC, ligatures, full-width and
+/// other presentation forms become their plain letters ("ﬁ" becomes "fi")
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nfkc;
+
+impl Normalizer for Nfkc {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if is_nfkc(text) {
+            return Cow::Borrowed(text);
+        }
+
+        Cow::Owned(text.nfkc().collect())
+    }
+}
+
+/// Removes accents and other diacritics, see `fold_diacritics`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripDiacritics;
+
+impl Normalizer for StripDiacritics {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        fold_diacritics(text)
+    }
+}
+
+/// Removes digits of every script
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripDigits;
+
+impl Normalizer for StripDigits {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.chars().any(char::is_numeric) {
+            return Cow::Borrowed(text);
+        }
+
+        Cow::Owned(text.chars().filter(|c| !c.is_numeric()).collect())
+    }
+}
+
+/// Removes URLs ("https://...", "www...") and email addresses. Whitespace around them is
+/// kept, so the words before and after them stay apart.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripUrls;
+
+impl StripUrls {
+    /// Returns true if a word, as split by whitespace, is a URL or an email address
+    fn is_url(word: &str) -> bool {
+        let email = word.split_once('@').map_or(false, |(user, host)| {
+            !user.is_empty() && host.contains('.')
+        });
+        let www = word
+            .get(..4)
+            .map_or(false, |start| start.eq_ignore_ascii_case("www."));
+
+        email || www || word.contains("://")
+    }
+}
+
+impl Normalizer for StripUrls {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.split_whitespace().any(StripUrls::is_url) {
+            return Cow::Borrowed(text);
+        }
+
+        Cow::Owned(
+            text.split_inclusive(char::is_whitespace)
+                .map(|piece| {
+                    let word = piece.trim_end_matches(char::is_whitespace);
+                    if StripUrls::is_url(word) {
+                        &piece[word.len()..]
+                    } else {
+                        piece
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Replaces HTML tags and comments with a space. Only the markup is removed, the text of
+/// every element is kept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripHtml;
+
+impl StripHtml {
+    /// Returns true if the `<` starting the text opens a tag or a comment
+    fn opens_tag(text: &str) -> bool {
+        text[1..]
+            .chars()
+            .next()
+            .map_or(false, |c| c.is_ascii_alphabetic() || "/!?".contains(c))
+    }
+
+    /// Returns the length of the tag at the start of the text, None if it is not a tag
+    fn tag_len(text: &str) -> Option<usize> {
+        if !StripHtml::opens_tag(text) {
+            return None;
+        }
+
+        if text.starts_with("<!--") {
+            return text.find("-->").map(|end| end + 3);
+        }
+
+        text.find('>').map(|end| end + 1)
+    }
+}
+
+impl Normalizer for StripHtml {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.contains('<') {
+            return Cow::Borrowed(text);
+        }
+
+        let mut stripped = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('<') {
+            stripped.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match StripHtml::tag_len(rest) {
+                Some(len) => {
+                    stripped.push(' ');
+                    rest = &rest[len..];
+                }
+                None => {
+                    stripped.push('<');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        stripped.push_str(rest);
+
+        Cow::Owned(stripped)
+    }
+
+    fn unfinished(&self, text: &str) -> usize {
+        let mut offset = 0;
+        while let Some(start) = text[offset..].find('<') {
+            let tag = &text[offset + start..];
+            match StripHtml::tag_len(tag) {
+                Some(len) => offset += start + len,
+                // a `<` ending the text may open a tag once more text arrives
+                None if tag.len() == 1 || StripHtml::opens_tag(tag) => {
+                    return offset + start;
+                }
+                None => offset += start + 1,
+            }
+        }
+
+        text.len()
+    }
+}
+
+/// Removes @mentions ("@textcat"). An `@` inside a word, as in email addresses, is
+/// kept (see `StripUrls`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripMentions;
+
+impl Normalizer for StripMentions {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        strip_marked_words(text, '@', true)
+    }
+}
+
+/// Removes the `#` of hashtags, "#python" becomes "python": the tag itself is often a
+/// word of the text
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripHashtags;
+
+impl Normalizer for StripHashtags {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        strip_marked_words(text, '#', false)
+    }
+}
+
+/// Removes the `marker` of the words starting with it, and the word itself when
+/// `with_word` is set. Markers following a letter or a digit are not at the start of a
+/// word and are kept.
+fn strip_marked_words(
+    text: &str,
+    marker: char,
+    with_word: bool,
+) -> Cow<'_, str> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut stripped = String::new();
+    let mut copied = 0;
+    let mut previous = None;
+
+    for (pos, c) in text.char_indices() {
+        let starts_word = text[pos + c.len_utf8()..]
+            .chars()
+            .next()
+            .map_or(false, is_word);
+        if c == marker && starts_word && !previous.map_or(false, is_word) {
+            stripped.push_str(&text[copied..pos]);
+            copied = pos + c.len_utf8();
+            if with_word {
+                copied += text[copied..]
+                    .find(|c: char| !is_word(c))
+                    .unwrap_or(text.len() - copied);
+            }
+        }
+        previous = Some(c);
+    }
+
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    stripped.push_str(&text[copied..]);
+    Cow::Owned(stripped)
+}
+
+/// Replaces emoji, with their modifiers and joiners, with a space
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripEmoji;
+
+impl StripEmoji {
+    /// Returns true for emoji and the characters which only modify them
+    fn is_emoji(c: char) -> bool {
+        matches!(c,
+            '\u{1F000}'..='\u{1FAFF}' // pictographs, emoticons, flags and skin tones
+            | '\u{2600}'..='\u{27BF}' // miscellaneous symbols and dingbats
+            | '\u{2B00}'..='\u{2BFF}' // arrows and shapes (⭐)
+            | '\u{FE0E}' | '\u{FE0F}' // text and emoji presentation selectors
+            | '\u{200D}' // zero width joiner
+            | '\u{20E3}' // combining keycap
+            | '\u{E0020}'..='\u{E007F}') // tags of subdivision flags
+    }
+}
+
+impl Normalizer for StripEmoji {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.chars().any(StripEmoji::is_emoji) {
+            return Cow::Borrowed(text);
+        }
+
+        let mut stripped = String::with_capacity(text.len());
+        let mut in_emoji = false;
+        for c in text.chars() {
+            if StripEmoji::is_emoji(c) {
+                if !in_emoji {
+                    stripped.push(' ');
+                }
+                in_emoji = true;
+            } else {
+                stripped.push(c);
+                in_emoji = false;
+            }
+        }
+
+        Cow::Owned(stripped)
+    }
+}
+
+/// Built-in normalizer, as stored with a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    /// See `Nfc`
+    Nfc,
+    /// See `Nfkc`
+    Nfkc,
+    /// See `StripDiacritics`
+    StripDiacritics,
+    /// See `StripDigits`
+    StripDigits,
+    /// See `StripUrls`
+    StripUrls,
+    /// See `StripHtml`
+    StripHtml,
+    /// See `StripMentions`
+    StripMentions,
+    /// See `StripHashtags`
+    StripHashtags,
+    /// See `StripEmoji`
+    StripEmoji,
+}
+
+impl Normalization {
+    /// Implementation of the normalizer
+    fn normalizer(&self) -> &'static dyn Normalizer {
+        match self {
+            Normalization::Nfc => &Nfc,
+            Normalization::Nfkc => &Nfkc,
+            Normalization::StripDiacritics => &StripDiacritics,
+            Normalization::StripDigits => &StripDigits,
+            Normalization::StripUrls => &StripUrls,
+            Normalization::StripHtml => &StripHtml,
+            Normalization::StripMentions => &StripMentions,
+            Normalization::StripHashtags => &StripHashtags,
+            Normalization::StripEmoji => &StripEmoji,
+        }
+    }
+}
+
+impl Normalizer for Normalization {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.normalizer().normalize(text)
+    }
+
+    fn unfinished(&self, text: &str) -> usize {
+        self.normalizer().unfinished(text)
+    }
+}
+
+/// Normalizers applied in order, e.g. HTML tags are best stripped before URLs, as
+/// `<a href="...">` would otherwise hide the URL. Empty by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pipeline(Vec<Normalization>);
+
+impl Pipeline {
+    /// Creates an empty pipeline, which leaves texts as they are
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Pipeline for social media posts: URLs, email addresses, @mentions, the `#` of
+    /// hashtags and emoji are removed, they are the same in every language and skew the
+    /// profiles of short texts
+    pub fn social() -> Pipeline {
+        Pipeline::new()
+            .then(Normalization::StripUrls)
+            .then(Normalization::StripMentions)
+            .then(Normalization::StripHashtags)
+            .then(Normalization::StripEmoji)
+    }
+
+    /// Appends a normalizer to the pipeline
+    pub fn then(mut self, normalization: Normalization) -> Pipeline {
+        self.0.push(normalization);
+        self
+    }
+
+    /// Normalizers of the pipeline, in order
+    pub fn steps(&self) -> &[Normalization] {
+        &self.0
+    }
+
+    /// Returns true if the pipeline has no normalizer
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<Normalization> for Pipeline {
+    fn from_iter<I: IntoIterator<Item = Normalization>>(iter: I) -> Self {
+        Pipeline(iter.into_iter().collect())
+    }
+}
+
+impl Normalizer for Pipeline {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.0
+            .iter()
+            .fold(Cow::Borrowed(text), |text, step| match text {
+                Cow::Borrowed(text) => step.normalize(text),
+                Cow::Owned(text) => {
+                    Cow::Owned(step.normalize(&text).into_owned())
+                }
+            })
+    }
+
+    /// The earliest unfinished markup of any step, found in the text before any step
+    /// changes it
+    fn unfinished(&self, text: &str) -> usize {
+        self.0
+            .iter()
+            .map(|step| step.unfinished(text))
+            .min()
+            .unwrap_or(text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizers() {
+        assert_eq!("é", Nfc.normalize("e\u{301}"));
+        assert_eq!("fi2", Nfkc.normalize("ﬁ²"));
+        assert_eq!("cancion", StripDiacritics.normalize("canción"));
+        assert_eq!("room ", StripDigits.normalize("room 42"));
+        assert_eq!(
+            "see  or  now",
+            StripUrls
+                .normalize("see https://example.com or me@example.org now")
+        );
+        assert_eq!("www", StripUrls.normalize("www"));
+        assert_eq!(
+            " hello  world  a < b",
+            StripHtml.normalize("<p>hello <!-- x -->world</p> a < b")
+        );
+        assert!(matches!(StripHtml.normalize("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn unfinished() {
+        assert_eq!(6, StripHtml.unfinished("hello <span class=\"a"));
+        assert_eq!(9, StripHtml.unfinished("<b>x</b> <!-- a > b"));
+        assert_eq!(2, StripHtml.unfinished("a <"));
+        assert_eq!(8, StripHtml.unfinished("a < b<b>"));
+        assert_eq!(4, Nfc.unfinished("a <b"));
+
+        let pipeline = Pipeline::new()
+            .then(Normalization::StripUrls)
+            .then(Normalization::StripHtml);
+        assert_eq!(2, pipeline.unfinished("a <b"));
+        assert_eq!(4, Pipeline::new().unfinished("a <b"));
+    }
+
+    #[test]
+    fn social() {
+        assert_eq!(
+            "hi , see me@example.org",
+            StripMentions.normalize("hi @crodas, see me@example.org")
+        );
+        assert_eq!("I ❤ rust #", StripHashtags.normalize("I ❤ #rust #"));
+        assert_eq!("great day  ", StripEmoji.normalize("great👍🏽day 👨‍👩‍👧"));
+        assert_eq!(
+            "que buen partido     vamos",
+            Pipeline::social().normalize(
+                "que buen partido @usuario 🎉⚽ https://t.co/x #vamos"
+            )
+        );
+        assert!(matches!(
+            Pipeline::social().normalize("hola"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn pipeline() {
+        let pipeline = Pipeline::new()
+            .then(Normalization::StripHtml)
+            .then(Normalization::StripUrls)
+            .then(Normalization::StripDigits);
+        assert_eq!(
+            " go     to  room ",
+            pipeline.normalize("<b>go</b> <i>www.x.com</i> to  room 101")
+        );
+        assert_eq!("text", Pipeline::new().normalize("text"));
+
+        let json = serde_json::to_string(&pipeline).unwrap();
+        assert_eq!("[\"strip_html\",\"strip_urls\",\"strip_digits\"]", json);
+        assert_eq!(pipeline, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,168 @@
+//! # Sampling
+//!
+//! Parts of a long text which are classified instead of the whole text (see
+//! `Categories::set_sampling`). Profiling a multi-megabyte document is slow, and its
+//! boilerplate (headers, licenses, tables) weighs as much as the prose.
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Which parts of a text are classified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampling {
+    /// The whole text, the default
+    Full,
+
+    /// The first `bytes` bytes
+    Head {
+        /// Bytes kept
+        bytes: usize,
+    },
+
+    /// `count` chunks of `size` bytes evenly spaced from the start to the end of the text
+    Chunks {
+        /// Number of chunks
+        count: usize,
+        /// Bytes of each chunk
+        size: usize,
+    },
+
+    /// `count` chunks of `size` bytes at random positions, which only depend on the seed
+    /// and the length of the text
+    Random {
+        /// Number of chunks
+        count: usize,
+        /// Bytes of each chunk
+        size: usize,
+        /// Seed of the positions
+        seed: u64,
+    },
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling::Full
+    }
+}
+
+impl Sampling {
+    /// Returns true for the default strategy
+    pub fn is_full(&self) -> bool {
+        *self == Sampling::Full
+    }
+
+    /// Returns an error message if the strategy keeps nothing
+    pub(crate) fn check(&self) -> Option<&'static str> {
+        match *self {
+            Sampling::Head { bytes: 0 } => Some("sampling 0 bytes"),
+            Sampling::Chunks { count, size }
+            | Sampling::Random { count, size, .. }
+                if count == 0 || size == 0 =>
+            {
+                Some("sampling empty chunks")
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the parts of the text to classify, the chunks joined by new lines. Chunks
+    /// are moved to the closest character boundaries, and texts no longer than what the
+    /// strategy keeps are returned whole.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let (count, size) = match *self {
+            Sampling::Full => return text.into(),
+            Sampling::Head { bytes } => {
+                return text[..floor_char_boundary(text, bytes)].into()
+            }
+            Sampling::Chunks { count, size } => (count, size),
+            Sampling::Random { count, size, .. } => (count, size),
+        };
+        if text.len() <= count.saturating_mul(size) {
+            return text.into();
+        }
+
+        let last = text.len() - size;
+        let starts = match *self {
+            Sampling::Random { seed, .. } => {
+                let mut rng = SplitMix64(seed);
+                let mut starts = (0..count)
+                    .map(|_| (rng.next() % (last as u64 + 1)) as usize)
+                    .collect::<Vec<_>>();
+                starts.sort_unstable();
+                starts
+            }
+            _ if count == 1 => vec![0],
+            _ => (0..count).map(|i| i * last / (count - 1)).collect(),
+        };
+
+        let mut sampled = String::with_capacity(count * (size + 1));
+        for start in starts {
+            let start = floor_char_boundary(text, start);
+            let end = floor_char_boundary(text, start + size);
+            if !sampled.is_empty() {
+                sampled.push('\n');
+            }
+            sampled.push_str(&text[start..end]);
+        }
+
+        sampled.into()
+    }
+}
+
+/// Largest character boundary of the text not after `index`
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Small deterministic generator, random positions must not change between platforms
+/// or versions
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling() {
+        let text = "aaaa bbbb cccc dddd eeee";
+        assert_eq!(text, Sampling::Full.apply(text));
+        assert_eq!("aaaa", Sampling::Head { bytes: 4 }.apply(text));
+        assert_eq!("a", Sampling::Head { bytes: 2 }.apply("añ"));
+        assert_eq!(text, Sampling::Head { bytes: 100 }.apply(text));
+
+        let chunks = Sampling::Chunks { count: 3, size: 4 };
+        assert_eq!("aaaa\ncccc\neeee", chunks.apply(text));
+        assert_eq!("ab", chunks.apply("ab"));
+
+        let random = Sampling::Random {
+            count: 2,
+            size: 4,
+            seed: 7,
+        };
+        let sampled = random.apply(text);
+        assert_eq!(sampled, random.apply(text));
+        assert_eq!(9, sampled.len());
+        assert!(sampled.split('\n').all(|chunk| text.contains(chunk)));
+
+        assert!(Sampling::Head { bytes: 0 }.check().is_some());
+        assert!(Sampling::Chunks { count: 0, size: 4 }.check().is_some());
+        assert!(chunks.check().is_none());
+    }
+}
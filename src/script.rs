@@ -0,0 +1,218 @@
+//! # Script
+//!
+//! Unicode scripts (writing systems) of texts. Categories are tagged with the scripts of
+//! their profile, and `Categories::get_categories` skips the categories which are not
+//! written in the dominant script of the text: a Chinese text is not compared with Italian,
+//! which is faster and avoids absurd answers.
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Share of the letters of a profile a script needs to tag its category
+const MIN_PROFILE_SHARE: f32 = 0.1;
+
+/// Number of scripts
+const SCRIPTS: usize = Script::Han as usize + 1;
+
+/// A writing system
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Script {
+    /// Latin alphabet
+    Latin,
+    /// Greek alphabet
+    Greek,
+    /// Cyrillic alphabet
+    Cyrillic,
+    /// Armenian alphabet
+    Armenian,
+    /// Georgian alphabet
+    Georgian,
+    /// Hebrew abjad
+    Hebrew,
+    /// Arabic abjad
+    Arabic,
+    /// Devanagari (Hindi, Marathi, Nepali...)
+    Devanagari,
+    /// Bengali
+    Bengali,
+    /// Gurmukhi (Punjabi)
+    Gurmukhi,
+    /// Gujarati
+    Gujarati,
+    /// Tamil
+    Tamil,
+    /// Telugu
+    Telugu,
+    /// Kannada
+    Kannada,
+    /// Malayalam
+    Malayalam,
+    /// Thai
+    Thai,
+    /// Hangul (Korean)
+    Hangul,
+    /// Hiragana (Japanese)
+    Hiragana,
+    /// Katakana (Japanese)
+    Katakana,
+    /// Han ideographs (Chinese, Japanese kanji)
+    Han,
+}
+
+impl Script {
+    /// Every script, in order
+    const ALL: [Script; SCRIPTS] = [
+        Script::Latin,
+        Script::Greek,
+        Script::Cyrillic,
+        Script::Armenian,
+        Script::Georgian,
+        Script::Hebrew,
+        Script::Arabic,
+        Script::Devanagari,
+        Script::Bengali,
+        Script::Gurmukhi,
+        Script::Gujarati,
+        Script::Tamil,
+        Script::Telugu,
+        Script::Kannada,
+        Script::Malayalam,
+        Script::Thai,
+        Script::Hangul,
+        Script::Hiragana,
+        Script::Katakana,
+        Script::Han,
+    ];
+
+    /// Returns the script of a letter, None for anything else (digits, punctuation,
+    /// symbols) and for letters of other scripts
+    pub fn of(c: char) -> Option<Script> {
+        if !c.is_alphabetic() {
+            return None;
+        }
+
+        let script = match c as u32 {
+            0x41..=0x5a | 0x61..=0x7a => Script::Latin,
+            0xaa | 0xba | 0xc0..=0x24f => Script::Latin,
+            0x1e00..=0x1eff | 0x2c60..=0x2c7f | 0xa720..=0xa7ff => {
+                Script::Latin
+            }
+            0x370..=0x3ff | 0x1f00..=0x1fff => Script::Greek,
+            0x400..=0x52f | 0x1c80..=0x1c8f => Script::Cyrillic,
+            0x2de0..=0x2dff | 0xa640..=0xa69f => Script::Cyrillic,
+            0x530..=0x58f => Script::Armenian,
+            0x10a0..=0x10ff | 0x1c90..=0x1cbf | 0x2d00..=0x2d2f => {
+                Script::Georgian
+            }
+            0x590..=0x5ff => Script::Hebrew,
+            0x600..=0x6ff | 0x750..=0x77f | 0x8a0..=0x8ff => Script::Arabic,
+            0xfb50..=0xfdff | 0xfe70..=0xfeff => Script::Arabic,
+            0x900..=0x97f => Script::Devanagari,
+            0x980..=0x9ff => Script::Bengali,
+            0xa00..=0xa7f => Script::Gurmukhi,
+            0xa80..=0xaff => Script::Gujarati,
+            0xb80..=0xbff => Script::Tamil,
+            0xc00..=0xc7f => Script::Telugu,
+            0xc80..=0xcff => Script::Kannada,
+            0xd00..=0xd7f => Script::Malayalam,
+            0xe00..=0xe7f => Script::Thai,
+            0x1100..=0x11ff | 0x3130..=0x318f | 0xac00..=0xd7af => {
+                Script::Hangul
+            }
+            0x3040..=0x309f => Script::Hiragana,
+            0x30a0..=0x30ff | 0x31f0..=0x31ff | 0xff66..=0xff9f => {
+                Script::Katakana
+            }
+            0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xf900..=0xfaff => Script::Han,
+            0x20000..=0x2fa1f => Script::Han,
+            _ => return None,
+        };
+
+        Some(script)
+    }
+}
+
+/// Number of letters of each script
+fn count<'a, I>(texts: I) -> [usize; SCRIPTS]
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut counts = [0; SCRIPTS];
+    for c in texts.into_iter().flat_map(str::chars) {
+        if let Some(script) = Script::of(c) {
+            counts[script as usize] += 1;
+        }
+    }
+
+    counts
+}
+
+/// Returns the script most letters of the text are written in, None if it has no letters
+/// of a known script. It is a single pass over the text.
+pub fn dominant(text: &str) -> Option<Script> {
+    let counts = count(Some(text));
+    let (best, letters) = counts
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(&a.0)))?;
+
+    if *letters == 0 {
+        None
+    } else {
+        Some(Script::ALL[best])
+    }
+}
+
+/// Scripts a category profile is written in: those of at least a tenth of its letters
+pub(crate) fn of_profile<'a, I>(ngrams: I) -> Vec<Script>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let counts = count(ngrams);
+    let letters = counts.iter().sum::<usize>();
+
+    Script::ALL
+        .iter()
+        .zip(counts.iter())
+        .filter(|(_, n)| {
+            **n > 0 && **n as f32 >= MIN_PROFILE_SHARE * letters as f32
+        })
+        .map(|(script, _)| *script)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dominant_script() {
+        for (i, script) in Script::ALL.iter().enumerate() {
+            assert_eq!(i, *script as usize);
+        }
+
+        assert_eq!(Some(Script::Latin), dominant("¿Qué tal, señor?"));
+        assert_eq!(Some(Script::Cyrillic), dominant("Привет, как дела? OK"));
+        assert_eq!(Some(Script::Han), dominant("我们今天去公园"));
+        assert_eq!(Some(Script::Arabic), dominant("مرحبا بالعالم"));
+        assert_eq!(Some(Script::Hangul), dominant("안녕하세요"));
+        assert_eq!(None, dominant("123 !? 😀"));
+
+        assert_eq!(
+            vec![Script::Hiragana, Script::Han],
+            of_profile(vec!["今日", "は", "いい", "天気", "です"])
+        );
+        assert_eq!(vec![Script::Latin], of_profile(vec!["_th", "he_", "1"]));
+    }
+}
@@ -0,0 +1,102 @@
+//! # Threshold
+//!
+//! How close to the best category the other categories must be for a text to be ambiguous
+//! (see `Categories::set_threshold`). A relative margin suits texts of any length, as
+//! distances grow with the text, and an absolute gap gives the same margin to every text.
+use serde::{Deserialize, Serialize};
+
+/// Default relative margin, 3% of the best distance
+const DEFAULT_RELATIVE: f32 = 0.03;
+
+/// Margin within which the categories close to the best one are candidates too
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Threshold {
+    /// Categories within this fraction (greater than 0 and lower than 1) of the best
+    /// distance, 3% by default
+    Relative(f32),
+
+    /// Categories whose distance is at most this much greater than the best distance
+    AbsoluteGap(u64),
+
+    /// Only the best categories: texts are ambiguous only when categories are tied
+    None,
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Threshold::Relative(DEFAULT_RELATIVE)
+    }
+}
+
+impl From<f32> for Threshold {
+    fn from(threshold: f32) -> Self {
+        Threshold::Relative(threshold)
+    }
+}
+
+impl Threshold {
+    /// Returns true for the default threshold
+    pub fn is_default(&self) -> bool {
+        *self == Threshold::default()
+    }
+
+    /// Returns an error message if the threshold is out of its valid range
+    pub(crate) fn check(&self) -> Option<&'static str> {
+        match *self {
+            Threshold::Relative(margin) if !(margin > 0.0 && margin < 1.0) => {
+                Some("the relative threshold has to be between 0 and 1")
+            }
+            _ => None,
+        }
+    }
+
+    /// Distance a candidate must be below of to be within the threshold of the best one.
+    /// It is always above the best distance, so the best candidate is kept even when its
+    /// distance is 0 (an exact match with the frequency metrics).
+    pub(crate) fn cutoff(&self, best: u64) -> u64 {
+        match *self {
+            Threshold::Relative(margin) => {
+                let cutoff = ((1.0 + margin) * best as f32) as u64;
+                cutoff.max(best.saturating_add(1))
+            }
+            Threshold::AbsoluteGap(gap) => {
+                best.saturating_add(gap).saturating_add(1)
+            }
+            Threshold::None => best.saturating_add(1),
+        }
+    }
+
+    /// Same threshold with a margin `times` as large
+    pub(crate) fn widened(&self, times: u32) -> Threshold {
+        match *self {
+            Threshold::Relative(margin) => {
+                Threshold::Relative(margin * times as f32)
+            }
+            Threshold::AbsoluteGap(gap) => {
+                Threshold::AbsoluteGap(gap.saturating_mul(times as u64))
+            }
+            Threshold::None => Threshold::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutoffs() {
+        assert_eq!(150, Threshold::Relative(0.5).cutoff(100));
+        assert_eq!(200, Threshold::Relative(0.5).widened(2).cutoff(100));
+        assert_eq!(111, Threshold::AbsoluteGap(10).cutoff(100));
+        assert_eq!(101, Threshold::None.cutoff(100));
+        assert_eq!(1, Threshold::default().cutoff(0));
+        assert_eq!(u64::MAX, Threshold::AbsoluteGap(10).cutoff(u64::MAX));
+
+        assert!(Threshold::Relative(0.5).check().is_none());
+        assert!(Threshold::Relative(0.0).check().is_some());
+        assert!(Threshold::Relative(f32::NAN).check().is_some());
+        assert!(Threshold::AbsoluteGap(0).check().is_none());
+    }
+}
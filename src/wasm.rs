@@ -0,0 +1,84 @@
+//! # WASM
+//!
+//! JavaScript bindings, built with `wasm-pack build -- --features wasm`. The generated package
+//! ships TypeScript definitions and can be published to npm as is.
+//!
+//! `TextCat.embedded()` classifies texts with the language model embedded in the library
+//! (see `default`), without fetching a model.
+use crate::category::{from_bytes, Categories};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+/** A candidate category for a text */
+export interface Detection {
+    /** Category (language) name */
+    language: string;
+    /** Category code, as stored in the model */
+    code: string;
+    /** 1.0 for the best candidate, lower for the others */
+    confidence: number;
+}
+"#;
+
+/// Candidate category, see `Detection` in the TypeScript definitions
+#[derive(Serialize)]
+struct Detection {
+    language: String,
+    code: String,
+    confidence: f32,
+}
+
+/// Text classifier exposed to JavaScript
+#[wasm_bindgen]
+pub struct TextCat {
+    categories: Categories<String>,
+}
+
+#[wasm_bindgen]
+impl TextCat {
+    /// Creates a classifier from the content of a model file
+    #[wasm_bindgen(constructor)]
+    pub fn new(model: &[u8]) -> Result<TextCat, JsError> {
+        Ok(TextCat {
+            categories: from_bytes(model)?,
+        })
+    }
+
+    /// Creates a classifier with the embedded language model (see the module
+    /// documentation)
+    pub fn embedded() -> Result<TextCat, JsError> {
+        Ok(TextCat {
+            categories: crate::default::textcat().categories(),
+        })
+    }
+
+    /// Returns the candidate categories for a text, best first
+    #[wasm_bindgen(unchecked_return_type = "Detection[]")]
+    pub fn detect(&self, text: &str) -> Result<JsValue, JsError> {
+        let candidates =
+            self.categories.get_categories(text).unwrap_or_default();
+        let best = candidates.first().map_or(0, |(_, distance)| *distance);
+
+        let detections = candidates
+            .into_iter()
+            .map(|(name, distance)| Detection {
+                code: name.clone(),
+                language: name,
+                confidence: if distance == 0 {
+                    1.0
+                } else {
+                    best as f32 / distance as f32
+                },
+            })
+            .collect::<Vec<_>>();
+
+        Ok(serde_wasm_bindgen::to_value(&detections)?)
+    }
+
+    /// Returns the names of all the categories in the model
+    pub fn categories(&self) -> Vec<String> {
+        self.categories.categories()
+    }
+}